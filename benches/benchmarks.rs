@@ -10,9 +10,13 @@
 extern crate criterion;
 
 use apollo::attacks;
-use apollo::{Bitboard, Color, MoveGenerator, MoveVec, Position, Square};
+use apollo::eval::Score;
+use apollo::search::{NodeKind, TableEntry, TranspositionTable};
+use apollo::{perft, Bitboard, Color, MoveGenerator, MoveVec, Position, Square};
 use criterion::black_box;
 use criterion::Criterion;
+use hashbrown::HashMap;
+use std::sync::RwLock;
 
 fn queen_attacks(square: Square, occ: Bitboard) -> Bitboard {
     attacks::queen_attacks(square, occ)
@@ -22,6 +26,25 @@ fn knight_attacks(square: Square) -> Bitboard {
     attacks::knight_attacks(square)
 }
 
+/// Every position reachable in two plies from the starting position, along with a legal move from
+/// each of them.
+fn two_ply_positions() -> Vec<(Position, apollo::Move)> {
+    let start = Position::from_start_position();
+    let mut positions = vec![];
+    for &first in start.legal_moves().iter() {
+        let mut child = start.clone();
+        child.apply_move(first);
+        for &second in child.legal_moves().iter() {
+            let mut grandchild = child.clone();
+            grandchild.apply_move(second);
+            let reply = grandchild.legal_moves()[0];
+            positions.push((grandchild, reply));
+        }
+    }
+
+    positions
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("queen attacks f5 empty board", |b| {
         b.iter(|| queen_attacks(black_box(Square::F5), Bitboard::none()))
@@ -51,6 +74,57 @@ fn criterion_benchmark(c: &mut Criterion) {
                 .unwrap();
         b.iter(|| black_box(&pos).squares_attacking(black_box(Color::Black), black_box(Square::F3)))
     });
+
+    // The perft benchmarks exercise the move generator, legality testing, and move application
+    // all at once. The node counts are checked so that a benchmark can't silently get faster by
+    // doing less work.
+    c.bench_function("perft 3 start", |b| {
+        let pos = Position::from_start_position();
+        b.iter(|| assert_eq!(8902, perft(black_box(&pos), black_box(3), true)))
+    });
+
+    c.bench_function("perft 3 kiwipete", |b| {
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        b.iter(|| assert_eq!(97862, perft(black_box(&pos), black_box(3), true)))
+    });
+
+    // Transposition table probes, against a lock-protected hash map like the one that the
+    // transposition table used to be, for comparison.
+    c.bench_function("tt probe", |b| {
+        let positions = two_ply_positions();
+        let table = TranspositionTable::new();
+        for (pos, mov) in &positions {
+            table.record_cut(pos, *mov, 3, Score::Evaluated(0.0));
+        }
+        b.iter(|| {
+            for (pos, _) in &positions {
+                black_box(table.query(black_box(pos), |entry| entry.map(|e| e.depth)));
+            }
+        })
+    });
+
+    c.bench_function("tt probe hashmap baseline", |b| {
+        let positions = two_ply_positions();
+        let table = RwLock::new(HashMap::new());
+        for (pos, mov) in &positions {
+            let entry = TableEntry {
+                zobrist_key: pos.zobrist_hash(),
+                best_move: Some(*mov),
+                depth: 3,
+                node: NodeKind::Cut(Score::Evaluated(0.0)),
+            };
+            table.write().unwrap().insert(entry.zobrist_key, entry);
+        }
+        b.iter(|| {
+            for (pos, _) in &positions {
+                let table = table.read().unwrap();
+                black_box(table.get(&black_box(pos).zobrist_hash()).map(|e| e.depth));
+            }
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -5,6 +5,7 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use crate::attacks;
 use crate::bitboard::Bitboard;
 use crate::bitboard::{
     BB_FILES, BB_FILE_A, BB_FILE_B, BB_FILE_C, BB_FILE_D, BB_FILE_E, BB_FILE_F, BB_FILE_G,
@@ -13,7 +14,14 @@ use crate::bitboard::{
 use crate::move_generator::{MoveGenerator, MoveVec};
 use crate::moves::Move;
 use crate::position::Position;
-use crate::types::{Color, File, FILES};
+use crate::types::{Color, File, Piece, PieceKind, Square, FILES};
+
+// Weights for each attack that a piece makes into the enemy king zone. Queens are the most
+// dangerous attackers by far.
+const KNIGHT_KING_ATTACK_WEIGHT: u32 = 2;
+const BISHOP_KING_ATTACK_WEIGHT: u32 = 2;
+const ROOK_KING_ATTACK_WEIGHT: u32 = 3;
+const QUEEN_KING_ATTACK_WEIGHT: u32 = 5;
 
 /// Provider of common board analyses upon a static position. It is suitable for use in board
 /// evaluators, where analysis queries can be aggressively cached when evaluating a single,
@@ -132,19 +140,179 @@ impl<'a> Analysis<'a> {
         assert!(pos.side_to_move() == color);
         let mut move_vec = MoveVec::default();
         let gen = MoveGenerator::new();
-        gen.generate_moves(&pos, &mut move_vec);
+        gen.generate_legal_moves(&pos, &mut move_vec);
+        move_vec.len() as u32
+    }
+
+    /// Returns the set of squares attacked by the given color's pawns.
+    pub fn pawn_attacks(&self, color: Color) -> Bitboard {
+        let pawns = self.pos.pawns(color).bits();
+        let (west, east) = match color {
+            Color::White => (pawns << 7, pawns << 9),
+            Color::Black => (pawns >> 9, pawns >> 7),
+        };
+
+        // Shifting west wraps pawns on the A file around to the H file, and vice versa.
+        Bitboard::from_bits(west).and(!BB_FILE_H) | Bitboard::from_bits(east).and(!BB_FILE_A)
+    }
+
+    /// Returns the set of squares that the given color's pieces can usefully move to: every
+    /// square that isn't occupied by a friendly piece or attacked by an enemy pawn.
+    pub fn mobility_area(&self, color: Color) -> Bitboard {
+        !self.pos.pieces(color) & !self.pawn_attacks(color.toggle())
+    }
+
+    /// Returns the number of squares in the mobility area attacked by the given color's minor and
+    /// major pieces. Unlike `mobility`, this doesn't give credit for moves that walk into pawn
+    /// attacks.
+    pub fn piece_mobility(&self, color: Color) -> u32 {
+        [
+            PieceKind::Knight,
+            PieceKind::Bishop,
+            PieceKind::Rook,
+            PieceKind::Queen,
+        ]
+        .iter()
+        .map(|&kind| self.piece_mobility_of_kind(color, kind))
+        .sum()
+    }
+
+    /// Returns the number of squares in the mobility area attacked by the given color's pieces of
+    /// the given kind. A square attacked by two pieces counts once for each of them.
+    pub fn piece_mobility_of_kind(&self, color: Color, kind: PieceKind) -> u32 {
+        let area = self.mobility_area(color);
+        let occupancy = self.pos.occupied();
+        let piece = Piece::new(kind, color);
         let mut count = 0;
-        for &mov in &move_vec {
-            if pos.is_legal_given_pseudolegal(mov) {
-                count += 1;
-            }
+        for sq in self.pos.pieces_of_kind(color, kind) {
+            count += (piece.attacks(sq, occupancy) & area).count();
         }
 
         count
     }
+
+    /// Returns the squares around the given color's king: the king's own square and every square
+    /// that it attacks.
+    pub fn king_zone(&self, color: Color) -> Bitboard {
+        let mut zone = Bitboard::none();
+        if let Some(king) = self.pos.kings(color).first() {
+            zone = attacks::king_attacks(king);
+            zone.set(king);
+        }
+
+        zone
+    }
+
+    /// Returns the number of the attacker's knights, bishops, rooks and queens that attack any
+    /// square in the given zone, along with the weighted number of attacks that they make into
+    /// it. Each attacked square counts once per attacking piece, weighted by the kind of piece.
+    pub fn king_zone_attacks(&self, zone: Bitboard, attacker: Color) -> (u32, u32) {
+        let occupancy = self.pos.occupied();
+        let pieces = self.pos.knights(attacker)
+            | self.pos.bishops(attacker)
+            | self.pos.rooks(attacker)
+            | self.pos.queens(attacker);
+
+        let mut count = 0;
+        let mut weight = 0;
+        for sq in pieces {
+            let piece = self.pos.piece_at(sq).unwrap();
+            let zone_attacks = (piece.attacks(sq, occupancy) & zone).count();
+            if zone_attacks == 0 {
+                continue;
+            }
+
+            let piece_weight = match piece.kind {
+                PieceKind::Knight => KNIGHT_KING_ATTACK_WEIGHT,
+                PieceKind::Bishop => BISHOP_KING_ATTACK_WEIGHT,
+                PieceKind::Rook => ROOK_KING_ATTACK_WEIGHT,
+                PieceKind::Queen => QUEEN_KING_ATTACK_WEIGHT,
+                _ => unreachable!(),
+            };
+            count += 1;
+            weight += piece_weight * zone_attacks;
+        }
+
+        (count, weight)
+    }
+}
+
+/// Returns a move that checkmates the opponent immediately, if there is one.
+pub fn mate_in_one(pos: &Position) -> Option<Move> {
+    pos.legal_moves()
+        .into_iter()
+        .find(|&mov| pos.gives_checkmate(mov))
+}
+
+/// Returns every absolute pin against the given color, as pairs of the pinned piece's square and
+/// the pinning piece's square.
+pub fn find_pins(pos: &Position, color: Color) -> Vec<(Square, Square)> {
+    let kings = pos.kings(color);
+    let occupancy = pos.occupied();
+    let enemy_sliders =
+        pos.bishops(color.toggle()) | pos.rooks(color.toggle()) | pos.queens(color.toggle());
+
+    let mut pins = vec![];
+    for pinned in pos.pieces(color) & !kings {
+        // A piece is pinned by a slider if taking it off the board would let the slider attack
+        // the king.
+        let mut without_pinned = occupancy;
+        without_pinned.unset(pinned);
+        for pinner in enemy_sliders {
+            let piece = pos.piece_at(pinner).unwrap();
+            if (piece.attacks(pinner, occupancy) & kings).empty()
+                && !(piece.attacks(pinner, without_pinned) & kings).empty()
+            {
+                pins.push((pinned, pinner));
+            }
+        }
+    }
+
+    pins
+}
+
+/// Returns every fork made by the given color's pieces, as pairs of the forking piece's square and
+/// the set of pieces that it forks. A piece forks two or more enemy pieces when it attacks each of
+/// them and each of them is either more valuable than the forking piece or undefended.
+pub fn find_forks(pos: &Position, color: Color) -> Vec<(Square, Bitboard)> {
+    let occupancy = pos.occupied();
+    let mut forks = vec![];
+    for forker in pos.pieces(color) {
+        let piece = pos.piece_at(forker).unwrap();
+        let mut targets = Bitboard::none();
+        for target in piece.attacks(forker, occupancy) & pos.pieces(color.toggle()) {
+            let target_piece = pos.piece_at(target).unwrap();
+            if target_piece.kind.value() > piece.kind.value()
+                || pos.squares_attacking(color.toggle(), target).empty()
+            {
+                targets.set(target);
+            }
+        }
+
+        if targets.count() >= 2 {
+            forks.push((forker, targets));
+        }
+    }
+
+    forks
 }
 
-fn adjacent_files(file: File) -> Bitboard {
+/// Returns the set of enemy pieces that the given color attacks and that nothing defends.
+pub fn find_hanging_pieces(pos: &Position, color: Color) -> Bitboard {
+    let mut hanging = Bitboard::none();
+    for target in pos.pieces(color.toggle()) & !pos.kings(color.toggle()) {
+        if !pos.squares_attacking(color, target).empty()
+            && pos.squares_attacking(color.toggle(), target).empty()
+        {
+            hanging.set(target);
+        }
+    }
+
+    hanging
+}
+
+/// Returns the files on either side of the given file.
+pub(crate) fn adjacent_files(file: File) -> Bitboard {
     match file {
         File::A => BB_FILE_B,
         File::B => BB_FILE_A.or(BB_FILE_C),
@@ -159,8 +327,9 @@ fn adjacent_files(file: File) -> Bitboard {
 
 #[cfg(test)]
 mod tests {
-    use super::Analysis;
+    use super::{find_forks, find_hanging_pieces, find_pins, mate_in_one, Analysis};
 
+    use crate::moves::Move;
     use crate::position::Position;
     use crate::types::{Color, Square};
 
@@ -215,6 +384,56 @@ mod tests {
         assert_eq!(12, analysis.mobility(Color::Black));
     }
 
+    #[test]
+    fn pawn_attacks_do_not_wrap() {
+        let pos = Position::from_fen("8/p6p/8/8/8/8/P6P/8 w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        let white = analysis.pawn_attacks(Color::White);
+        assert_eq!(2, white.count());
+        assert!(white.test(Square::B3));
+        assert!(white.test(Square::G3));
+
+        let black = analysis.pawn_attacks(Color::Black);
+        assert_eq!(2, black.count());
+        assert!(black.test(Square::B6));
+        assert!(black.test(Square::G6));
+    }
+
+    #[test]
+    fn piece_mobility_excludes_pawn_attacks() {
+        // The knight on a1 can only go to b3 and c2, both of which are covered by Black's pawns.
+        let pos = Position::from_fen("4k3/8/8/8/p7/1p1p4/8/N3K3 w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        assert!(!analysis.mobility_area(Color::White).test(Square::B3));
+        assert!(!analysis.mobility_area(Color::White).test(Square::C2));
+        assert_eq!(0, analysis.piece_mobility(Color::White));
+
+        // Without the pawns, both squares count.
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        assert_eq!(2, analysis.piece_mobility(Color::White));
+    }
+
+    #[test]
+    fn king_zone_attacks() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/2B5/8/4K3 w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        let zone = analysis.king_zone(Color::Black);
+        assert_eq!(6, zone.count());
+        assert!(zone.test(Square::G8) && zone.test(Square::H7));
+
+        // The bishop on c3 bears on g7, but it's only a bishop.
+        let (count, weight) = analysis.king_zone_attacks(zone, Color::White);
+        assert_eq!((1, 2), (count, weight));
+
+        // A queen on h5 hits f7 and h7 as well, which is far more dangerous.
+        let pos = Position::from_fen("6k1/5ppp/8/7Q/8/2B5/8/4K3 w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        let (count, with_queen) = analysis.king_zone_attacks(zone, Color::White);
+        assert_eq!(2, count);
+        assert!(with_queen >= weight + 10, "{}", with_queen);
+    }
+
     #[test]
     fn isolated_pawn_smoke() {
         let pos = Position::from_fen("8/8/8/8/8/3P1P2/6P1/8 w - - 0 1").unwrap();
@@ -223,4 +442,54 @@ mod tests {
         assert_eq!(1, isolated_pawns.count());
         assert!(isolated_pawns.test(Square::D3));
     }
+
+    #[test]
+    fn pins() {
+        let pos = Position::from_fen("8/8/3q4/8/8/3B4/3K4/8 w - - 0 1").unwrap();
+        assert_eq!(
+            vec![(Square::D3, Square::D6)],
+            find_pins(&pos, Color::White)
+        );
+        assert!(find_pins(&pos, Color::Black).is_empty());
+    }
+
+    #[test]
+    fn pins_ignore_checks_and_double_blocks() {
+        // The queen on d6 is already checking the king, and the rook on h2 is blocked twice, by
+        // the knight on f2 and the bishop on e2.
+        let pos = Position::from_fen("8/8/3q4/8/8/8/3KBN1r/8 w - - 0 1").unwrap();
+        assert!(find_pins(&pos, Color::White).is_empty());
+    }
+
+    #[test]
+    fn knight_fork() {
+        // The knight on c7 forks the king on e8 and the rook on a8.
+        let pos = Position::from_fen("r3k3/2N5/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        let forks = find_forks(&pos, Color::White);
+        assert_eq!(1, forks.len());
+        assert_eq!(Square::C7, forks[0].0);
+        assert!(forks[0].1.test(Square::A8));
+        assert!(forks[0].1.test(Square::E8));
+    }
+
+    #[test]
+    fn hanging_pieces() {
+        // The knight on f6 is attacked by the rook and undefended, while the pawn on e5 is attacked
+        // by the bishop but defended.
+        let pos = Position::from_fen("4k3/8/3p1n2/4p3/8/8/1B6/4KR2 w - - 0 1").unwrap();
+        let hanging = find_hanging_pieces(&pos, Color::White);
+        assert_eq!(1, hanging.count());
+        assert!(hanging.test(Square::F6));
+    }
+
+    #[test]
+    fn mate_in_one_found() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(Some(Move::quiet(Square::A1, Square::A8)), mate_in_one(&pos));
+    }
+
+    #[test]
+    fn mate_in_one_quiet_position() {
+        assert_eq!(None, mate_in_one(&Position::from_start_position()));
+    }
 }
@@ -201,13 +201,142 @@ impl RayTable {
     }
 }
 
+/// A table of the attacks of a sliding piece from every square, for every arrangement of the
+/// pieces that could block it, indexed with magic bitboards.
+///
+/// Only the pieces on a square's relevant occupancy mask can block a slider on that square; pieces
+/// on the edge of the board never block anything, since there's nothing behind them. Multiplying
+/// the blockers on the mask by the square's magic number packs them into the top bits of the
+/// product, which makes them an index into the square's block of the attack table. The magic
+/// numbers were found by trial and error so that no two sets of blockers with different attacks
+/// share an index.
+struct MagicTable {
+    entries: [MagicEntry; 64],
+    attacks: Vec<Bitboard>,
+}
+
+#[derive(Copy, Clone, Default)]
+struct MagicEntry {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl MagicTable {
+    /// Builds the table for a slider with the given magic numbers, using `mask` to produce the
+    /// relevant occupancy mask for a square and `ray_attacks` to produce the attacks for a set of
+    /// blockers.
+    fn new(
+        magics: &[u64; 64],
+        mask: fn(Square) -> Bitboard,
+        ray_attacks: fn(Square, Bitboard) -> Bitboard,
+    ) -> MagicTable {
+        let mut table = MagicTable {
+            entries: [MagicEntry::default(); 64],
+            attacks: vec![],
+        };
+
+        for &sq in SQUARES.iter() {
+            let mask = mask(sq);
+            let entry = MagicEntry {
+                mask,
+                magic: magics[sq.as_index()],
+                shift: 64 - mask.count(),
+                offset: table.attacks.len(),
+            };
+
+            table.entries[sq.as_index()] = entry;
+            table
+                .attacks
+                .resize(entry.offset + (1 << mask.count()), Bitboard::none());
+
+            // Visit every subset of the mask, starting from the empty set, by counting upwards
+            // using only the bits in the mask.
+            let mut blockers = Bitboard::none();
+            loop {
+                let index = entry.offset + entry.index(blockers);
+                let attacks = ray_attacks(sq, blockers);
+                assert!(
+                    table.attacks[index].empty() || table.attacks[index] == attacks,
+                    "magic number for {} has a collision",
+                    sq
+                );
+                table.attacks[index] = attacks;
+                blockers = Bitboard::from_bits(blockers.bits().wrapping_sub(mask.bits())) & mask;
+                if blockers.empty() {
+                    break;
+                }
+            }
+        }
+
+        table
+    }
+
+    pub fn attacks(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        let entry = &self.entries[sq.as_index()];
+        self.attacks[entry.offset + entry.index(occupancy)]
+    }
+}
+
+impl MagicEntry {
+    fn index(&self, occupancy: Bitboard) -> usize {
+        ((occupancy & self.mask).bits().wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+// The tables are built the first time that they're used, so there's no need to initialize them
+// before looking up attacks.
 lazy_static! {
     static ref KING_TABLE: KingTable = KingTable::new();
     static ref PAWN_TABLE: PawnTable = PawnTable::new();
     static ref KNIGHT_TABLE: KnightTable = KnightTable::new();
     static ref RAY_TABLE: RayTable = RayTable::new();
+    static ref ROOK_TABLE: MagicTable = MagicTable::new(&ROOK_MAGICS, rook_mask, ray_rook_attacks);
+    static ref BISHOP_TABLE: MagicTable =
+        MagicTable::new(&BISHOP_MAGICS, bishop_mask, ray_bishop_attacks);
 }
 
+#[rustfmt::skip]
+const ROOK_MAGICS: [u64; 64] = [
+    0x2080002080400010, 0x00C0002001401000, 0x2100110008402002, 0x0880080081041000,
+    0x0200020020041008, 0x2300040008010012, 0x0C00283004008201, 0x0180010000407A80,
+    0x0168800080400020, 0x0010400040201000, 0x1001002001001048, 0x1001002408100100,
+    0x0801000408010012, 0x4001000209000400, 0x08A20004C8020001, 0x2002801145002280,
+    0x0080860021004200, 0x001000C009402002, 0x00B0002004002800, 0x100A808010020800,
+    0x8101010008000410, 0x0244008002000480, 0x0000040010810208, 0x2000020000448534,
+    0x4104400480008033, 0x0000810100204000, 0x0440430900200010, 0x4600240900100100,
+    0x0060080080040080, 0x0001000300080400, 0x0004084400011002, 0x0023040200008041,
+    0x0580050043002080, 0x0400804002802008, 0x0001002001004010, 0x1000200901001000,
+    0x4410800801800C00, 0xA012003806001004, 0x0020100104008802, 0x0004808402000041,
+    0x0010400170898000, 0x0080500020004004, 0x1040408012020020, 0x8010040008004040,
+    0x2001080100110004, 0x0000020004008080, 0x0021010810040002, 0x0800008C43020024,
+    0x0000800021005100, 0x0070201040008080, 0x0000D04282006A00, 0x0010014400080240,
+    0x0001080110050100, 0x0012000810240600, 0x0402000801040200, 0x028100108A004100,
+    0x0050800300102045, 0x8208210040120882, 0x8010600101183441, 0x020B000910006045,
+    0x0241001002480005, 0x0081000400880241, 0x0000009008024124, 0x0048122980410402,
+];
+
+#[rustfmt::skip]
+const BISHOP_MAGICS: [u64; 64] = [
+    0x0848020822040013, 0x8010A40085821200, 0x0008008430840822, 0x0808048108040000,
+    0x1304042100008104, 0x5001012010204023, 0x81048801B8200420, 0x200A008084012000,
+    0x0040102001042084, 0x840A505042428020, 0x0000700102202920, 0x44101C0C10800002,
+    0x0040040422000000, 0x0180020802090202, 0x4020020811041202, 0x000104308C042000,
+    0x4140661002424400, 0x0028012008010460, 0x0188062102002A00, 0x0014004840102008,
+    0x0105000290400002, 0x8001022200410400, 0x104A041918013446, 0x008A000082008238,
+    0x04A0060008100430, 0x0008220008820801, 0x2508041208005010, 0x4008080200202020,
+    0x2441001013004000, 0x0030008060407000, 0x4008108000420800, 0x0012021050290100,
+    0x0210080482200500, 0xCC01112048100480, 0x0020402806500440, 0x00048E0080580080,
+    0x0040102020020080, 0x0028010440080807, 0x4601041108008800, 0x8040810E04104200,
+    0x901210110400088A, 0xA003080212081050, 0x00C1004048401004, 0x900000A014400800,
+    0x0008021040405401, 0x4020008206002090, 0x0004190424030100, 0x0424008A02026250,
+    0x8004088250900040, 0x1C00430088A04200, 0x0001020094040001, 0x8040210020880061,
+    0x2010040450442032, 0x0800840850044001, 0x0004040802140004, 0x0004080A04222020,
+    0x8088802110022000, 0x1081A10416114400, 0x0205010A24060820, 0x0000000720411080,
+    0x1008000208430400, 0x580C026028810840, 0x802020441020A110, 0x12C0022401020018,
+];
+
 fn positive_ray_attacks(sq: Square, occupancy: Bitboard, dir: Direction) -> Bitboard {
     debug_assert!(dir.as_vector() > 0);
     let attacks = RAY_TABLE.attacks(sq.as_index(), dir);
@@ -246,12 +375,38 @@ fn rank_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
         | negative_ray_attacks(sq, occupancy, Direction::West)
 }
 
+/// Bishop attacks computed by scanning each ray for its first blocker, which is how the magic
+/// tables are built.
+fn ray_bishop_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
+    diagonal_attacks(sq, occupancy) | antidiagonal_attacks(sq, occupancy)
+}
+
+/// Rook attacks computed by scanning each ray for its first blocker, which is how the magic tables
+/// are built.
+fn ray_rook_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
+    file_attacks(sq, occupancy) | rank_attacks(sq, occupancy)
+}
+
+/// The squares whose occupants could block a rook on the given square. Pieces at the ends of the
+/// rook's rays are left out, since there is nothing behind them to block.
+fn rook_mask(sq: Square) -> Bitboard {
+    let file = file_attacks(sq, Bitboard::none()) & !(BB_RANK_1 | BB_RANK_8);
+    let rank = rank_attacks(sq, Bitboard::none()) & !(BB_FILE_A | BB_FILE_H);
+    file | rank
+}
+
+/// The squares whose occupants could block a bishop on the given square. Every bishop ray ends on
+/// the edge of the board, so the edges are left out.
+fn bishop_mask(sq: Square) -> Bitboard {
+    ray_bishop_attacks(sq, Bitboard::none()) & !(BB_RANK_1 | BB_RANK_8 | BB_FILE_A | BB_FILE_H)
+}
+
 pub fn pawn_attacks(sq: Square, color: Color) -> Bitboard {
     PAWN_TABLE.attacks(sq, color)
 }
 
 pub fn bishop_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
-    diagonal_attacks(sq, occupancy) | antidiagonal_attacks(sq, occupancy)
+    BISHOP_TABLE.attacks(sq, occupancy)
 }
 
 pub fn knight_attacks(sq: Square) -> Bitboard {
@@ -259,7 +414,7 @@ pub fn knight_attacks(sq: Square) -> Bitboard {
 }
 
 pub fn rook_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
-    file_attacks(sq, occupancy) | rank_attacks(sq, occupancy)
+    ROOK_TABLE.attacks(sq, occupancy)
 }
 
 pub fn queen_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
@@ -269,3 +424,129 @@ pub fn queen_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
 pub fn king_attacks(sq: Square) -> Bitboard {
     KING_TABLE.attacks(sq)
 }
+
+/// Returns the squares strictly between two squares that share a rank, file, or diagonal, or an
+/// empty bitboard if they don't share one.
+pub fn between(a: Square, b: Square) -> Bitboard {
+    const DIRECTIONS: [Direction; 8] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    for &dir in &DIRECTIONS {
+        let ray = RAY_TABLE.attacks(a.as_index(), dir);
+        if ray.test(b) {
+            // The ray from `a` runs through `b` and continues past it along the ray from `b`.
+            let mut segment = ray & !RAY_TABLE.attacks(b.as_index(), dir);
+            segment.unset(b);
+            return segment;
+        }
+    }
+
+    Bitboard::none()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::{
+        between, bishop_attacks, knight_attacks, ray_bishop_attacks, ray_rook_attacks, rook_attacks,
+    };
+    use crate::bitboard::Bitboard;
+    use crate::types::{Square, SQUARES};
+
+    #[test]
+    fn knight_attacks_without_initialization() {
+        // Nothing in this test initializes the attack tables ahead of time.
+        let attacks = knight_attacks(Square::G1);
+        let expected: Vec<Square> = attacks.iter().collect();
+        assert_eq!(vec![Square::E2, Square::F3, Square::H3], expected);
+        assert_eq!(8, knight_attacks(Square::D4).count());
+    }
+
+    #[test]
+    fn rook_attacks_blocked() {
+        let mut occupancy = Bitboard::none();
+        occupancy.set(Square::A4);
+        let attacks = rook_attacks(Square::A1, occupancy);
+        assert!(attacks.test(Square::A4));
+        assert!(!attacks.test(Square::A5));
+        assert!(attacks.test(Square::H1));
+        assert_eq!(10, attacks.count());
+    }
+
+    #[test]
+    fn between_squares() {
+        let squares = |board: Bitboard| board.iter().collect::<Vec<_>>();
+        assert_eq!(
+            vec![Square::B1, Square::C1, Square::D1],
+            squares(between(Square::A1, Square::E1))
+        );
+        assert_eq!(
+            vec![Square::D4, Square::C5],
+            squares(between(Square::E3, Square::B6))
+        );
+        assert_eq!(6, between(Square::A1, Square::H8).count());
+        assert_eq!(
+            between(Square::A1, Square::H8),
+            between(Square::H8, Square::A1)
+        );
+        assert!(between(Square::A1, Square::B1).empty());
+        assert!(between(Square::A1, Square::B3).empty());
+    }
+
+    #[test]
+    fn bishop_attacks_blocked() {
+        let mut occupancy = Bitboard::none();
+        occupancy.set(Square::F6);
+        occupancy.set(Square::B2);
+        let attacks = bishop_attacks(Square::D4, occupancy);
+        assert!(attacks.test(Square::F6));
+        assert!(!attacks.test(Square::G7));
+        assert!(attacks.test(Square::B2));
+        assert!(!attacks.test(Square::A1));
+        assert!(attacks.test(Square::A7));
+        assert!(attacks.test(Square::G1));
+        assert_eq!(10, attacks.count());
+    }
+
+    #[test]
+    fn magic_attacks_match_ray_attacks() {
+        let mut rng = StdRng::seed_from_u64(0x3a61c);
+        for &sq in SQUARES.iter() {
+            for _ in 0..200 {
+                // Sparse occupancies are the most common on a real board, and block the fewest
+                // rays, so mix them in with denser ones.
+                let occupancy = match rng.gen_range(0, 3) {
+                    0 => rng.gen::<u64>(),
+                    1 => rng.gen::<u64>() & rng.gen::<u64>(),
+                    _ => rng.gen::<u64>() & rng.gen::<u64>() & rng.gen::<u64>(),
+                };
+
+                let occupancy = Bitboard::from_bits(occupancy);
+                assert_eq!(
+                    ray_rook_attacks(sq, occupancy),
+                    rook_attacks(sq, occupancy),
+                    "rook on {} with {:?}",
+                    sq,
+                    occupancy
+                );
+                assert_eq!(
+                    ray_bishop_attacks(sq, occupancy),
+                    bishop_attacks(sq, occupancy),
+                    "bishop on {} with {:?}",
+                    sq,
+                    occupancy
+                );
+            }
+        }
+    }
+}
@@ -17,8 +17,9 @@ use apollo::book::OpeningBook;
 use apollo::eval::ShannonEvaluator;
 use apollo::search::{CsvDataRecorder, Searcher};
 use apollo::uci::UciServer;
-use apollo::{perft, Position};
+use apollo::{perft_in_pool, Position};
 use clap::{App, Arg, ArgMatches, SubCommand};
+use rayon::ThreadPoolBuilder;
 
 fn main() {
     env_logger::init();
@@ -42,6 +43,14 @@ fn main() {
                         .short("-d")
                         .long("--depth")
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .help("Number of threads to count nodes with, one per CPU by default")
+                        .value_name("THREADS")
+                        .short("-j")
+                        .long("--threads")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
@@ -84,6 +93,16 @@ fn main() {
 fn run_perft(matches: &ArgMatches) -> ! {
     let fen = matches.value_of("FEN").unwrap();
     let depth = value_t_or_exit!(matches, "depth", u32);
+    // Without a thread count, count with one thread per CPU.
+    let threads = if matches.is_present("threads") {
+        value_t_or_exit!(matches, "threads", usize)
+    } else {
+        0
+    };
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("failed to build perft thread pool");
     let pos = match Position::from_fen(fen) {
         Ok(pos) => pos,
         Err(_) => {
@@ -99,7 +118,7 @@ fn run_perft(matches: &ArgMatches) -> ! {
     println!();
     for i in 1..depth + 1 {
         let start = Instant::now();
-        let results = perft(&pos, i, true);
+        let results = perft_in_pool(&pos, i, &pool);
         let stop = Instant::now();
         let duration = stop - start;
         let nanos = duration.subsec_nanos() as u64;
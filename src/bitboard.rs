@@ -14,13 +14,14 @@
 //! A bitboard is a single 64-bit integer and it behaves like a set, using
 //! bitwise operations for the normal set operations (union, intersection,
 //! set complement, etc.).
+use arrayvec::ArrayVec;
 use num_traits::FromPrimitive;
 use std::default::Default;
 use std::fmt;
 use std::iter::Iterator;
 use std::ops;
 
-use crate::types::{self, File, Rank, Square};
+use crate::types::{self, Direction, File, Rank, Square};
 
 const RANK_MASKS: [u64; 8] = [
     0x0000_0000_0000_00FF,
@@ -47,7 +48,7 @@ const FILE_MASKS: [u64; 8] = [
 /// A Bitboard is a 64-bit integer which one bit represents one of the
 /// eight squares on the board. Bitboards are used in a variety of scenarios
 /// to represent the board itself and the pieces upon it.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Bitboard {
     bits: u64,
 }
@@ -125,6 +126,37 @@ impl Bitboard {
         self.and(Bitboard::from_bits(FILE_MASKS[file as usize]))
     }
 
+    /// Produces a bitboard with every member of this bitboard smeared in the given direction, up
+    /// to the edge of the board. For example, north-filling a pawn produces the pawn and every
+    /// square in front of it on its file.
+    pub fn fill(self, dir: Direction) -> Bitboard {
+        // This is a Kogge-Stone fill: each step doubles the distance that the set bits have been
+        // smeared. `prop` tracks the squares that can be stepped onto without wrapping around the
+        // east or west edge of the board.
+        fn step(bits: u64, shift: i32) -> u64 {
+            if shift > 0 {
+                bits << shift
+            } else {
+                bits >> -shift
+            }
+        }
+
+        let shift = dir.as_vector();
+        let mut prop = match dir {
+            Direction::North | Direction::South => !0,
+            Direction::NorthEast | Direction::East | Direction::SouthEast => !FILE_MASKS[0],
+            Direction::NorthWest | Direction::West | Direction::SouthWest => !FILE_MASKS[7],
+        };
+
+        let mut gen = self.bits;
+        gen |= prop & step(gen, shift);
+        prop &= step(prop, shift);
+        gen |= prop & step(gen, 2 * shift);
+        prop &= step(prop, 2 * shift);
+        gen |= prop & step(gen, 4 * shift);
+        Bitboard::from_bits(gen)
+    }
+
     /// Retireves the raw bits associated with this bitboard.
     pub const fn bits(self) -> u64 {
         self.bits
@@ -146,6 +178,19 @@ impl Bitboard {
     pub fn first(self) -> Option<Square> {
         self.into_iter().next()
     }
+
+    /// Collects the squares in the set represented by this bitboard, in
+    /// ascending order, into a vector on the stack.
+    pub fn squares(self) -> ArrayVec<[Square; 64]> {
+        let mut squares = ArrayVec::new();
+        let mut bits = self.bits;
+        while bits != 0 {
+            squares.push(FromPrimitive::from_u32(bits.trailing_zeros()).unwrap());
+            bits &= bits - 1;
+        }
+
+        squares
+    }
 }
 
 impl fmt::Debug for Bitboard {
@@ -226,6 +271,14 @@ impl ops::BitXorAssign for Bitboard {
     }
 }
 
+impl ops::Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        Bitboard::from_bits(!self.bits)
+    }
+}
+
 /// BitboardIterator is an iterator over squares that are set in a
 /// given bitboard.
 pub struct BitboardIterator {
@@ -289,6 +342,9 @@ pub const BB_FILE_GH: Bitboard = BB_FILE_G.or(BB_FILE_H);
 pub const BB_RANK_12: Bitboard = BB_RANK_1.or(BB_RANK_2);
 pub const BB_RANK_78: Bitboard = BB_RANK_7.or(BB_RANK_8);
 
+pub const BB_LIGHT_SQUARES: Bitboard = Bitboard::from_bits(0x55aa_55aa_55aa_55aa);
+pub const BB_DARK_SQUARES: Bitboard = Bitboard::from_bits(!0x55aa_55aa_55aa_55aa);
+
 pub const BB_RANKS: [Bitboard; 8] = [
     BB_RANK_1, BB_RANK_2, BB_RANK_3, BB_RANK_4, BB_RANK_5, BB_RANK_6, BB_RANK_7, BB_RANK_8,
 ];
@@ -399,4 +455,125 @@ mod tests {
         board.set(Square::H8);
         assert!(board.count() == 3);
     }
+
+    #[test]
+    fn squares() {
+        let mut board = Bitboard::none();
+        board.set(Square::H8);
+        board.set(Square::A2);
+        board.set(Square::B5);
+        let squares = board.squares();
+        assert_eq!(3, squares.len());
+        assert_eq!(&[Square::A2, Square::B5, Square::H8], squares.as_slice());
+        assert!(Bitboard::none().squares().is_empty());
+        assert_eq!(64, Bitboard::all().squares().len());
+    }
+
+    mod fill {
+        use super::*;
+
+        fn squares(board: Bitboard) -> Vec<Square> {
+            board.into_iter().collect()
+        }
+
+        #[test]
+        fn north() {
+            let mut board = Bitboard::none();
+            board.set(Square::E2);
+            assert_eq!(
+                vec![
+                    Square::E2,
+                    Square::E3,
+                    Square::E4,
+                    Square::E5,
+                    Square::E6,
+                    Square::E7,
+                    Square::E8
+                ],
+                squares(board.fill(Direction::North))
+            );
+        }
+
+        #[test]
+        fn south() {
+            let mut board = Bitboard::none();
+            board.set(Square::B3);
+            assert_eq!(
+                vec![Square::B1, Square::B2, Square::B3],
+                squares(board.fill(Direction::South))
+            );
+        }
+
+        #[test]
+        fn east_does_not_wrap() {
+            let mut board = Bitboard::none();
+            board.set(Square::F4);
+            assert_eq!(
+                vec![Square::F4, Square::G4, Square::H4],
+                squares(board.fill(Direction::East))
+            );
+        }
+
+        #[test]
+        fn west_does_not_wrap() {
+            let mut board = Bitboard::none();
+            board.set(Square::C4);
+            assert_eq!(
+                vec![Square::A4, Square::B4, Square::C4],
+                squares(board.fill(Direction::West))
+            );
+        }
+
+        #[test]
+        fn diagonals() {
+            let mut board = Bitboard::none();
+            board.set(Square::F2);
+            assert_eq!(
+                vec![Square::F2, Square::G3, Square::H4],
+                squares(board.fill(Direction::NorthEast))
+            );
+            assert_eq!(
+                vec![
+                    Square::F2,
+                    Square::E3,
+                    Square::D4,
+                    Square::C5,
+                    Square::B6,
+                    Square::A7
+                ],
+                squares(board.fill(Direction::NorthWest))
+            );
+            assert_eq!(
+                vec![Square::G1, Square::F2],
+                squares(board.fill(Direction::SouthEast))
+            );
+            assert_eq!(
+                vec![Square::E1, Square::F2],
+                squares(board.fill(Direction::SouthWest))
+            );
+        }
+
+        #[test]
+        fn multiple_bits() {
+            let mut board = Bitboard::none();
+            board.set(Square::A7);
+            board.set(Square::H2);
+            let filled = board.fill(Direction::North);
+            assert_eq!(9, filled.count());
+            assert!(filled.test(Square::A8));
+            assert!(filled.test(Square::H8));
+            assert!(!filled.test(Square::A6));
+        }
+    }
+
+    #[test]
+    fn square_colors() {
+        assert!(BB_DARK_SQUARES.test(Square::A1));
+        assert!(BB_LIGHT_SQUARES.test(Square::H1));
+        assert!(BB_LIGHT_SQUARES.test(Square::D1));
+        assert!(BB_DARK_SQUARES.test(Square::E1));
+        assert!(BB_LIGHT_SQUARES.test(Square::A8));
+        assert_eq!(32, BB_LIGHT_SQUARES.count());
+        assert_eq!(Bitboard::all(), BB_LIGHT_SQUARES | BB_DARK_SQUARES);
+    }
 }
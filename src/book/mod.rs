@@ -7,6 +7,8 @@
 // except according to those terms.
 
 mod opening_book;
+mod polyglot;
 mod radix_tree;
 
 pub use opening_book::{BookEntry, OpeningBook};
+pub use polyglot::PolyglotBook;
@@ -0,0 +1,223 @@
+// Copyright 2017-2020 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A reader for opening books in the Polyglot format, which is the format that most chess GUIs and
+//! engines share their books in.
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use num_traits::FromPrimitive;
+
+use crate::moves::Move;
+use crate::position::{polyglot_hash, Position};
+use crate::types::{PieceKind, Square};
+
+/// A Polyglot opening book, which maps positions to moves that can be played from them.
+pub struct PolyglotBook {
+    /// The book's entries, sorted by key so that every entry for a position is adjacent.
+    entries: Vec<PolyglotEntry>,
+}
+
+struct PolyglotEntry {
+    key: u64,
+    mov: u16,
+    weight: u16,
+}
+
+// Every Polyglot entry is 16 bytes, all of them big-endian:
+//   * 8 bytes - Polyglot key of the position
+//   * 2 bytes - move
+//   * 2 bytes - weight
+//   * 4 bytes - learning data, which isn't used
+const ENTRY_SIZE: usize = 16;
+
+impl PolyglotBook {
+    /// Reads the Polyglot book at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<PolyglotBook> {
+        let mut reader = BufReader::new(File::open(path)?);
+        PolyglotBook::read_from(&mut reader)
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<PolyglotBook> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() % ENTRY_SIZE != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated Polyglot book",
+            ));
+        }
+
+        let mut entries: Vec<PolyglotEntry> = bytes
+            .chunks(ENTRY_SIZE)
+            .map(|chunk| {
+                let mut key = [0u8; 8];
+                key.copy_from_slice(&chunk[0..8]);
+                PolyglotEntry {
+                    key: u64::from_be_bytes(key),
+                    mov: u16::from_be_bytes([chunk[8], chunk[9]]),
+                    weight: u16::from_be_bytes([chunk[10], chunk[11]]),
+                }
+            })
+            .collect();
+
+        // Books are supposed to be sorted already, but sorting here means that a sloppy book
+        // doesn't lose entries. The sort is stable, so moves keep the order the book gave them.
+        entries.sort_by_key(|entry| entry.key);
+        Ok(PolyglotBook { entries })
+    }
+
+    /// Returns the book moves for the given position, along with their weights. Moves with larger
+    /// weights are the ones that the book's author thought were better. Entries whose moves aren't
+    /// legal in the position are skipped.
+    pub fn lookup(&self, pos: &Position) -> Vec<(Move, u16)> {
+        let key = polyglot_hash(pos);
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| decode_move(pos, entry.mov).map(|mov| (mov, entry.weight)))
+            .collect()
+    }
+}
+
+/// Decodes a Polyglot move into the legal move that it refers to in the given position.
+///
+/// Polyglot moves are encoded as:
+///   * 3 bits - destination file
+///   * 3 bits - destination rank
+///   * 3 bits - source file
+///   * 3 bits - source rank
+///   * 3 bits - promotion piece (0 = none, 1 = knight, 2 = bishop, 3 = rook, 4 = queen)
+///
+/// Castling is encoded as the king capturing its own rook, so e1h1 is White castling kingside.
+fn decode_move(pos: &Position, bits: u16) -> Option<Move> {
+    let dest: Square = FromPrimitive::from_u16(bits & 0x3f)?;
+    let source: Square = FromPrimitive::from_u16((bits >> 6) & 0x3f)?;
+    let promotion = match (bits >> 12) & 0x7 {
+        0 => None,
+        1 => Some(PieceKind::Knight),
+        2 => Some(PieceKind::Bishop),
+        3 => Some(PieceKind::Rook),
+        4 => Some(PieceKind::Queen),
+        _ => return None,
+    };
+
+    let color = pos.side_to_move();
+    pos.legal_moves().into_iter().find(|&mov| {
+        if mov.source() != source {
+            return false;
+        }
+
+        if mov.is_kingside_castle() {
+            return dest == pos.kingside_castle_rook(color);
+        }
+
+        if mov.is_queenside_castle() {
+            return dest == pos.queenside_castle_rook(color);
+        }
+
+        let promoted = if mov.is_promotion() {
+            Some(mov.promotion_piece())
+        } else {
+            None
+        };
+
+        mov.destination() == dest && promoted == promotion
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::PolyglotBook;
+    use crate::moves::Move;
+    use crate::position::{polyglot_hash, Position};
+    use crate::types::Square;
+
+    fn entry(key: u64, mov: u16, weight: u16) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&key.to_be_bytes());
+        bytes.extend_from_slice(&mov.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes
+    }
+
+    /// Encodes a move the way Polyglot does, from the indices of its squares.
+    fn polyglot_move(source: Square, dest: Square) -> u16 {
+        ((source as u16) << 6) | dest as u16
+    }
+
+    #[test]
+    fn lookup() {
+        let start = Position::from_start_position();
+        let italian = Position::from_fen(
+            "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        )
+        .unwrap();
+
+        let mut book = vec![];
+        book.extend(entry(
+            0x463b96181691fc9c,
+            polyglot_move(Square::E2, Square::E4),
+            10,
+        ));
+        book.extend(entry(
+            0x463b96181691fc9c,
+            polyglot_move(Square::D2, Square::D4),
+            5,
+        ));
+        // Castling is encoded as the king capturing its own rook.
+        book.extend(entry(
+            polyglot_hash(&italian),
+            polyglot_move(Square::E1, Square::H1),
+            3,
+        ));
+        // An entry for a position that isn't looked up.
+        book.extend(entry(
+            0x823c9b50fd114196,
+            polyglot_move(Square::E7, Square::E5),
+            1,
+        ));
+
+        let path = env::temp_dir().join(format!("apollo-polyglot-{}.bin", std::process::id()));
+        fs::write(&path, &book).unwrap();
+        let book = PolyglotBook::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            vec![
+                (Move::double_pawn_push(Square::E2, Square::E4), 10),
+                (Move::double_pawn_push(Square::D2, Square::D4), 5),
+            ],
+            book.lookup(&start)
+        );
+        assert_eq!(
+            vec![(Move::kingside_castle(Square::E1, Square::G1), 3)],
+            book.lookup(&italian)
+        );
+
+        let empty = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(book.lookup(&empty).is_empty());
+    }
+
+    #[test]
+    fn truncated_book() {
+        let mut book = entry(
+            0x463b96181691fc9c,
+            polyglot_move(Square::E2, Square::E4),
+            10,
+        );
+        book.pop();
+        assert!(PolyglotBook::read_from(&mut book.as_slice()).is_err());
+    }
+}
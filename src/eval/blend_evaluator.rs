@@ -0,0 +1,120 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::eval::{BoardEvaluator, Score};
+use crate::position::Position;
+
+/// An evaluator that blends the scores of two other evaluators, useful for comparing evaluators
+/// or for layering a new evaluator on top of an existing one.
+///
+/// The blended score is a weighted average of the two evaluators' scores. If either evaluator
+/// reports a mate score, that score is used as-is.
+pub struct BlendEvaluator<A, B> {
+    first: A,
+    second: B,
+    weight: f32,
+}
+
+impl<A: BoardEvaluator, B: BoardEvaluator> BlendEvaluator<A, B> {
+    /// Constructs a new BlendEvaluator. `weight` is the weight given to the first evaluator, and
+    /// must be between zero and one; the second evaluator gets the remainder.
+    pub fn new(first: A, second: B, weight: f32) -> BlendEvaluator<A, B> {
+        assert!(
+            (0.0..=1.0).contains(&weight),
+            "blend weight must be between zero and one"
+        );
+        BlendEvaluator {
+            first,
+            second,
+            weight,
+        }
+    }
+}
+
+impl<A: BoardEvaluator, B: BoardEvaluator> Default for BlendEvaluator<A, B> {
+    fn default() -> BlendEvaluator<A, B> {
+        BlendEvaluator::new(Default::default(), Default::default(), 0.5)
+    }
+}
+
+impl<A: BoardEvaluator, B: BoardEvaluator> BoardEvaluator for BlendEvaluator<A, B> {
+    fn evaluate(&self, pos: &Position) -> Score {
+        let first = self.first.evaluate(pos) * self.weight;
+        let second = self.second.evaluate(pos) * (1.0 - self.weight);
+        first + second
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlendEvaluator;
+    use crate::eval::{BoardEvaluator, Score, ShannonEvaluator};
+    use crate::position::Position;
+
+    /// Evaluators that return the same score for every position.
+    #[derive(Default)]
+    struct One;
+
+    #[derive(Default)]
+    struct Three;
+
+    #[derive(Default)]
+    struct Mate;
+
+    impl BoardEvaluator for One {
+        fn evaluate(&self, _: &Position) -> Score {
+            Score::Evaluated(1.0)
+        }
+    }
+
+    impl BoardEvaluator for Three {
+        fn evaluate(&self, _: &Position) -> Score {
+            Score::Evaluated(3.0)
+        }
+    }
+
+    impl BoardEvaluator for Mate {
+        fn evaluate(&self, _: &Position) -> Score {
+            Score::Win(2)
+        }
+    }
+
+    #[test]
+    fn blend_with_self() {
+        let shannon = ShannonEvaluator::new();
+        let blend: BlendEvaluator<ShannonEvaluator, ShannonEvaluator> = Default::default();
+        for fen in &[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "4k3/8/8/8/8/8/8/Q3K3 w - - 0 1",
+        ] {
+            let pos = Position::from_fen(fen).unwrap();
+            assert_eq!(shannon.evaluate(&pos), blend.evaluate(&pos));
+        }
+    }
+
+    #[test]
+    fn blend_midpoint() {
+        let pos = Position::from_start_position();
+        let blend: BlendEvaluator<One, Three> = Default::default();
+        assert_eq!(Score::Evaluated(2.0), blend.evaluate(&pos));
+    }
+
+    #[test]
+    fn blend_weighted() {
+        let pos = Position::from_start_position();
+        let blend = BlendEvaluator::new(One, Three, 0.75);
+        assert_eq!(Score::Evaluated(1.5), blend.evaluate(&pos));
+    }
+
+    #[test]
+    fn blend_mate_passes_through() {
+        let pos = Position::from_start_position();
+        let blend: BlendEvaluator<One, Mate> = Default::default();
+        assert_eq!(Score::Win(2), blend.evaluate(&pos));
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::eval::{BoardEvaluator, Score, ShannonEvaluator};
+use crate::position::Position;
+
+/// An evaluator that forwards to another evaluator that's chosen at runtime, for when the choice of
+/// evaluator isn't known at compile time (e.g. when it's a UCI option). Evaluating through a
+/// DynamicEvaluator costs a dynamic dispatch per evaluation.
+///
+/// The default DynamicEvaluator forwards to a ShannonEvaluator.
+pub struct DynamicEvaluator {
+    evaluate: Box<dyn Fn(&Position) -> Score>,
+}
+
+impl DynamicEvaluator {
+    /// Constructs a new DynamicEvaluator that forwards to the given evaluator.
+    pub fn new<E: BoardEvaluator + 'static>(evaluator: E) -> DynamicEvaluator {
+        DynamicEvaluator {
+            evaluate: Box::new(move |pos| evaluator.evaluate(pos)),
+        }
+    }
+}
+
+impl Default for DynamicEvaluator {
+    fn default() -> DynamicEvaluator {
+        DynamicEvaluator::new(ShannonEvaluator::new())
+    }
+}
+
+impl BoardEvaluator for DynamicEvaluator {
+    fn evaluate(&self, pos: &Position) -> Score {
+        (self.evaluate)(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DynamicEvaluator;
+    use crate::eval::{BoardEvaluator, MaterialEvaluator, ShannonEvaluator};
+    use crate::position::Position;
+
+    #[test]
+    fn forwards() {
+        let pos = Position::from_fen("4k3/8/8/8/8/8/PPP5/RN2K3 w - - 0 1").unwrap();
+        assert_eq!(
+            ShannonEvaluator::new().evaluate(&pos),
+            DynamicEvaluator::default().evaluate(&pos)
+        );
+        assert_eq!(
+            MaterialEvaluator::new().evaluate(&pos),
+            DynamicEvaluator::new(MaterialEvaluator::new()).evaluate(&pos)
+        );
+    }
+}
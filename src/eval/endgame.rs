@@ -0,0 +1,199 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Special-case knowledge about endgames that the general evaluation gets wrong.
+use crate::eval::{EvalParams, Score};
+use crate::position::Position;
+use crate::types::{Color, PieceKind, Rank, Square, TableIndex};
+
+/// The bonus, in pawns, for each step that the losing king is driven away from the center of the
+/// board when mating a lone king.
+const EDGE_WEIGHT: f32 = 0.1;
+
+/// The bonus, in pawns, for each step that the winning king closes on the losing king when mating a
+/// lone king.
+const PROXIMITY_WEIGHT: f32 = 0.05;
+
+/// Returns an exact score, from White's perspective, for endgames that the general evaluation
+/// doesn't understand, or `None` if the position isn't one of them. Evaluators consult this before
+/// doing their own evaluation.
+///
+/// The recognized endgames are:
+///
+/// 1. Positions without enough material for either side to mate, which are draws,
+/// 2. King and pawn against king, when the defending king can't catch the pawn, which is scored as
+///    though the pawn had already promoted, and
+/// 3. A lone king against a king with a queen or rook, where the score guides the stronger side
+///    towards driving the lone king to the edge of the board.
+pub fn endgame_override(pos: &Position) -> Option<Score> {
+    if pos.is_insufficient_material() {
+        return Some(Score::Evaluated(0.0));
+    }
+
+    for &strong in &[Color::White, Color::Black] {
+        let sign = match strong {
+            Color::White => 1.0,
+            Color::Black => -1.0,
+        };
+
+        if let Some(score) = king_and_pawn(pos, strong).or_else(|| lone_king(pos, strong)) {
+            return Some(Score::Evaluated(sign * score));
+        }
+    }
+
+    None
+}
+
+/// Scores king and pawn against king from the stronger side's perspective, if the defending king
+/// is outside the square of the pawn and so can't stop it from promoting.
+fn king_and_pawn(pos: &Position, strong: Color) -> Option<f32> {
+    let weak = strong.toggle();
+    if pos.pieces(strong).count() != 2 || pos.pieces(weak).count() != 1 {
+        return None;
+    }
+
+    let pawn = pos.pawns(strong).first()?;
+    let promotion_rank = match strong {
+        Color::White => Rank::Eight,
+        Color::Black => Rank::One,
+    };
+    let promotion = Square::of(promotion_rank, pawn.file());
+
+    // The pawn's own king can get in the way of its path to promotion.
+    let strong_king = pos.kings(strong).first()?;
+    if strong_king.file() == pawn.file()
+        && distance(strong_king, promotion) < distance(pawn, promotion)
+    {
+        return None;
+    }
+
+    // A pawn on its starting rank can move two squares at once.
+    let start_rank = match strong {
+        Color::White => Rank::Two,
+        Color::Black => Rank::Seven,
+    };
+    let mut pawn_moves = distance(pawn, promotion);
+    if pawn.rank() == start_rank {
+        pawn_moves -= 1;
+    }
+
+    // If it's the defender's move, the defender gets a tempo to catch the pawn with.
+    let weak_king = pos.kings(weak).first()?;
+    let weak_moves = distance(weak_king, promotion);
+    let tempo = if pos.side_to_move() == weak { 1 } else { 0 };
+    if weak_moves <= pawn_moves + tempo {
+        return None;
+    }
+
+    Some(EvalParams::default().endgame_values.value(PieceKind::Queen))
+}
+
+/// Scores a lone king against a king and a queen or rook from the stronger side's perspective. On
+/// top of the material, the score rewards pushing the lone king to the edge of the board and
+/// bringing the kings together, which is how these endgames are won.
+fn lone_king(pos: &Position, strong: Color) -> Option<f32> {
+    let weak = strong.toggle();
+    if pos.pieces(weak).count() != 1 {
+        return None;
+    }
+
+    if pos.queens(strong).empty() && pos.rooks(strong).empty() {
+        return None;
+    }
+
+    let strong_king = pos.kings(strong).first()?;
+    let weak_king = pos.kings(weak).first()?;
+    let material = EvalParams::default().material(pos).abs();
+    let edge = center_distance(weak_king) as f32 * EDGE_WEIGHT;
+    let proximity = (14 - manhattan_distance(strong_king, weak_king)) as f32 * PROXIMITY_WEIGHT;
+    Some(material + edge + proximity)
+}
+
+/// The number of king moves that it takes to get from one square to another.
+fn distance(a: Square, b: Square) -> u32 {
+    let ranks = (a.rank().as_index() as i32 - b.rank().as_index() as i32).abs();
+    let files = (a.file().as_index() as i32 - b.file().as_index() as i32).abs();
+    ranks.max(files) as u32
+}
+
+fn manhattan_distance(a: Square, b: Square) -> u32 {
+    let ranks = (a.rank().as_index() as i32 - b.rank().as_index() as i32).abs();
+    let files = (a.file().as_index() as i32 - b.file().as_index() as i32).abs();
+    (ranks + files) as u32
+}
+
+/// How far a square is from the four center squares, as the sum of its rank and file distances.
+fn center_distance(sq: Square) -> u32 {
+    let from_center = |idx: usize| if idx < 4 { 3 - idx } else { idx - 4 };
+    (from_center(sq.rank().as_index()) + from_center(sq.file().as_index())) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::endgame_override;
+    use crate::eval::Score;
+    use crate::position::Position;
+
+    fn override_for(fen: &str) -> Option<Score> {
+        endgame_override(&Position::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn insufficient_material_is_drawn() {
+        let draw = Some(Score::Evaluated(0.0));
+        assert_eq!(draw, override_for("8/8/4k3/8/8/3KB3/8/8 w - - 0 1"));
+        assert_eq!(draw, override_for("8/8/4k3/8/8/3KN3/8/8 b - - 0 1"));
+        assert_eq!(draw, override_for("8/8/8/3k4/8/8/8/3K4 w - - 0 1"));
+
+        // Bishops on c1 and f4 both travel on dark squares.
+        assert_eq!(draw, override_for("8/8/4k3/8/5b2/3K4/8/2B5 w - - 0 1"));
+        assert_eq!(None, override_for("8/8/4k3/8/4b3/3K4/8/2B5 w - - 0 1"));
+    }
+
+    #[test]
+    fn middlegame_is_not_overridden() {
+        assert_eq!(None, endgame_override(&Position::from_start_position()));
+        assert_eq!(
+            None,
+            override_for("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+        );
+    }
+
+    #[test]
+    fn unstoppable_pawn() {
+        // Black's king is far outside the square of the a-pawn.
+        match override_for("8/8/8/P7/8/8/6k1/K7 w - - 0 1") {
+            Some(Score::Evaluated(score)) => assert!(score > 8.0, "{}", score),
+            score => panic!("unexpected override {:?}", score),
+        }
+
+        // Black's king is one step outside the square, so it's only in time if it has the move.
+        assert!(override_for("8/8/8/P3k3/8/8/8/K7 w - - 0 1").is_some());
+        assert_eq!(None, override_for("8/8/8/P3k3/8/8/8/K7 b - - 0 1"));
+
+        // The same holds for Black's pawns.
+        match override_for("k7/8/8/8/p7/8/8/6K1 b - - 0 1") {
+            Some(Score::Evaluated(score)) => assert!(score < -8.0, "{}", score),
+            score => panic!("unexpected override {:?}", score),
+        }
+    }
+
+    #[test]
+    fn lone_king_is_driven_to_the_edge() {
+        let score = |fen| match override_for(fen) {
+            Some(Score::Evaluated(score)) => score,
+            score => panic!("unexpected override {:?}", score),
+        };
+
+        let centralized = score("8/8/8/3k4/8/8/8/R3K3 w - - 0 1");
+        let cornered = score("7k/8/8/8/8/8/8/R3K3 w - - 0 1");
+        assert!(centralized > 4.0);
+        assert!(cornered > centralized);
+        assert!(score("r3k3/8/8/8/8/8/8/7K b - - 0 1") < -4.0);
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::eval::{endgame_override, BoardEvaluator, EvalParams, Score};
+use crate::position::Position;
+
+/// An evaluator that counts material and nothing else. It's far weaker than the Shannon evaluator,
+/// but it's a useful baseline when testing other evaluators and the searcher.
+pub struct MaterialEvaluator {
+    params: EvalParams,
+}
+
+impl MaterialEvaluator {
+    pub fn new() -> MaterialEvaluator {
+        MaterialEvaluator::with_params(Default::default())
+    }
+
+    /// Constructs a new evaluator that uses the given parameters.
+    pub fn with_params(params: EvalParams) -> MaterialEvaluator {
+        MaterialEvaluator { params }
+    }
+}
+
+impl Default for MaterialEvaluator {
+    fn default() -> MaterialEvaluator {
+        MaterialEvaluator::new()
+    }
+}
+
+impl BoardEvaluator for MaterialEvaluator {
+    fn evaluate(&self, pos: &Position) -> Score {
+        endgame_override(pos).unwrap_or_else(|| Score::Evaluated(self.params.material(pos)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MaterialEvaluator;
+    use crate::eval::{BoardEvaluator, Score};
+    use crate::position::Position;
+
+    #[test]
+    fn counts_material() {
+        let eval = MaterialEvaluator::new();
+        assert_eq!(
+            Score::Evaluated(0f32),
+            eval.evaluate(&Position::from_start_position())
+        );
+
+        // White is up a knight, and mobility doesn't enter into it. With a knight off of the board
+        // the game is a little closer to the endgame, so the knight is worth a little less.
+        let pos =
+            Position::from_fen("rnbqkb1r/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        match eval.evaluate(&pos) {
+            Score::Evaluated(score) => assert!(2.9 < score && score < 3.0, "{}", score),
+            score => panic!("unexpected score {}", score),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Evaluation of piece mobility: pieces that reach many squares have more ways to attack and
+//! defend than pieces hemmed in by their own army.
+use crate::analysis::Analysis;
+use crate::position::Position;
+use crate::types::{Color, PieceKind};
+
+/// The pieces whose mobility is scored, along with the bonus, in hundredths of a pawn, for each
+/// square in the mobility area that a piece of that kind attacks. Pawns and kings are left out;
+/// pawn moves are scored by the pawn structure and the king is better off staying put for most of
+/// the game.
+///
+/// Long-range pieces attack many squares even when they aren't doing much, so each of their
+/// squares is worth less than one of a knight's.
+const MOBILITY_WEIGHTS: [(PieceKind, i32); 4] = [
+    (PieceKind::Knight, 4),
+    (PieceKind::Bishop, 4),
+    (PieceKind::Rook, 2),
+    (PieceKind::Queen, 1),
+];
+
+/// Returns the mobility component of the evaluation of the given position, in pawns, from White's
+/// perspective.
+pub fn mobility(pos: &Position) -> f32 {
+    let white = weighted_mobility(pos, Color::White);
+    let black = weighted_mobility(pos, Color::Black);
+    (white - black) as f32 / 100.0
+}
+
+fn weighted_mobility(pos: &Position, color: Color) -> i32 {
+    let analysis = Analysis::new(pos);
+    MOBILITY_WEIGHTS
+        .iter()
+        .map(|&(kind, weight)| weight * analysis.piece_mobility_of_kind(color, kind) as i32)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mobility;
+    use crate::analysis::Analysis;
+    use crate::position::Position;
+    use crate::types::Color;
+
+    fn mobile_squares(pos: &Position, color: Color) -> u32 {
+        Analysis::new(pos).piece_mobility(color)
+    }
+
+    #[test]
+    fn friendly_pieces_block_mobility() {
+        // The knight on a1 can only reach c2, since its own pawn stands on b3.
+        let pos = Position::from_fen("4k3/8/8/8/8/1P6/8/N3K3 w - - 0 1").unwrap();
+        assert_eq!(1, mobile_squares(&pos, Color::White));
+
+        // Enemy pieces can be captured, so their squares count, but squares that enemy pawns guard
+        // don't.
+        let pos = Position::from_fen("4k3/8/8/8/8/1p6/8/N3K3 w - - 0 1").unwrap();
+        assert_eq!(1, mobile_squares(&pos, Color::White));
+        let pos = Position::from_fen("4k3/8/8/8/8/1b6/8/N3K3 w - - 0 1").unwrap();
+        assert_eq!(2, mobile_squares(&pos, Color::White));
+    }
+
+    #[test]
+    fn pawns_and_kings_are_not_mobile() {
+        let pos = Position::from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1").unwrap();
+        assert_eq!(0, mobile_squares(&pos, Color::White));
+        assert_eq!(0, mobile_squares(&pos, Color::Black));
+        assert_eq!(0.0, mobility(&pos));
+    }
+
+    #[test]
+    fn start_position_is_even() {
+        let pos =
+            Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(4, mobile_squares(&pos, Color::White));
+        assert_eq!(0.0, mobility(&pos));
+    }
+
+    #[test]
+    fn developed_side_is_more_mobile() {
+        // White has developed its minor pieces and put its rooks on the open files, while Black's
+        // pieces are still stuck behind their pawns.
+        let pos =
+            Position::from_fen("rnbqkbnr/ppp2ppp/4p3/8/2BP1B2/2N2N2/PP3PPP/R2QR1K1 b kq - 0 8")
+                .unwrap();
+        let white = mobile_squares(&pos, Color::White);
+        let black = mobile_squares(&pos, Color::Black);
+        assert!(white > black, "{} <= {}", white, black);
+        assert!(mobility(&pos) > 0.0, "{}", mobility(&pos));
+    }
+}
@@ -7,11 +7,29 @@
 // except according to those terms.
 use crate::position::Position;
 
+mod blend_evaluator;
+mod dynamic_evaluator;
+mod endgame;
+mod material_evaluator;
+mod mobility;
+mod params;
+mod pawn_structure;
+mod piece_square_evaluator;
 mod score;
 mod shannon_evaluator;
+mod tapered_evaluator;
 
+pub use blend_evaluator::BlendEvaluator;
+pub use dynamic_evaluator::DynamicEvaluator;
+pub use endgame::endgame_override;
+pub use material_evaluator::MaterialEvaluator;
+pub use mobility::mobility;
+pub use params::{game_phase, phase, EvalParams, PieceValues, MAX_PHASE};
+pub use pawn_structure::{passed_pawns, pawn_structure};
+pub use piece_square_evaluator::{piece_square_bonus, PieceSquareEvaluator, PieceSquareTable};
 pub use score::Score;
 pub use shannon_evaluator::ShannonEvaluator;
+pub use tapered_evaluator::TaperedEvaluator;
 
 pub trait BoardEvaluator: Default {
     fn evaluate(&self, pos: &Position) -> Score;
@@ -0,0 +1,166 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::position::Position;
+use crate::types::{Color, PieceKind};
+
+/// The value of each kind of piece, in pawns. Kings are priceless and aren't included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceValues {
+    pub pawn: f32,
+    pub knight: f32,
+    pub bishop: f32,
+    pub rook: f32,
+    pub queen: f32,
+}
+
+impl PieceValues {
+    /// Returns the value of the given kind of piece. Kings are worth nothing.
+    pub fn value(&self, kind: PieceKind) -> f32 {
+        match kind {
+            PieceKind::Pawn => self.pawn,
+            PieceKind::Knight => self.knight,
+            PieceKind::Bishop => self.bishop,
+            PieceKind::Rook => self.rook,
+            PieceKind::Queen => self.queen,
+            PieceKind::King => 0.0,
+        }
+    }
+}
+
+/// Tunable constants used by board evaluators.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalParams {
+    /// Piece values while there are still plenty of pieces on the board.
+    pub midgame_values: PieceValues,
+
+    /// Piece values once most of the pieces have been traded off. Rooks and queens gain value as
+    /// the board opens up, while knights lose value as the pawns that they feed on disappear.
+    pub endgame_values: PieceValues,
+}
+
+impl EvalParams {
+    /// Returns the value of the given kind of piece at the given game phase, as returned by
+    /// `game_phase`, by interpolating between the midgame and endgame values.
+    pub fn piece_value(&self, kind: PieceKind, phase: f32) -> f32 {
+        let midgame = self.midgame_values.value(kind);
+        let endgame = self.endgame_values.value(kind);
+        midgame * phase + endgame * (1.0 - phase)
+    }
+
+    /// Returns the material balance of the given position, in pawns, from White's perspective.
+    pub fn material(&self, pos: &Position) -> f32 {
+        let phase = game_phase(pos);
+        let mut balance = 0.0;
+        for kind in PieceKind::iter() {
+            let white = pos.pieces_of_kind(Color::White, kind).count() as f32;
+            let black = pos.pieces_of_kind(Color::Black, kind).count() as f32;
+            balance += self.piece_value(kind, phase) * (white - black);
+        }
+
+        balance
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams {
+            midgame_values: PieceValues {
+                pawn: 1.0,
+                knight: 3.0,
+                bishop: 3.0,
+                rook: 5.0,
+                queen: 9.0,
+            },
+            endgame_values: PieceValues {
+                pawn: 1.2,
+                knight: 2.8,
+                bishop: 3.2,
+                rook: 5.5,
+                queen: 9.5,
+            },
+        }
+    }
+}
+
+const KNIGHT_PHASE: u32 = 1;
+const BISHOP_PHASE: u32 = 1;
+const ROOK_PHASE: u32 = 2;
+const QUEEN_PHASE: u32 = 4;
+
+/// The phase of the starting position, as returned by `phase`.
+pub const MAX_PHASE: u32 = 4 * KNIGHT_PHASE + 4 * BISHOP_PHASE + 4 * ROOK_PHASE + 2 * QUEEN_PHASE;
+
+/// Returns how far the given position is from the endgame, based on the pieces other than pawns
+/// and kings that remain on the board. Each minor piece counts for one, each rook for two, and
+/// each queen for four, so this is `MAX_PHASE` with all of the pieces on the board and zero once
+/// only kings and pawns remain.
+pub fn phase(pos: &Position) -> u32 {
+    let mut phase = 0;
+    for &color in &[Color::White, Color::Black] {
+        phase += pos.knights(color).count() * KNIGHT_PHASE;
+        phase += pos.bishops(color).count() * BISHOP_PHASE;
+        phase += pos.rooks(color).count() * ROOK_PHASE;
+        phase += pos.queens(color).count() * QUEEN_PHASE;
+    }
+
+    // Promotions can push the phase past its starting value.
+    phase.min(MAX_PHASE)
+}
+
+/// Returns `phase` as a fraction of `MAX_PHASE`: 1.0 with all of the pieces on the board and 0.0
+/// once only kings and pawns remain.
+pub fn game_phase(pos: &Position) -> f32 {
+    phase(pos) as f32 / MAX_PHASE as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{game_phase, EvalParams, MAX_PHASE};
+    use crate::position::Position;
+    use crate::types::PieceKind;
+
+    #[test]
+    fn phase() {
+        assert_eq!(1.0, game_phase(&Position::from_start_position()));
+        let pawns_only = Position::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(0.0, game_phase(&pawns_only));
+    }
+
+    #[test]
+    fn integer_phase() {
+        assert_eq!(24, MAX_PHASE);
+        assert_eq!(MAX_PHASE, super::phase(&Position::from_start_position()));
+        let bare_kings = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(0, super::phase(&bare_kings));
+
+        // A rook and a knight apiece.
+        let pos = Position::from_fen("3rk1n1/pppppppp/8/8/8/8/PPPPPPPP/1N1RK3 w - - 0 1").unwrap();
+        assert_eq!(6, super::phase(&pos));
+    }
+
+    #[test]
+    fn endgame_rook_value() {
+        let params = EvalParams::default();
+        let midgame_rook = params.piece_value(PieceKind::Rook, 1.0);
+        assert_eq!(5.0, midgame_rook);
+
+        // With a lone rook and a couple of pawns left, the rook is worth more than in the midgame.
+        let pos = Position::from_fen("4k3/p7/8/8/8/8/P7/R3K3 w - - 0 1").unwrap();
+        assert!(params.material(&pos) > midgame_rook);
+        assert!(params.piece_value(PieceKind::Rook, game_phase(&pos)) > midgame_rook);
+
+        // Knights go the other way.
+        assert!(params.piece_value(PieceKind::Knight, game_phase(&pos)) < 3.0);
+    }
+
+    #[test]
+    fn start_position_material_is_even() {
+        let params = EvalParams::default();
+        assert_eq!(0.0, params.material(&Position::from_start_position()));
+    }
+}
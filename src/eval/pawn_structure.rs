@@ -0,0 +1,135 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Evaluation of the pawn structure: the weaknesses that pawns leave behind when they can't
+//! protect one another, and the passed pawns that nothing can stop short of promotion.
+use crate::analysis::{adjacent_files, Analysis};
+use crate::bitboard::{Bitboard, BB_RANKS};
+use crate::position::Position;
+use crate::types::{Color, TableIndex};
+
+/// The penalty, in pawns, for each pawn that shares its file with another friendly pawn.
+const DOUBLED_PAWN_PENALTY: f32 = 0.1;
+
+/// The penalty, in pawns, for each pawn with no friendly pawns on the adjacent files.
+const ISOLATED_PAWN_PENALTY: f32 = 0.15;
+
+/// The bonus, in pawns, for a passed pawn, by rank counting from its own side of the board. Pawns
+/// never stand on the first or last ranks.
+const PASSED_PAWN_BONUS: [f32; 8] = [0.0, 0.05, 0.1, 0.2, 0.35, 0.6, 1.0, 0.0];
+
+/// Returns the pawn structure component of the evaluation of the given position, in pawns, from
+/// White's perspective.
+pub fn pawn_structure(pos: &Position) -> f32 {
+    let white = pawn_structure_for(pos, Color::White);
+    let black = pawn_structure_for(pos, Color::Black);
+    white - black
+}
+
+/// Returns the given color's pawns that are passed: those with no enemy pawns ahead of them on
+/// their own file or either adjacent file, which could otherwise block or capture them.
+pub fn passed_pawns(pos: &Position, color: Color) -> Bitboard {
+    let enemy_pawns = pos.pawns(color.toggle());
+    let mut passed = Bitboard::none();
+    for pawn in pos.pawns(color) {
+        let file = pawn.file();
+        let rank = pawn.rank().as_index();
+        let ahead = match color {
+            Color::White => &BB_RANKS[rank + 1..],
+            Color::Black => &BB_RANKS[..rank],
+        };
+
+        let front_span = ahead.iter().fold(Bitboard::none(), |acc, &r| acc | r)
+            & (Bitboard::all().file(file) | adjacent_files(file));
+        if (front_span & enemy_pawns).empty() {
+            passed.set(pawn);
+        }
+    }
+
+    passed
+}
+
+fn pawn_structure_for(pos: &Position, color: Color) -> f32 {
+    let analysis = Analysis::new(pos);
+    let mut score = -DOUBLED_PAWN_PENALTY * analysis.doubled_pawns(color).count() as f32
+        - ISOLATED_PAWN_PENALTY * analysis.isolated_pawns(color).count() as f32;
+
+    for pawn in passed_pawns(pos, color) {
+        let rank = match color {
+            Color::White => pawn.rank().as_index(),
+            Color::Black => 7 - pawn.rank().as_index(),
+        };
+
+        score += PASSED_PAWN_BONUS[rank];
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{passed_pawns, pawn_structure};
+    use crate::position::Position;
+    use crate::types::{Color, Square};
+
+    fn structure(fen: &str) -> f32 {
+        pawn_structure(&Position::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn doubled_pawns() {
+        // White's c-pawns are doubled, while Black's pawns stand side by side. Neither side has
+        // isolated or passed pawns.
+        let score = structure("4k3/1ppp4/8/8/8/2P5/1PP5/4K3 w - - 0 1");
+        assert!((score + 0.2).abs() < 1e-6, "{}", score);
+    }
+
+    #[test]
+    fn isolated_pawn() {
+        // White's a-pawn is cut off from the pawns on the d- and e-files.
+        let score = structure("4k3/p2pp3/8/8/8/8/P2PP3/4K3 w - - 0 1");
+        assert_eq!(0.0, score);
+
+        let score = structure("4k3/pp1pp3/8/8/8/8/P2PP3/4K3 w - - 0 1");
+        assert!((score + 0.15).abs() < 1e-6, "{}", score);
+    }
+
+    #[test]
+    fn passed_pawn_on_sixth_rank() {
+        let fen = "4k3/8/3P4/8/2p5/8/8/4K3 w - - 0 1";
+        let pos = Position::from_fen(fen).unwrap();
+        assert_eq!(
+            &[Square::D6],
+            passed_pawns(&pos, Color::White).squares().as_slice()
+        );
+        assert_eq!(
+            &[Square::C4],
+            passed_pawns(&pos, Color::Black).squares().as_slice()
+        );
+
+        // Both pawns are passed and isolated, but White's is much further along.
+        let score = structure(fen);
+        assert!((score - (0.6 - 0.35)).abs() < 1e-6, "{}", score);
+    }
+
+    #[test]
+    fn blocked_pawn_is_not_passed() {
+        // The e6 pawn guards d5 and f5, and the d7 pawn blocks d6 outright.
+        let pos = Position::from_fen("4k3/3p4/4p3/8/3P4/8/8/4K3 w - - 0 1").unwrap();
+        assert!(passed_pawns(&pos, Color::White).empty());
+        let pos = Position::from_fen("4k3/3p4/8/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(passed_pawns(&pos, Color::White).empty());
+
+        // Pawns behind a pawn don't stop it.
+        let pos = Position::from_fen("4k3/8/8/3P4/4p3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            &[Square::D5],
+            passed_pawns(&pos, Color::White).squares().as_slice()
+        );
+    }
+}
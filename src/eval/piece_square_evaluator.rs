@@ -0,0 +1,272 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::eval::{endgame_override, BoardEvaluator, EvalParams, Score};
+use crate::position::Position;
+use crate::types::{Color, PieceKind, Square, TableIndex};
+
+/// A table of bonuses, in hundredths of a pawn, for a piece standing on each square of the board.
+///
+/// Tables are written from White's point of view and laid out the way the board is drawn, so the
+/// first row of a table is the eighth rank and the last row is the first rank.
+pub type PieceSquareTable = [i16; 64];
+
+#[rustfmt::skip]
+const PAWN_TABLE: PieceSquareTable = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: PieceSquareTable = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: PieceSquareTable = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: PieceSquareTable = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: PieceSquareTable = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+/// The king belongs behind its pawns, tucked away in a corner, for as long as there are enough
+/// pieces on the board to attack it.
+#[rustfmt::skip]
+const KING_TABLE: PieceSquareTable = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+/// Returns the bonus, in hundredths of a pawn, that the given table awards a piece of the given
+/// color on the given square. Black's pieces use the table mirrored top to bottom, so that each
+/// side's pieces are rewarded for the same squares relative to their own side of the board.
+pub fn piece_square_bonus(table: &PieceSquareTable, color: Color, sq: Square) -> i32 {
+    // Square indices count from a1, while the tables start from a8.
+    let idx = match color {
+        Color::White => sq.as_index() ^ 56,
+        Color::Black => sq.as_index(),
+    };
+
+    i32::from(table[idx])
+}
+
+/// An evaluator that counts material and rewards pieces for standing on good squares: pawns for
+/// advancing, knights and bishops for heading towards the center, and the king for staying safely
+/// castled.
+pub struct PieceSquareEvaluator {
+    params: EvalParams,
+}
+
+impl PieceSquareEvaluator {
+    pub fn new() -> PieceSquareEvaluator {
+        PieceSquareEvaluator::with_params(Default::default())
+    }
+
+    /// Constructs a new evaluator that uses the given parameters.
+    pub fn with_params(params: EvalParams) -> PieceSquareEvaluator {
+        PieceSquareEvaluator { params }
+    }
+}
+
+impl Default for PieceSquareEvaluator {
+    fn default() -> PieceSquareEvaluator {
+        PieceSquareEvaluator::new()
+    }
+}
+
+impl BoardEvaluator for PieceSquareEvaluator {
+    fn evaluate(&self, pos: &Position) -> Score {
+        endgame_override(pos).unwrap_or_else(|| {
+            Score::Evaluated(self.params.material(pos) + positional(pos, table_for))
+        })
+    }
+}
+
+/// The positional part of the evaluation of the given position, in pawns, from White's perspective,
+/// using the piece-square table that `tables` gives for each kind of piece.
+pub(super) fn positional<F>(pos: &Position, tables: F) -> f32
+where
+    F: Fn(PieceKind) -> &'static PieceSquareTable,
+{
+    // Summing the bonuses in hundredths of a pawn keeps symmetrical positions from picking up
+    // rounding errors.
+    let mut score = 0;
+    for &color in &[Color::White, Color::Black] {
+        let sign = match color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+
+        for kind in PieceKind::iter() {
+            let table = tables(kind);
+            for sq in pos.pieces_of_kind(color, kind).iter() {
+                score += sign * piece_square_bonus(table, color, sq);
+            }
+        }
+    }
+
+    score as f32 / 100.0
+}
+
+pub(super) fn table_for(kind: PieceKind) -> &'static PieceSquareTable {
+    match kind {
+        PieceKind::Pawn => &PAWN_TABLE,
+        PieceKind::Knight => &KNIGHT_TABLE,
+        PieceKind::Bishop => &BISHOP_TABLE,
+        PieceKind::Rook => &ROOK_TABLE,
+        PieceKind::Queen => &QUEEN_TABLE,
+        PieceKind::King => &KING_TABLE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PieceSquareEvaluator;
+    use crate::eval::{BoardEvaluator, Score};
+    use crate::position::Position;
+
+    fn evaluate(fen: &str) -> f32 {
+        match PieceSquareEvaluator::new().evaluate(&Position::from_fen(fen).unwrap()) {
+            Score::Evaluated(score) => score,
+            score => panic!("unexpected score {}", score),
+        }
+    }
+
+    /// Flips a position top to bottom and swaps the colors of all of its pieces, which gives the
+    /// same position from the other side's point of view.
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |s: &str| -> String {
+            s.chars()
+                .map(|c| {
+                    if c.is_ascii_uppercase() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c.to_ascii_uppercase()
+                    }
+                })
+                .collect()
+        };
+
+        let board: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        let en_passant = match fields[3] {
+            "-" => "-".to_owned(),
+            ep => ep
+                .chars()
+                .map(|c| match c {
+                    '3' => '6',
+                    '6' => '3',
+                    c => c,
+                })
+                .collect(),
+        };
+        format!(
+            "{} {} {} {} {} {}",
+            board.join("/"),
+            side,
+            swap_case(fields[2]),
+            en_passant,
+            fields[4],
+            fields[5]
+        )
+    }
+
+    #[test]
+    fn start_position_is_even() {
+        assert_eq!(
+            0.0,
+            evaluate("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+        );
+    }
+
+    #[test]
+    fn centralized_knight() {
+        let center = evaluate("4k3/pppppppp/8/8/3N4/8/PPPPPPPP/4K3 w - - 0 1");
+        let rim = evaluate("4k3/pppppppp/8/8/N7/8/PPPPPPPP/4K3 w - - 0 1");
+        assert!(center > rim, "{} <= {}", center, rim);
+    }
+
+    #[test]
+    fn castled_king() {
+        let castled =
+            evaluate("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4");
+        let uncastled =
+            evaluate("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPPKPPP/RNBQ3R b kq - 5 4");
+        assert!(castled > uncastled, "{} <= {}", castled, uncastled);
+    }
+
+    #[test]
+    fn symmetric() {
+        for fen in &[
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+        ] {
+            let mirrored = mirror(fen);
+            let score = evaluate(fen);
+            let mirrored_score = evaluate(&mirrored);
+            assert!(
+                (score + mirrored_score).abs() < 1e-4,
+                "{} scores {}, {} scores {}",
+                fen,
+                score,
+                mirrored,
+                mirrored_score
+            );
+        }
+    }
+}
@@ -7,16 +7,20 @@
 // except according to those terms.
 use std::cmp::Ordering;
 use std::fmt;
-use std::ops::Neg;
+use std::ops::{Add, Mul, Neg};
+
+const MATE_VALUE: i16 = 32000;
+const MAX_MATE_PLIES: u32 = 1000;
+const MAX_EVALUATED: f32 = 30000.0;
 
 /// Score is the output of a board evaluation function. Board evaluators can return one of three
 /// variants, depending on the board position.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Score {
-    /// The board position is a guaranteed win for the maximizing player in the given number of moves.
+    /// The board position is a guaranteed win for the maximizing player in the given number of plies.
     Win(u32),
 
-    /// The board position is a guaranteed loss for the maximizing player in the given number of moves.
+    /// The board position is a guaranteed loss for the maximizing player in the given number of plies.
     Loss(u32),
 
     /// The board position is not a guaranteed win or loss and the board evaluator has assigned the
@@ -25,6 +29,56 @@ pub enum Score {
 }
 
 impl Score {
+    /// Returns whether or not this score is a forced mate, for either player.
+    pub fn is_mate(&self) -> bool {
+        self.is_winning_mate() || self.is_losing_mate()
+    }
+
+    /// Returns whether or not this score is a forced mate for the maximizing player.
+    pub fn is_winning_mate(&self) -> bool {
+        matches!(self, Score::Win(_))
+    }
+
+    /// Returns whether or not this score is a forced mate against the maximizing player.
+    pub fn is_losing_mate(&self) -> bool {
+        matches!(self, Score::Loss(_))
+    }
+
+    /// If this score is a forced mate, returns the number of plies until the mate: positive if the
+    /// maximizing player delivers it, negative if the maximizing player is mated. A mate in two
+    /// moves for the side to move, for example, is three plies away.
+    pub fn mate_in(&self) -> Option<i32> {
+        match *self {
+            Score::Win(plies) => Some(plies as i32),
+            Score::Loss(plies) => Some(-(plies as i32)),
+            Score::Evaluated(_) => None,
+        }
+    }
+
+    /// Packs this score into 16 bits, for storing it compactly. Evaluated scores are stored in
+    /// hundredths of a pawn, clamped to +/- 300 pawns; mate scores are stored as offsets from the
+    /// extremes of the `i16` range, so they can be up to 1000 plies long.
+    pub fn to_i16(self) -> i16 {
+        match self {
+            Score::Win(plies) => MATE_VALUE - plies.min(MAX_MATE_PLIES) as i16,
+            Score::Loss(plies) => -MATE_VALUE + plies.min(MAX_MATE_PLIES) as i16,
+            Score::Evaluated(score) => {
+                (score * 100.0).round().clamp(-MAX_EVALUATED, MAX_EVALUATED) as i16
+            }
+        }
+    }
+
+    /// Unpacks a score that was packed with `to_i16`.
+    pub fn from_i16(value: i16) -> Score {
+        if value > MATE_VALUE - MAX_MATE_PLIES as i16 - 1 {
+            Score::Win((MATE_VALUE - value) as u32)
+        } else if value < -MATE_VALUE + MAX_MATE_PLIES as i16 + 1 {
+            Score::Loss((value + MATE_VALUE) as u32)
+        } else {
+            Score::Evaluated(f32::from(value) / 100.0)
+        }
+    }
+
     pub fn step(self) -> Score {
         match self {
             Score::Win(score) => Score::Win(score + 1),
@@ -101,6 +155,32 @@ impl Neg for Score {
     }
 }
 
+/// Evaluated scores can be scaled. Mate scores are unaffected by scaling.
+impl Mul<f32> for Score {
+    type Output = Score;
+
+    fn mul(self, factor: f32) -> Score {
+        match self {
+            Score::Evaluated(score) => Score::Evaluated(score * factor),
+            s => s,
+        }
+    }
+}
+
+/// Evaluated scores can be added together. Mate scores take precedence over evaluated scores; if
+/// both scores are mate scores, the left-hand side wins.
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        match (self, other) {
+            (Score::Evaluated(lhs), Score::Evaluated(rhs)) => Score::Evaluated(lhs + rhs),
+            (Score::Evaluated(_), mate) => mate,
+            (mate, _) => mate,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Score;
@@ -123,17 +203,128 @@ mod tests {
         assert!(Score::Loss(99) < Score::Win(1));
     }
 
+    #[test]
+    fn mate_in() {
+        assert_eq!(Some(3), Score::Win(3).mate_in());
+        assert_eq!(Some(-2), Score::Loss(2).mate_in());
+        assert_eq!(Some(-2), (-Score::Win(2)).mate_in());
+        assert_eq!(Some(0), Score::Loss(0).mate_in());
+        assert_eq!(None, Score::Evaluated(4.5).mate_in());
+    }
+
     #[test]
     fn eval_cmp() {
         assert!(Score::Evaluated(1f32) < Score::Evaluated(2f32));
         assert!(Score::Evaluated(3f32) > Score::Evaluated(2f32));
     }
 
+    #[test]
+    fn mate_predicates() {
+        assert!(Score::Win(3).is_mate());
+        assert!(Score::Win(3).is_winning_mate());
+        assert!(!Score::Win(3).is_losing_mate());
+        assert!(Score::Loss(3).is_mate());
+        assert!(Score::Loss(3).is_losing_mate());
+        assert!(!Score::Loss(3).is_winning_mate());
+        assert!(!Score::Evaluated(9999f32).is_mate());
+        assert!(!Score::Evaluated(-9999f32).is_losing_mate());
+    }
+
+    #[test]
+    fn mate_cmp() {
+        // Any mate beats any evaluated score and any evaluated score beats being mated.
+        assert!(Score::Win(99) > Score::Evaluated(f32::MAX));
+        assert!(Score::Loss(1) < Score::Evaluated(f32::MIN));
+
+        // Shorter mates are better than longer ones, and being mated later is better than sooner.
+        assert!(Score::Win(1) > Score::Win(3));
+        assert!(Score::Loss(4) > Score::Loss(2));
+        assert_eq!(
+            Some(&Score::Win(1)),
+            [Score::Win(5), Score::Win(1), Score::Evaluated(3f32)]
+                .iter()
+                .max()
+        );
+        assert_eq!(
+            Some(&Score::Loss(2)),
+            [Score::Loss(6), Score::Loss(2), Score::Evaluated(-3f32)]
+                .iter()
+                .min()
+        );
+    }
+
+    #[test]
+    fn i16_round_trip() {
+        for &score in &[
+            Score::Win(0),
+            Score::Win(7),
+            Score::Loss(0),
+            Score::Loss(12),
+            Score::Evaluated(0f32),
+            Score::Evaluated(1.5f32),
+            Score::Evaluated(-42.25f32),
+        ] {
+            assert_eq!(score, Score::from_i16(score.to_i16()));
+        }
+
+        // Evaluated scores are clamped so they don't collide with mate scores.
+        assert_eq!(
+            Score::Evaluated(300f32),
+            Score::from_i16(Score::Evaluated(1e9).to_i16())
+        );
+        assert_eq!(
+            Score::Evaluated(-300f32),
+            Score::from_i16(Score::Evaluated(-1e9).to_i16())
+        );
+    }
+
     #[test]
     fn neg() {
         assert_eq!(-Score::Win(1), Score::Loss(1));
         assert_eq!(-Score::Loss(1), Score::Win(1));
         assert_eq!(-Score::Evaluated(1f32), Score::Evaluated(-1f32));
+        assert_eq!(-Score::Win(3), Score::Loss(3));
+    }
+
+    #[test]
+    fn neg_is_an_involution_that_reverses_order() {
+        let scores = [
+            Score::Loss(0),
+            Score::Loss(2),
+            Score::Evaluated(-1.5f32),
+            Score::Evaluated(0f32),
+            Score::Evaluated(1.5f32),
+            Score::Win(3),
+            Score::Win(0),
+        ];
+        for &score in &scores {
+            assert_eq!(score, -(-score));
+        }
+
+        // Negamax flips the window (alpha, beta) into (-beta, -alpha), which is only sound if
+        // negation reverses the order of any two scores.
+        for pair in scores.windows(2) {
+            assert!(pair[0] < pair[1], "{} < {}", pair[0], pair[1]);
+            assert!(-pair[1] < -pair[0], "-{} < -{}", pair[1], pair[0]);
+        }
+    }
+
+    #[test]
+    fn scale() {
+        assert_eq!(Score::Evaluated(1.5f32), Score::Evaluated(3f32) * 0.5);
+        assert_eq!(Score::Win(2), Score::Win(2) * 0.5);
+        assert_eq!(Score::Loss(2), Score::Loss(2) * 0.5);
+    }
+
+    #[test]
+    fn add() {
+        assert_eq!(
+            Score::Evaluated(3f32),
+            Score::Evaluated(1f32) + Score::Evaluated(2f32)
+        );
+        assert_eq!(Score::Win(1), Score::Evaluated(1f32) + Score::Win(1));
+        assert_eq!(Score::Loss(1), Score::Loss(1) + Score::Evaluated(1f32));
+        assert_eq!(Score::Loss(1), Score::Loss(1) + Score::Win(3));
     }
 
     #[test]
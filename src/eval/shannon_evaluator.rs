@@ -6,30 +6,32 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 use crate::analysis::Analysis;
-use crate::eval::{BoardEvaluator, Score};
+use crate::eval::{endgame_override, BoardEvaluator, EvalParams, Score};
 use crate::position::Position;
 use crate::types::Color;
 
 const KING_WEIGHT: f32 = 2000f32;
-const QUEEN_WEIGHT: f32 = 9f32;
-const ROOK_WEIGHT: f32 = 5f32;
-const BISHOP_WEIGHT: f32 = 3f32;
-const KNIGHT_WEIGHT: f32 = 3f32;
-const PAWN_WEIGHT: f32 = 1f32;
 const PAWN_FORMATION_WEIGHT: f32 = 0.5;
 const MOBILITY_WEIGHT: f32 = 0.1;
 
-pub struct ShannonEvaluator;
+pub struct ShannonEvaluator {
+    params: EvalParams,
+}
 
 impl ShannonEvaluator {
     pub fn new() -> ShannonEvaluator {
-        ShannonEvaluator
+        ShannonEvaluator::with_params(Default::default())
+    }
+
+    /// Constructs a new evaluator that uses the given parameters.
+    pub fn with_params(params: EvalParams) -> ShannonEvaluator {
+        ShannonEvaluator { params }
     }
 }
 
 impl Default for ShannonEvaluator {
     fn default() -> ShannonEvaluator {
-        ShannonEvaluator
+        ShannonEvaluator::new()
     }
 }
 
@@ -55,13 +57,13 @@ impl BoardEvaluator for ShannonEvaluator {
             }
         }
 
+        if let Some(score) = endgame_override(pos) {
+            return score;
+        }
+
         let kings = evaluate_metric(KING_WEIGHT, |c| pos.kings(c).count() as f32);
-        let queens = evaluate_metric(QUEEN_WEIGHT, |c| pos.queens(c).count() as f32);
-        let rooks = evaluate_metric(ROOK_WEIGHT, |c| pos.rooks(c).count() as f32);
-        let bishops = evaluate_metric(BISHOP_WEIGHT, |c| pos.bishops(c).count() as f32);
-        let knights = evaluate_metric(KNIGHT_WEIGHT, |c| pos.knights(c).count() as f32);
-        let pawns = evaluate_metric(PAWN_WEIGHT, |c| pos.pawns(c).count() as f32);
-        let mobility = MOBILITY_WEIGHT * (white_mobility as f32 - black_mobility as f32);
+        let material = self.params.material(pos);
+        let mobility = evaluate_metric(MOBILITY_WEIGHT, |c| analysis.piece_mobility(c) as f32);
         let isolated_pawns = evaluate_metric(PAWN_FORMATION_WEIGHT, |c| {
             analysis.isolated_pawns(c).count() as f32
         });
@@ -73,16 +75,7 @@ impl BoardEvaluator for ShannonEvaluator {
         });
 
         Score::Evaluated(
-            kings
-                + queens
-                + rooks
-                + bishops
-                + knights
-                + pawns
-                + isolated_pawns
-                + backward_pawns
-                + doubled_pawns
-                + mobility,
+            kings + material + isolated_pawns + backward_pawns + doubled_pawns + mobility,
         )
     }
 }
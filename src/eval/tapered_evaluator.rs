@@ -0,0 +1,144 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::eval::piece_square_evaluator::{positional, table_for};
+use crate::eval::{
+    endgame_override, game_phase, mobility, pawn_structure, BoardEvaluator, EvalParams,
+    PieceSquareTable, Score,
+};
+use crate::position::Position;
+use crate::types::PieceKind;
+
+/// Passed pawns become the most important thing on the board once the pieces that could stop them
+/// are gone, so pawns are rewarded much more for advancing in the endgame.
+#[rustfmt::skip]
+const ENDGAME_PAWN_TABLE: PieceSquareTable = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     15,  15,  15,  15,  15,  15,  15,  15,
+      5,   5,   5,   5,   5,   5,   5,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+/// With few pieces left to attack it, the king is a strong piece that belongs in the center.
+#[rustfmt::skip]
+const ENDGAME_KING_TABLE: PieceSquareTable = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+/// An evaluator that scores each position twice, once as a midgame position and once as an
+/// endgame position, and blends the two scores according to the phase of the game. Each half has
+/// its own piece values and piece-square tables; the midgame half is exactly the evaluation of the
+/// `PieceSquareEvaluator`.
+///
+/// Blending the scores, rather than switching from one to the other once enough pieces have come
+/// off of the board, keeps the evaluation from jumping whenever a trade crosses the threshold.
+///
+/// On top of the blended score, the evaluator scores the pawn structure and the mobility of the
+/// pieces, both of which matter in every phase of the game.
+pub struct TaperedEvaluator {
+    params: EvalParams,
+}
+
+impl TaperedEvaluator {
+    pub fn new() -> TaperedEvaluator {
+        TaperedEvaluator::with_params(Default::default())
+    }
+
+    /// Constructs a new evaluator that uses the given parameters.
+    pub fn with_params(params: EvalParams) -> TaperedEvaluator {
+        TaperedEvaluator { params }
+    }
+}
+
+impl Default for TaperedEvaluator {
+    fn default() -> TaperedEvaluator {
+        TaperedEvaluator::new()
+    }
+}
+
+impl BoardEvaluator for TaperedEvaluator {
+    fn evaluate(&self, pos: &Position) -> Score {
+        if let Some(score) = endgame_override(pos) {
+            return score;
+        }
+
+        // The material balance is already blended by phase, so only the positional halves need it.
+        let weight = game_phase(pos);
+        let midgame = positional(pos, table_for);
+        let endgame = positional(pos, endgame_table_for);
+        let blended = self.params.material(pos) + midgame * weight + endgame * (1.0 - weight);
+        Score::Evaluated(blended + pawn_structure(pos) + mobility(pos))
+    }
+}
+
+fn endgame_table_for(kind: PieceKind) -> &'static PieceSquareTable {
+    match kind {
+        PieceKind::Pawn => &ENDGAME_PAWN_TABLE,
+        PieceKind::King => &ENDGAME_KING_TABLE,
+        kind => table_for(kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TaperedEvaluator;
+    use crate::eval::{mobility, pawn_structure, BoardEvaluator, PieceSquareEvaluator, Score};
+    use crate::position::Position;
+
+    fn evaluate(fen: &str) -> f32 {
+        match TaperedEvaluator::new().evaluate(&Position::from_fen(fen).unwrap()) {
+            Score::Evaluated(score) => score,
+            score => panic!("unexpected score {}", score),
+        }
+    }
+
+    #[test]
+    fn full_phase_is_midgame() {
+        // With every piece still on the board, the endgame half of the evaluation has no weight,
+        // which leaves the midgame half, the pawn structure, and the mobility terms.
+        for fen in &[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            "rnbqkbnr/pp3ppp/8/2pp4/8/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 4",
+        ] {
+            let pos = Position::from_fen(fen).unwrap();
+            let midgame = PieceSquareEvaluator::new().evaluate(&pos);
+            assert_eq!(
+                midgame + Score::Evaluated(pawn_structure(&pos) + mobility(&pos)),
+                TaperedEvaluator::new().evaluate(&pos),
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn endgame_king_centralizes() {
+        // Once the pieces are gone, the king is better off in the center than in its castled spot.
+        let central = evaluate("8/5pk1/8/8/3K4/8/5P2/8 w - - 0 1");
+        let castled = evaluate("8/5pk1/8/8/8/8/5P2/6K1 w - - 0 1");
+        assert!(central > castled, "{} <= {}", central, castled);
+    }
+
+    #[test]
+    fn endgame_pawns_advance() {
+        let advanced = evaluate("6k1/8/1P6/8/8/8/5p2/6K1 b - - 0 1");
+        let home = evaluate("6k1/8/8/8/8/8/1P3p2/6K1 b - - 0 1");
+        assert!(advanced > home, "{} <= {}", advanced, home);
+    }
+}
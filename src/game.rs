@@ -0,0 +1,262 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt::Write;
+
+use crate::history::{is_repetition, PositionHistory};
+use crate::moves::Move;
+use crate::pgn::{self, PgnError};
+use crate::position::Position;
+use crate::types::{Color, GameResult};
+
+/// A game of chess, consisting of a starting position and the sequence of moves played from it.
+#[derive(Clone, Debug)]
+pub struct Game {
+    start: Position,
+    current: Position,
+    moves: Vec<Move>,
+    /// Every position that has occurred in this game, including the current one.
+    history: PositionHistory,
+    /// PGN tag pairs describing this game, e.g. `Event` and `White`, in the order they were set.
+    tags: Vec<(String, String)>,
+}
+
+impl Game {
+    /// Constructs a new game that begins at the standard starting position.
+    pub fn new() -> Game {
+        Game::from_position(Position::from_start_position())
+    }
+
+    /// Constructs a new game that begins at the given position.
+    pub fn from_position(start: Position) -> Game {
+        let mut history = PositionHistory::new();
+        history.push(&start);
+        Game {
+            current: start.clone(),
+            history,
+            start,
+            moves: vec![],
+            tags: vec![],
+        }
+    }
+
+    /// Parses a single game in PGN format. The game starts from the position given by its `FEN`
+    /// tag if it has one, and the standard starting position otherwise.
+    pub fn from_pgn(text: &str) -> Result<Game, PgnError> {
+        pgn::parse_pgn(text)
+    }
+
+    /// Parses every game in the text of a PGN file. Games are separated at each `[Event` tag and
+    /// parsed independently, so a malformed game produces an error in its slot without affecting
+    /// the games around it.
+    pub fn from_pgn_collection(text: &str) -> Vec<Result<Game, PgnError>> {
+        pgn::split_games(text)
+            .into_iter()
+            .map(pgn::parse_pgn)
+            .collect()
+    }
+
+    /// The PGN tag pairs of this game, in the order they were set.
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// The value of the PGN tag with the given name, if this game has one.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the value of a PGN tag, replacing the tag's existing value if it has one.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, existing)) => *existing = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// The position that this game started from.
+    pub fn start_position(&self) -> &Position {
+        &self.start
+    }
+
+    /// The current position of this game, after all moves have been played.
+    pub fn position(&self) -> &Position {
+        &self.current
+    }
+
+    /// The moves that have been played in this game.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Plays a move, advancing the current position of the game.
+    pub fn make_move(&mut self, mov: Move) {
+        self.current.apply_move(mov);
+        self.moves.push(mov);
+        self.history.push(&self.current);
+    }
+
+    /// Returns whether or not playing the given move would produce a position that has occurred
+    /// twice already in this game, which would entitle a player to claim a draw by threefold
+    /// repetition.
+    pub fn move_creates_threefold(&self, mov: Move) -> bool {
+        let mut child = self.current.clone();
+        child.apply_move(mov);
+        let mut history = self.history.clone();
+        history.push(&child);
+        is_repetition(&history, 3)
+    }
+
+    /// The PGN `Result` tag for this game: `1-0` or `0-1` if one side has been checkmated,
+    /// `1/2-1/2` if the game has been drawn by stalemate or the fifty-move rule, and `*` if the
+    /// game isn't over.
+    pub fn result_tag(&self) -> &'static str {
+        match self.current.outcome() {
+            Some(GameResult::Win(Color::White)) => "1-0",
+            Some(GameResult::Win(Color::Black)) => "0-1",
+            Some(GameResult::Draw) => "1/2-1/2",
+            None => "*",
+        }
+    }
+
+    /// Formats the moves played in this game as a numbered SAN move list.
+    pub fn format_moves(&self) -> String {
+        Game::format_line(&self.start, &self.moves)
+    }
+
+    /// Formats a sequence of moves played from the given position as a numbered SAN move list,
+    /// e.g. `1. e4 e5 2. Nf3 Nc6`. Lines that begin with Black to move start with an ellipsis,
+    /// e.g. `1... e5 2. Nf3`.
+    pub fn format_line(start: &Position, moves: &[Move]) -> String {
+        let mut pos = start.clone();
+        let mut buf = String::new();
+        for (i, &mov) in moves.iter().enumerate() {
+            if i != 0 {
+                buf.push(' ');
+            }
+
+            match pos.side_to_move() {
+                Color::White => write!(&mut buf, "{}. ", pos.fullmove_clock()).unwrap(),
+                Color::Black if i == 0 => write!(&mut buf, "{}... ", pos.fullmove_clock()).unwrap(),
+                Color::Black => {}
+            }
+
+            buf.push_str(&pos.move_to_san(mov));
+            pos.apply_move(mov);
+        }
+
+        buf
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Game;
+    use crate::position::Position;
+
+    fn line(start: &Position, uci_moves: &[&str]) -> String {
+        let mut pos = start.clone();
+        let mut moves = vec![];
+        for uci in uci_moves {
+            let mov = pos.move_from_uci(uci).unwrap();
+            pos.apply_move(mov);
+            moves.push(mov);
+        }
+
+        Game::format_line(start, &moves)
+    }
+
+    #[test]
+    fn format_from_start() {
+        let start = Position::from_start_position();
+        assert_eq!(
+            "1. e4 e5 2. Nf3 Nc6 3. Bb5",
+            line(&start, &["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"])
+        );
+    }
+
+    #[test]
+    fn format_from_black_to_move() {
+        let start =
+            Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 2 2")
+                .unwrap();
+        assert_eq!(
+            "2... Nf6 3. Nxe5 Nxe4",
+            line(&start, &["g8f6", "f3e5", "f6e4"])
+        );
+    }
+
+    #[test]
+    fn format_empty() {
+        assert_eq!("", Game::format_line(&Position::from_start_position(), &[]));
+    }
+
+    #[test]
+    fn game_moves() {
+        let mut game = Game::new();
+        let e4 = game.position().move_from_uci("e2e4").unwrap();
+        game.make_move(e4);
+        let e5 = game.position().move_from_uci("e7e5").unwrap();
+        game.make_move(e5);
+        assert_eq!(&[e4, e5], game.moves());
+        assert_eq!("1. e4 e5", game.format_moves());
+    }
+
+    fn play(game: &mut Game, uci_moves: &[&str]) {
+        for uci in uci_moves {
+            let mov = game.position().move_from_uci(uci).unwrap();
+            game.make_move(mov);
+        }
+    }
+
+    #[test]
+    fn result_tags() {
+        let mut game = Game::new();
+        assert_eq!("*", game.result_tag());
+
+        // Scholar's mate.
+        play(
+            &mut game,
+            &["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"],
+        );
+        assert_eq!("1-0", game.result_tag());
+
+        // Fool's mate.
+        let mut game = Game::new();
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!("0-1", game.result_tag());
+
+        let stalemate = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!("1/2-1/2", Game::from_position(stalemate).result_tag());
+    }
+
+    #[test]
+    fn threefold() {
+        let mut game = Game::new();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        for uci in shuffle.iter().chain(shuffle[..3].iter()) {
+            let mov = game.position().move_from_uci(uci).unwrap();
+            assert!(!game.move_creates_threefold(mov), "{}", uci);
+            game.make_move(mov);
+        }
+
+        // The starting position has occurred twice, and Ng8 brings it about a third time.
+        let ng8 = game.position().move_from_uci("f6g8").unwrap();
+        assert!(game.move_creates_threefold(ng8));
+        let nh5 = game.position().move_from_uci("f6h5").unwrap();
+        assert!(!game.move_creates_threefold(nh5));
+    }
+}
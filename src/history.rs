@@ -0,0 +1,123 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Tracking the positions that have occurred in a game, for detecting draws by repetition.
+use crate::position::Position;
+
+/// A stack of the positions that have occurred in a game or along a line of play, the most recent
+/// of which is the current position. Positions are recorded by their zobrist hashes, alongside
+/// their halfmove clocks so that positions from before the last capture or pawn move, which can
+/// never occur again, aren't considered when looking for repetitions.
+#[derive(Clone, Debug, Default)]
+pub struct PositionHistory {
+    entries: Vec<(u64, u32)>,
+}
+
+impl PositionHistory {
+    /// Constructs a new, empty history.
+    pub fn new() -> PositionHistory {
+        PositionHistory { entries: vec![] }
+    }
+
+    /// Records a position, which becomes the current position of this history.
+    pub fn push(&mut self, pos: &Position) {
+        self.entries
+            .push((pos.zobrist_hash(), pos.halfmove_clock()));
+    }
+
+    /// Removes the current position from this history, e.g. when unmaking a move.
+    pub fn pop(&mut self) {
+        self.entries.pop();
+    }
+
+    /// The number of positions in this history.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The zobrist hashes of the positions in this history, oldest first.
+    pub fn hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.entries.iter().map(|&(hash, _)| hash)
+    }
+
+    /// The number of times that the current position has occurred since the last irreversible
+    /// move, counting the current position itself.
+    pub fn occurrences(&self) -> usize {
+        let (hash, halfmove_clock) = match self.entries.last() {
+            Some(&entry) => entry,
+            None => return 0,
+        };
+
+        // The halfmove clock counts the plies since the last capture or pawn move, so only that
+        // many positions before the current one can possibly be repetitions of it.
+        self.entries
+            .iter()
+            .rev()
+            .take(halfmove_clock as usize + 1)
+            .filter(|&&(h, _)| h == hash)
+            .count()
+    }
+}
+
+/// Returns whether or not the current position of the given history has occurred at least `count`
+/// times since the last irreversible move. A `count` of three detects a threefold repetition.
+pub fn is_repetition(history: &PositionHistory, count: usize) -> bool {
+    history.occurrences() >= count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_repetition, PositionHistory};
+    use crate::moves::Move;
+    use crate::position::Position;
+    use crate::types::Square;
+
+    #[test]
+    fn knight_shuffle_threefold() {
+        let mut pos = Position::from_start_position();
+        let mut history = PositionHistory::new();
+        history.push(&pos);
+
+        let shuffle = [
+            Move::quiet(Square::G1, Square::F3),
+            Move::quiet(Square::G8, Square::F6),
+            Move::quiet(Square::F3, Square::G1),
+            Move::quiet(Square::F6, Square::G8),
+        ];
+
+        // The starting position recurs after every four plies, so it occurs for the third time
+        // after the eighth ply.
+        for ply in 1..=8 {
+            pos.apply_move(shuffle[(ply - 1) % 4]);
+            history.push(&pos);
+            assert_eq!(ply >= 4, is_repetition(&history, 2), "ply {}", ply);
+            assert_eq!(ply == 8, is_repetition(&history, 3), "ply {}", ply);
+        }
+
+        history.pop();
+        assert!(!is_repetition(&history, 3));
+    }
+
+    #[test]
+    fn irreversible_move_resets_repetitions() {
+        // The halfmove clock of the second position says that it was reached by a capture or pawn
+        // move, so the earlier position with the same pieces can't be counted as a repetition.
+        let mut history = PositionHistory::new();
+        history.push(&Position::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 6 10").unwrap());
+        history.push(&Position::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 14").unwrap());
+        assert_eq!(1, history.occurrences());
+
+        history.pop();
+        history.push(&Position::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 10 14").unwrap());
+        assert_eq!(2, history.occurrences());
+    }
+}
@@ -18,23 +18,30 @@ extern crate log;
 #[macro_use]
 extern crate serde_derive;
 
-mod analysis;
+pub mod analysis;
 pub mod attacks;
 mod bitboard;
 pub mod book;
 pub mod eval;
+mod game;
+mod history;
 mod move_generator;
 mod moves;
 mod perft;
+mod pgn;
 mod position;
 pub mod search;
+pub mod selfplay;
 mod types;
 pub mod uci;
 mod zobrist;
 
 pub use bitboard::{Bitboard, BitboardIterator};
+pub use game::Game;
+pub use history::{is_repetition, PositionHistory};
 pub use move_generator::{MoveGenerator, MoveVec};
 pub use moves::Move;
-pub use perft::perft;
-pub use position::Position;
-pub use types::{Color, File, PieceKind, Rank, Square};
+pub use perft::{fuzz_movegen, perft, perft_divide, perft_in_pool, perft_parallel, verify_movegen};
+pub use pgn::{parse_pgn, PgnError};
+pub use position::{IllegalReason, MoveUndo, Position};
+pub use types::{Color, File, GameResult, Piece, PieceKind, Rank, Square};
@@ -39,21 +39,127 @@ pub type MoveVec = ArrayVec<[Move; 224]>;
 /// that can be accomodated by this MoveGenerator.
 pub struct MoveGenerator;
 
+/// The subset of pseudolegal moves that a call to the move generator should produce.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum GenerationMode {
+    /// All pseudolegal moves.
+    All,
+    /// Moves that capture a piece, including en-passant and promotion captures.
+    Captures,
+    /// Moves that do not capture a piece, including castles and non-capturing promotions.
+    Quiets,
+}
+
+impl GenerationMode {
+    fn captures(self) -> bool {
+        self != GenerationMode::Quiets
+    }
+
+    fn quiets(self) -> bool {
+        self != GenerationMode::Captures
+    }
+}
+
 impl MoveGenerator {
     pub fn new() -> MoveGenerator {
         MoveGenerator
     }
 
+    /// Generates all pseudolegal moves from the given position.
     pub fn generate_moves(&self, pos: &Position, buf: &mut MoveVec) {
-        self.generate_pawn_moves(pos, buf);
-        self.generate_knight_moves(pos, buf);
-        self.generate_sliding_moves(pos, buf, |c| pos.bishops(c), attacks::bishop_attacks);
-        self.generate_sliding_moves(pos, buf, |c| pos.rooks(c), attacks::rook_attacks);
-        self.generate_sliding_moves(pos, buf, |c| pos.queens(c), attacks::queen_attacks);
-        self.generate_king_moves(pos, buf);
+        self.generate(pos, buf, GenerationMode::All);
+    }
+
+    /// Generates all legal moves from the given position. Rather than making each pseudolegal move
+    /// to see whether it leaves the king in check, this works out which moves are legal from the
+    /// checks and absolute pins on the board.
+    pub fn generate_legal_moves(&self, pos: &Position, buf: &mut MoveVec) {
+        let mut pseudolegal = MoveVec::default();
+        self.generate(pos, &mut pseudolegal, GenerationMode::All);
+        self.retain_legal(pos, &mut pseudolegal);
+        buf.extend(pseudolegal);
+    }
+
+    /// Removes the illegal moves from a buffer of pseudolegal moves from the given position, such
+    /// as one filled by `generate_captures`. This uses the same checks and pins as
+    /// `generate_legal_moves`.
+    pub fn retain_legal(&self, pos: &Position, buf: &mut MoveVec) {
+        // Without a king, there's nothing for a move to expose to check.
+        if let Some(king) = pos.kings(pos.side_to_move()).first() {
+            let legality = Legality::new(pos, king);
+            buf.retain(|&mut mov| legality.is_legal(mov));
+        }
     }
 
-    fn generate_pawn_moves(&self, pos: &Position, buf: &mut MoveVec) {
+    /// Generates all pseudolegal moves that capture a piece, including en-passant captures and
+    /// promotion captures.
+    pub fn generate_captures(&self, pos: &Position, buf: &mut MoveVec) {
+        self.generate(pos, buf, GenerationMode::Captures);
+    }
+
+    /// Generates the pseudolegal moves that could get the side to move out of check: king moves,
+    /// captures of the checking piece, and moves that block the check. In double check, only king
+    /// moves are generated. If the side to move isn't in check, this generates all pseudolegal
+    /// moves.
+    ///
+    /// Evasions are not necessarily legal; a king can step onto an attacked square, and a pinned
+    /// piece can block a check.
+    pub fn generate_evasions(&self, pos: &Position, buf: &mut MoveVec) {
+        let color = pos.side_to_move();
+        let king = pos.kings(color).first();
+        let checkers = king
+            .map(|king| pos.squares_attacking(color.toggle(), king))
+            .unwrap_or_else(Bitboard::none);
+        let king = match king {
+            Some(king) if !checkers.empty() => king,
+            _ => {
+                self.generate(pos, buf, GenerationMode::All);
+                return;
+            }
+        };
+
+        let block_mask = match checkers.count() {
+            1 => attacks::between(king, checkers.first().unwrap()) | checkers,
+            _ => Bitboard::none(),
+        };
+
+        let mut pseudolegal = MoveVec::default();
+        self.generate(pos, &mut pseudolegal, GenerationMode::All);
+        buf.extend(pseudolegal.into_iter().filter(|&mov| {
+            if mov.source() == king {
+                return !mov.is_castle();
+            }
+
+            if mov.is_en_passant() {
+                // The pawn that en-passant captures isn't on the destination square.
+                let captured = match color {
+                    Color::White => mov.destination().towards(Direction::South),
+                    Color::Black => mov.destination().towards(Direction::North),
+                };
+                return block_mask.test(captured) || block_mask.test(mov.destination());
+            }
+
+            block_mask.test(mov.destination())
+        }));
+    }
+
+    /// Generates all pseudolegal moves that do not capture a piece. This includes castles and
+    /// promotions that do not capture. Together with `generate_captures`, this partitions the
+    /// moves produced by `generate_moves`.
+    pub fn generate_quiets(&self, pos: &Position, buf: &mut MoveVec) {
+        self.generate(pos, buf, GenerationMode::Quiets);
+    }
+
+    fn generate(&self, pos: &Position, buf: &mut MoveVec, mode: GenerationMode) {
+        self.generate_pawn_moves(pos, buf, mode);
+        self.generate_knight_moves(pos, buf, mode);
+        self.generate_sliding_moves(pos, buf, mode, |c| pos.bishops(c), attacks::bishop_attacks);
+        self.generate_sliding_moves(pos, buf, mode, |c| pos.rooks(c), attacks::rook_attacks);
+        self.generate_sliding_moves(pos, buf, mode, |c| pos.queens(c), attacks::queen_attacks);
+        self.generate_king_moves(pos, buf, mode);
+    }
+
+    fn generate_pawn_moves(&self, pos: &Position, buf: &mut MoveVec, mode: GenerationMode) {
         let color = pos.side_to_move();
         let enemy_pieces = pos.pieces(color.toggle());
         let allied_pieces = pos.pieces(color);
@@ -73,7 +179,7 @@ impl MoveGenerator {
             let target = pawn.towards(pawn_dir);
 
             // Non-capturing moves.
-            if !pieces.test(target) {
+            if mode.quiets() && !pieces.test(target) {
                 if target.rank() == promo_rank {
                     buf.push(Move::promotion(pawn, target, PieceKind::Knight));
                     buf.push(Move::promotion(pawn, target, PieceKind::Bishop));
@@ -85,13 +191,17 @@ impl MoveGenerator {
             }
 
             // Double pawn pushes, for pawns originating on the starting rank.
-            if pawn.rank() == start_rank {
+            if mode.quiets() && pawn.rank() == start_rank {
                 let two_push_target = target.towards(pawn_dir);
                 if !pieces.test(target) && !pieces.test(two_push_target) {
                     buf.push(Move::double_pawn_push(pawn, two_push_target));
                 }
             }
 
+            if !mode.captures() {
+                continue;
+            }
+
             // Non-en-passant capturing moves.
             for target in attacks::pawn_attacks(pawn, color) {
                 if enemy_pieces.test(target) {
@@ -127,23 +237,31 @@ impl MoveGenerator {
         }
     }
 
-    fn generate_knight_moves(&self, pos: &Position, buf: &mut MoveVec) {
+    fn generate_knight_moves(&self, pos: &Position, buf: &mut MoveVec, mode: GenerationMode) {
         let color = pos.side_to_move();
         let enemy_pieces = pos.pieces(color.toggle());
         let allied_pieces = pos.pieces(color);
         for knight in pos.knights(color) {
             for target in attacks::knight_attacks(knight) {
                 if enemy_pieces.test(target) {
-                    buf.push(Move::capture(knight, target));
-                } else if !allied_pieces.test(target) {
+                    if mode.captures() {
+                        buf.push(Move::capture(knight, target));
+                    }
+                } else if mode.quiets() && !allied_pieces.test(target) {
                     buf.push(Move::quiet(knight, target));
                 }
             }
         }
     }
 
-    fn generate_sliding_moves<B, A>(&self, pos: &Position, buf: &mut MoveVec, board: B, attacks: A)
-    where
+    fn generate_sliding_moves<B, A>(
+        &self,
+        pos: &Position,
+        buf: &mut MoveVec,
+        mode: GenerationMode,
+        board: B,
+        attacks: A,
+    ) where
         B: Fn(Color) -> Bitboard,
         A: Fn(Square, Bitboard) -> Bitboard,
     {
@@ -155,85 +273,170 @@ impl MoveGenerator {
             for target in attacks(piece, pieces) {
                 // In theory we only need to test the end of rays for occupancy, but this works.
                 if enemy_pieces.test(target) {
-                    buf.push(Move::capture(piece, target));
-                } else if !allied_pieces.test(target) {
+                    if mode.captures() {
+                        buf.push(Move::capture(piece, target));
+                    }
+                } else if mode.quiets() && !allied_pieces.test(target) {
                     buf.push(Move::quiet(piece, target));
                 }
             }
         }
     }
 
-    fn generate_king_moves(&self, pos: &Position, buf: &mut MoveVec) {
+    fn generate_king_moves(&self, pos: &Position, buf: &mut MoveVec, mode: GenerationMode) {
         let color = pos.side_to_move();
         let enemy_pieces = pos.pieces(color.toggle());
         let allied_pieces = pos.pieces(color);
-        let pieces = enemy_pieces.or(allied_pieces);
         for king in pos.kings(color) {
             for target in attacks::king_attacks(king) {
                 if enemy_pieces.test(target) {
-                    buf.push(Move::capture(king, target));
-                } else if !allied_pieces.test(target) {
+                    if mode.captures() {
+                        buf.push(Move::capture(king, target));
+                    }
+                } else if mode.quiets() && !allied_pieces.test(target) {
                     buf.push(Move::quiet(king, target));
                 }
             }
 
-            // Generate castling moves, if we are allowed to castle.
-            if pos.is_check(color) {
-                // No castling out of check.
+            // Generate castling moves, if we are allowed to castle. Castles never capture.
+            if !mode.quiets() {
                 continue;
             }
 
-            if pos.can_castle_kingside(color) {
-                let starting_rook = if color == Color::White {
-                    Square::H1
-                } else {
-                    Square::H8
-                };
-
-                if let Some(piece) = pos.piece_at(starting_rook) {
-                    if piece.kind == PieceKind::Rook && piece.color == color {
-                        let one = king.towards(Direction::East);
-                        let two = one.towards(Direction::East);
-                        if !pieces.test(one) && !pieces.test(two) {
-                            // The king moves across both squares one and two and it is illegal
-                            // to castle through check. We can only proceed if no enemy piece is
-                            // attacking the squares the king travels upon.
-                            if pos.squares_attacking(color.toggle(), one).empty()
-                                && pos.squares_attacking(color.toggle(), two).empty()
-                            {
-                                buf.push(Move::kingside_castle(king, two));
-                            }
-                        }
-                    }
+            for &kingside in &[true, false] {
+                if let Some(castle) = pos.castle_move(kingside) {
+                    buf.push(castle);
                 }
             }
+        }
+    }
+}
 
-            if pos.can_castle_queenside(color) {
-                let starting_rook = if color == Color::White {
-                    Square::A1
-                } else {
-                    Square::A8
-                };
+/// The checks and absolute pins against the king of the side to move, which together determine
+/// which pseudolegal moves are legal.
+struct Legality<'a> {
+    pos: &'a Position,
+    king: Square,
+    checkers: Bitboard,
+    /// The squares that a piece other than the king can move to in order to resolve a check: the
+    /// checking piece and, if it's a slider, the squares between it and the king.
+    check_mask: Bitboard,
+    /// Absolutely pinned pieces, paired with the squares that they can move to without exposing the
+    /// king: the pinning piece and the squares between it and the king.
+    pins: ArrayVec<[(Square, Bitboard); 8]>,
+}
 
-                if let Some(piece) = pos.piece_at(starting_rook) {
-                    if piece.kind == PieceKind::Rook && piece.color == color {
-                        let one = king.towards(Direction::West);
-                        let two = one.towards(Direction::West);
-                        let three = two.towards(Direction::West);
-                        if !pieces.test(one) && !pieces.test(two) && !pieces.test(three) {
-                            // Square three can be checked, but it can't be occupied. The rook
-                            // travels across square three, but the king does not.
-                            if pos.squares_attacking(color.toggle(), one).empty()
-                                && pos.squares_attacking(color.toggle(), two).empty()
-                            {
-                                buf.push(Move::queenside_castle(king, two));
-                            }
-                        }
-                    }
-                }
+impl<'a> Legality<'a> {
+    fn new(pos: &'a Position, king: Square) -> Legality<'a> {
+        let us = pos.side_to_move();
+        let them = us.toggle();
+        let checkers = pos.squares_attacking(them, king);
+        let check_mask = match checkers.count() {
+            0 => Bitboard::all(),
+            1 => {
+                let checker = checkers.first().unwrap();
+                attacks::between(king, checker) | checkers
             }
+            _ => Bitboard::none(),
+        };
+
+        // Sliders that would attack the king if it weren't for our own pieces in the way. If
+        // exactly one of our pieces is in the way, it's pinned.
+        let their_pieces = pos.pieces(them);
+        let snipers = (attacks::rook_attacks(king, their_pieces)
+            & (pos.rooks(them) | pos.queens(them)))
+            | (attacks::bishop_attacks(king, their_pieces)
+                & (pos.bishops(them) | pos.queens(them)));
+        let mut pins = ArrayVec::new();
+        for sniper in snipers {
+            let ray = attacks::between(king, sniper);
+            let blockers = ray & pos.occupied();
+            if blockers.count() == 1 && !(blockers & pos.pieces(us)).empty() {
+                let mut allowed = ray;
+                allowed.set(sniper);
+                pins.push((blockers.first().unwrap(), allowed));
+            }
+        }
+
+        Legality {
+            pos,
+            king,
+            checkers,
+            check_mask,
+            pins,
         }
     }
+
+    fn is_legal(&self, mov: Move) -> bool {
+        if mov.source() == self.king {
+            // The move generator only produces castles that don't start in, pass through, or end
+            // in check. Other king moves need their destination checked with the king out of the
+            // way, so that the king can't hide from a slider behind itself.
+            if mov.is_castle() {
+                return true;
+            }
+
+            let mut occupancy = self.pos.occupied();
+            occupancy.unset(self.king);
+            return !self.is_attacked(mov.destination(), occupancy, Bitboard::none());
+        }
+
+        // In double check, only the king can move.
+        if self.checkers.count() > 1 {
+            return false;
+        }
+
+        if mov.is_en_passant() {
+            return self.is_legal_en_passant(mov);
+        }
+
+        if !self.check_mask.test(mov.destination()) {
+            return false;
+        }
+
+        match self
+            .pins
+            .iter()
+            .find(|&&(pinned, _)| pinned == mov.source())
+        {
+            Some(&(_, allowed)) => allowed.test(mov.destination()),
+            None => true,
+        }
+    }
+
+    /// En-passant removes two pieces from the same rank at once, which can expose the king in ways
+    /// that pins don't capture, so en-passant moves are checked against the board as it will be
+    /// after the capture.
+    fn is_legal_en_passant(&self, mov: Move) -> bool {
+        let captured = match self.pos.side_to_move() {
+            Color::White => mov.destination().towards(Direction::South),
+            Color::Black => mov.destination().towards(Direction::North),
+        };
+
+        let mut occupancy = self.pos.occupied();
+        occupancy.unset(mov.source());
+        occupancy.unset(captured);
+        occupancy.set(mov.destination());
+        let mut removed = Bitboard::none();
+        removed.set(captured);
+        !self.is_attacked(self.king, occupancy, removed)
+    }
+
+    /// Returns whether or not the opponent attacks the given square, given a board with the given
+    /// occupancy and with the opponent's pieces on `removed` taken off of it.
+    fn is_attacked(&self, sq: Square, occupancy: Bitboard, removed: Bitboard) -> bool {
+        let us = self.pos.side_to_move();
+        let them = us.toggle();
+        let theirs = |kind| self.pos.pieces_of_kind(them, kind) & !removed;
+        let diagonal = theirs(PieceKind::Bishop) | theirs(PieceKind::Queen);
+        let straight = theirs(PieceKind::Rook) | theirs(PieceKind::Queen);
+        let attackers = (attacks::pawn_attacks(sq, us) & theirs(PieceKind::Pawn))
+            | (attacks::knight_attacks(sq) & theirs(PieceKind::Knight))
+            | (attacks::king_attacks(sq) & theirs(PieceKind::King))
+            | (attacks::bishop_attacks(sq, occupancy) & diagonal)
+            | (attacks::rook_attacks(sq, occupancy) & straight);
+        !attackers.empty()
+    }
 }
 
 #[cfg(test)]
@@ -243,7 +446,7 @@ mod tests {
     use super::{MoveGenerator, MoveVec};
     use crate::moves::Move;
     use crate::position::Position;
-    use crate::types::{PieceKind, Square};
+    use crate::types::{File, PieceKind, Square};
 
     fn assert_moves_generated(fen: &'static str, moves: &[Move]) {
         let pos = Position::from_fen(fen).unwrap();
@@ -547,4 +750,266 @@ mod tests {
             )
         }
     }
+
+    mod partition {
+        use super::*;
+
+        fn assert_partitions(fen: &'static str) {
+            let pos = Position::from_fen(fen).unwrap();
+            let gen = MoveGenerator::new();
+            let mut all = MoveVec::default();
+            let mut captures = MoveVec::default();
+            let mut quiets = MoveVec::default();
+            gen.generate_moves(&pos, &mut all);
+            gen.generate_captures(&pos, &mut captures);
+            gen.generate_quiets(&pos, &mut quiets);
+
+            let all: HashSet<_> = all.iter().cloned().collect();
+            let captures: HashSet<_> = captures.iter().cloned().collect();
+            let quiets: HashSet<_> = quiets.iter().cloned().collect();
+            assert!(captures.is_disjoint(&quiets), "{}", fen);
+            assert!(captures.iter().all(|m| m.is_capture()), "{}", fen);
+            assert!(quiets.iter().all(|m| !m.is_capture()), "{}", fen);
+            let union: HashSet<_> = captures.union(&quiets).cloned().collect();
+            assert_eq!(all, union, "{}", fen);
+        }
+
+        #[test]
+        fn start_position() {
+            assert_partitions("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        }
+
+        #[test]
+        fn kiwipete() {
+            assert_partitions(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            );
+        }
+
+        #[test]
+        fn en_passant() {
+            assert_partitions(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1",
+            );
+        }
+
+        #[test]
+        fn promotions() {
+            assert_partitions("r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1");
+            assert_partitions("5b2/4P3/8/8/8/8/8/8 w - - 0 1");
+        }
+
+        #[test]
+        fn captures_match_filter() {
+            let fens = [
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1",
+                "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+                "1k1r4/3r4/2nq4/8/3p4/4PN2/3R4/1K1Q3R w - - 0 1",
+                "rnbqkb1r/pp1p1ppp/2p5/4P3/2B5/8/PPP1NnPP/RNBQK2R w KQkq - 0 6",
+            ];
+            let gen = MoveGenerator::new();
+            for &fen in &fens {
+                let pos = Position::from_fen(fen).unwrap();
+                let mut all = MoveVec::default();
+                let mut captures = MoveVec::default();
+                gen.generate_moves(&pos, &mut all);
+                gen.generate_captures(&pos, &mut captures);
+
+                let filtered: HashSet<_> = all.iter().cloned().filter(|m| m.is_capture()).collect();
+                assert_eq!(filtered.len(), captures.len(), "{}", fen);
+                assert_eq!(filtered, captures.iter().cloned().collect(), "{}", fen);
+            }
+        }
+
+        #[test]
+        fn castles_are_quiet() {
+            let pos = Position::from_fen("8/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+            let gen = MoveGenerator::new();
+            let mut quiets = MoveVec::default();
+            gen.generate_quiets(&pos, &mut quiets);
+            assert!(quiets.contains(&Move::kingside_castle(Square::E1, Square::G1)));
+            assert!(quiets.contains(&Move::queenside_castle(Square::E1, Square::C1)));
+        }
+    }
+
+    mod evasions {
+        use super::*;
+
+        /// Asserts that the evasions from a position are pseudolegal and include every legal move,
+        /// and returns them.
+        fn evasions(fen: &'static str) -> HashSet<Move> {
+            let pos = Position::from_fen(fen).unwrap();
+            let gen = MoveGenerator::new();
+            let mut all = MoveVec::default();
+            let mut evasions = MoveVec::default();
+            gen.generate_moves(&pos, &mut all);
+            gen.generate_evasions(&pos, &mut evasions);
+
+            let all: HashSet<_> = all.iter().cloned().collect();
+            let evasions: HashSet<_> = evasions.iter().cloned().collect();
+            assert!(evasions.is_subset(&all), "{}", fen);
+            for mov in pos.legal_moves() {
+                assert!(evasions.contains(&mov), "{} is missing {}", fen, mov);
+            }
+
+            evasions
+        }
+
+        #[test]
+        fn not_in_check() {
+            let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+            let pos = Position::from_fen(fen).unwrap();
+            let mut all = MoveVec::default();
+            MoveGenerator::new().generate_moves(&pos, &mut all);
+            assert_eq!(all.len(), evasions(fen).len());
+        }
+
+        #[test]
+        fn block_or_capture_slider() {
+            // The rook on e8 checks the king. The bishop can block on e2 or e6, the king can step
+            // aside, and the knight can do nothing.
+            let moves = evasions("4r2k/8/8/8/2B5/8/8/4K2N w - - 0 1");
+            assert!(moves.contains(&Move::quiet(Square::C4, Square::E2)));
+            assert!(moves.contains(&Move::quiet(Square::C4, Square::E6)));
+            assert!(moves.contains(&Move::quiet(Square::E1, Square::D2)));
+            assert!(!moves.contains(&Move::quiet(Square::C4, Square::D5)));
+            assert!(!moves.contains(&Move::quiet(Square::H1, Square::G3)));
+            assert!(moves
+                .iter()
+                .all(|m| m.source() == Square::E1 || m.destination().file() == File::E));
+        }
+
+        #[test]
+        fn knight_check_cannot_be_blocked() {
+            let moves = evasions("4k3/8/8/8/8/3n4/8/R3K3 w Q - 0 1");
+            assert!(moves.iter().all(|m| m.source() == Square::E1));
+            assert!(!moves.contains(&Move::queenside_castle(Square::E1, Square::C1)));
+        }
+
+        #[test]
+        fn double_check_only_moves_king() {
+            let moves = evasions("4k3/8/8/8/1b6/8/8/r3K2R w K - 0 1");
+            assert!(!moves.is_empty());
+            assert!(moves.iter().all(|m| m.source() == Square::E1));
+        }
+
+        #[test]
+        fn en_passant_captures_checker() {
+            let moves = evasions("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1");
+            assert!(moves.contains(&Move::en_passant(Square::E4, Square::D3)));
+        }
+    }
+
+    mod legal {
+        use super::*;
+
+        /// Asserts that the legal move generator agrees with filtering pseudolegal moves, and
+        /// returns the number of legal moves.
+        fn assert_agrees_with_filter(pos: &Position) -> usize {
+            let gen = MoveGenerator::new();
+            let mut pseudolegal = MoveVec::default();
+            gen.generate_moves(pos, &mut pseudolegal);
+            let filtered: HashSet<_> = pseudolegal
+                .into_iter()
+                .filter(|&mov| pos.is_legal_given_pseudolegal(mov))
+                .collect();
+
+            let mut legal = MoveVec::default();
+            gen.generate_legal_moves(pos, &mut legal);
+            assert_eq!(filtered.len(), legal.len(), "{}", pos.as_fen());
+            assert_eq!(
+                filtered,
+                legal.iter().copied().collect::<HashSet<_>>(),
+                "{}",
+                pos.as_fen()
+            );
+            legal.len()
+        }
+
+        fn legal_count(fen: &'static str) -> usize {
+            assert_agrees_with_filter(&Position::from_fen(fen).unwrap())
+        }
+
+        #[test]
+        fn perft_positions() {
+            assert_eq!(
+                20,
+                legal_count("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+            );
+            assert_eq!(
+                48,
+                legal_count("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            );
+            assert_eq!(
+                6,
+                legal_count("r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1")
+            );
+        }
+
+        #[test]
+        fn pinned_piece_moves_along_pin() {
+            // The bishop on d2 is pinned by the bishop on a5 and can only capture it or move to c3
+            // or b4. The knight on e2 is pinned by the rook on e8 and can't move at all.
+            let pos = Position::from_fen("4r1k1/8/8/b7/8/8/3BN3/4K3 w - - 0 1").unwrap();
+            let gen = MoveGenerator::new();
+            let mut legal = MoveVec::default();
+            gen.generate_legal_moves(&pos, &mut legal);
+            let bishop_moves: Vec<_> = legal
+                .iter()
+                .filter(|mov| mov.source() == Square::D2)
+                .map(|mov| mov.destination())
+                .collect();
+            assert_eq!(3, bishop_moves.len());
+            for &sq in &[Square::C3, Square::B4, Square::A5] {
+                assert!(bishop_moves.contains(&sq));
+            }
+            assert!(legal.iter().all(|mov| mov.source() != Square::E2));
+            assert_agrees_with_filter(&pos);
+        }
+
+        #[test]
+        fn double_check_only_moves_king() {
+            // The knight on f3 and the rook on e8 both check the king on e1.
+            let pos = Position::from_fen("4r1k1/8/8/8/8/5n2/3Q4/4K3 w - - 0 1").unwrap();
+            let gen = MoveGenerator::new();
+            let mut legal = MoveVec::default();
+            gen.generate_legal_moves(&pos, &mut legal);
+            assert!(!legal.is_empty());
+            assert!(legal.iter().all(|mov| mov.source() == Square::E1));
+            assert_agrees_with_filter(&pos);
+        }
+
+        #[test]
+        fn en_passant_exposing_king_along_rank() {
+            // bxc6 takes both pawns off of the fifth rank, exposing the king to the rook on h5.
+            let pos = Position::from_fen("8/8/3p4/KPp4r/1R3p1k/8/4P1P1/8 w - c6 0 2").unwrap();
+            let gen = MoveGenerator::new();
+            let mut legal = MoveVec::default();
+            gen.generate_legal_moves(&pos, &mut legal);
+            assert!(!legal.contains(&Move::en_passant(Square::B5, Square::C6)));
+            assert_agrees_with_filter(&pos);
+        }
+
+        #[test]
+        fn en_passant_capturing_checker() {
+            // The pawn on d5 checks the king on e4, and exd6 captures it.
+            let pos = Position::from_fen("4k3/8/8/3pP3/4K3/8/8/8 w - d6 0 2").unwrap();
+            let gen = MoveGenerator::new();
+            let mut legal = MoveVec::default();
+            gen.generate_legal_moves(&pos, &mut legal);
+            assert!(legal.contains(&Move::en_passant(Square::E5, Square::D6)));
+            assert_agrees_with_filter(&pos);
+        }
+
+        #[test]
+        fn king_cannot_retreat_along_checking_ray() {
+            let pos = Position::from_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+            let gen = MoveGenerator::new();
+            let mut legal = MoveVec::default();
+            gen.generate_legal_moves(&pos, &mut legal);
+            assert!(!legal.contains(&Move::quiet(Square::E1, Square::F1)));
+            assert_agrees_with_filter(&pos);
+        }
+    }
 }
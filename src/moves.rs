@@ -171,6 +171,19 @@ impl Move {
         Move(0)
     }
 
+    /// Returns the 16-bit encoding of this move, as described in the module documentation. The
+    /// encoding is stable, so it is suitable for persisting moves to disk or sending them over the
+    /// wire.
+    pub fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    /// Reconstructs a move from its 16-bit encoding, as produced by `to_bits`. Bit patterns that
+    /// `to_bits` never produces decode to moves that are meaningless.
+    pub fn from_bits(bits: u16) -> Move {
+        Move(bits)
+    }
+
     /// If this move is a promotion, returns the piece kind that the
     /// pawn is being promoted to. Panics if the move is not a promotion.
     pub fn promotion_piece(self) -> PieceKind {
@@ -409,4 +422,30 @@ mod tests {
         let mv = Move::kingside_castle(Square::E1, Square::G1);
         assert_eq!("e1g1", mv.as_uci());
     }
+
+    #[test]
+    fn bits_round_trip() {
+        let mut moves = vec![
+            Move::null(),
+            Move::quiet(Square::A4, Square::A5),
+            Move::capture(Square::B4, Square::C4),
+            Move::en_passant(Square::E5, Square::D6),
+            Move::double_pawn_push(Square::D2, Square::D4),
+            Move::kingside_castle(Square::E1, Square::G1),
+            Move::queenside_castle(Square::E8, Square::C8),
+        ];
+        for &kind in &[
+            PieceKind::Knight,
+            PieceKind::Bishop,
+            PieceKind::Rook,
+            PieceKind::Queen,
+        ] {
+            moves.push(Move::promotion(Square::G7, Square::G8, kind));
+            moves.push(Move::promotion_capture(Square::G2, Square::H1, kind));
+        }
+
+        for mov in moves {
+            assert_eq!(mov, Move::from_bits(mov.to_bits()));
+        }
+    }
 }
@@ -5,11 +5,21 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 
 use crate::move_generator::{MoveGenerator, MoveVec};
+use crate::moves::Move;
 use crate::position::Position;
+use crate::types::{Color, GameResult};
+use crate::zobrist;
 
+/// Counts the leaf nodes of the tree of legal moves `depth` plies deep from the given position,
+/// searching the tree on the current thread. `perft_parallel` counts the same nodes using many
+/// threads.
 pub fn perft(pos: &Position, depth: u32, use_legality_test: bool) -> u64 {
     if depth == 0 {
         return 1;
@@ -19,7 +29,7 @@ pub fn perft(pos: &Position, depth: u32, use_legality_test: bool) -> u64 {
     let movegen = MoveGenerator::new();
     movegen.generate_moves(pos, &mut moves);
     return moves
-        .par_iter()
+        .iter()
         .map(|&mov| {
             if use_legality_test {
                 if pos.is_legal_given_pseudolegal(mov) {
@@ -63,9 +73,214 @@ pub fn perft(pos: &Position, depth: u32, use_legality_test: bool) -> u64 {
         .sum();
 }
 
+/// Counts the same leaf nodes as `perft`, but splits the legal moves in the given position across
+/// a pool of `threads` threads, each of which runs a sequential `perft` below its moves. Passing
+/// zero threads uses one thread per CPU.
+///
+/// This builds a new thread pool on every call; callers that count many positions should build a
+/// pool once and use `perft_in_pool` instead.
+pub fn perft_parallel(pos: &Position, depth: u32, threads: usize) -> u64 {
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("failed to build perft thread pool");
+    perft_in_pool(pos, depth, &pool)
+}
+
+/// Counts the same leaf nodes as `perft_parallel`, on the threads of an existing pool.
+pub fn perft_in_pool(pos: &Position, depth: u32, pool: &ThreadPool) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = pos.legal_moves();
+    pool.install(|| {
+        moves
+            .par_iter()
+            .map(|&mov| {
+                let mut new_pos = pos.clone();
+                new_pos.apply_move(mov);
+                perft(&new_pos, depth - 1, true)
+            })
+            .sum()
+    })
+}
+
+/// Counts the leaf nodes `depth` plies below each legal move in the given position, which is the
+/// breakdown that other engines print as "perft divide". When `perft` disagrees with a reference
+/// engine, comparing the two breakdowns points at the subtree where move generation goes wrong.
+///
+/// The moves are sorted by their UCI representation, so that the output is easy to diff.
+pub fn perft_divide(pos: &Position, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return vec![];
+    }
+
+    let mut divide: Vec<(Move, u64)> = pos
+        .legal_moves()
+        .into_iter()
+        .map(|mov| {
+            let mut new_pos = pos.clone();
+            new_pos.apply_move(mov);
+            (mov, perft(&new_pos, depth - 1, true))
+        })
+        .collect();
+    divide.sort_by_key(|&(mov, _)| mov.as_uci());
+    divide
+}
+
+/// Cross-checks three ways of producing legal moves: filtering the move generator's pseudolegal
+/// moves with `is_legal_given_pseudolegal`, generating legal moves directly with
+/// `generate_legal_moves`, and making each pseudolegal move and asking every enemy piece whether
+/// it attacks the king. The last of these shares no code with the other two beyond the attack
+/// tables. All three must agree on the set of legal moves in every position reachable from `fen`
+/// within `depth` plies.
+///
+/// Returns the number of leaf nodes at `depth`. Panics with the first position where any two of
+/// them disagree, along with the moves that they disagree on.
+pub fn verify_movegen(fen: &str, depth: u32) -> u64 {
+    fn verify(pos: &mut Position, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let movegen = MoveGenerator::new();
+        let mut pseudolegal = MoveVec::default();
+        movegen.generate_moves(pos, &mut pseudolegal);
+
+        let side_to_move = pos.side_to_move();
+        let mut filtered = vec![];
+        let mut made = vec![];
+        for &mov in &pseudolegal {
+            if pos.is_legal_given_pseudolegal(mov) {
+                filtered.push(mov);
+            }
+
+            let undo = pos.make_move(mov);
+            if !king_attacked(pos, side_to_move) {
+                made.push(mov);
+            }
+            pos.unmake_move(mov, undo);
+        }
+
+        let mut generated = MoveVec::default();
+        movegen.generate_legal_moves(pos, &mut generated);
+        check_agreement(
+            pos,
+            ("legality filter", &filtered),
+            ("generator", &generated),
+        );
+        check_agreement(pos, ("legality filter", &filtered), ("attack scan", &made));
+
+        let mut nodes = 0;
+        for &mov in &filtered {
+            let undo = pos.make_move(mov);
+            nodes += verify(pos, depth - 1);
+            pos.unmake_move(mov, undo);
+        }
+
+        nodes
+    }
+
+    /// Returns whether the given color's king is attacked by any enemy piece, found from scratch
+    /// by looking at the squares that each enemy piece attacks.
+    fn king_attacked(pos: &Position, color: Color) -> bool {
+        let occupied = pos.occupied();
+        let kings = pos.kings(color);
+        pos.pieces(color.toggle()).iter().any(|sq| {
+            let piece = pos.piece_at(sq).unwrap();
+            !(piece.attacks(sq, occupied) & kings).empty()
+        })
+    }
+
+    /// Panics if the two named sets of moves differ, listing the moves that only one of them has.
+    fn check_agreement(pos: &Position, (a_name, a): (&str, &[Move]), (b_name, b): (&str, &[Move])) {
+        let only_a: Vec<_> = a.iter().filter(|m| !b.contains(m)).collect();
+        let only_b: Vec<_> = b.iter().filter(|m| !a.contains(m)).collect();
+        if !only_a.is_empty() || !only_b.is_empty() || a.len() != b.len() {
+            panic!(
+                "move generation diverged at {}: {} alone allows {:?}, {} alone allows {:?}",
+                pos.as_fen(),
+                a_name,
+                only_a,
+                b_name,
+                only_b
+            );
+        }
+    }
+
+    let mut pos = Position::from_fen(fen).expect("invalid fen");
+    verify(&mut pos, depth)
+}
+
+/// The longest sequence of random moves that `fuzz_movegen` plays in a single iteration.
+const FUZZ_PLIES: u32 = 16;
+
+/// Plays random sequences of legal moves from random positions, checking the invariants that move
+/// application and hashing must uphold after every move:
+///
+/// 1. Applying a move to a position gives the same FEN as applying it to that position after a
+///    round trip through FEN,
+/// 2. The incrementally-updated zobrist hash matches a hash computed from scratch, and
+/// 3. A position with no legal moves is a checkmate, won by the other side, if the side to move is
+///    in check, and a stalemate, drawn, otherwise.
+///
+/// Each of the `iterations` starts from a random opening and plays until there are no legal moves
+/// or `FUZZ_PLIES` moves have been played. The same seed always plays the same games. Panics with the
+/// first position and move that violate an invariant.
+pub fn fuzz_movegen(seed: u64, iterations: u32) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..iterations {
+        let opening_plies = rng.gen_range(0, 16);
+        let mut pos = Position::random_opening(&mut rng, opening_plies);
+        for _ in 0..FUZZ_PLIES {
+            let moves = pos.legal_moves();
+            let mov = match moves.choose(&mut rng) {
+                Some(&mov) => mov,
+                None => {
+                    let side = pos.side_to_move();
+                    let expected = if pos.is_check(side) {
+                        GameResult::Win(side.toggle())
+                    } else {
+                        GameResult::Draw
+                    };
+                    assert_eq!(
+                        Some(expected),
+                        pos.outcome(),
+                        "no legal moves at {}",
+                        pos.as_fen()
+                    );
+                    break;
+                }
+            };
+
+            let fen = pos.as_fen();
+            let mut reparsed = Position::from_fen(&fen).expect("as_fen produced an invalid fen");
+            reparsed.apply_move(mov);
+            pos.apply_move(mov);
+            assert_eq!(
+                reparsed.as_fen(),
+                pos.as_fen(),
+                "applying {} to {} diverged after a fen round trip",
+                mov,
+                fen
+            );
+            assert_eq!(
+                zobrist::hash(&pos),
+                pos.zobrist_hash(),
+                "applying {} to {} produced a stale zobrist hash",
+                mov,
+                fen
+            );
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::perft;
+    use rayon::ThreadPoolBuilder;
+
+    use super::{fuzz_movegen, perft, perft_divide, perft_in_pool, perft_parallel, verify_movegen};
     use crate::position::Position;
 
     fn perft_test(fen: &'static str, depth: u32, count: u64) {
@@ -123,5 +338,86 @@ mod tests {
         position_5_2 (2): "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8" => 1486;
         position_5_3 (3): "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8" => 62379;
         position_5_4 (4): "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8" => 2103487;
+
+        chess960_1_3 (3): "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9" => 12189;
+        chess960_1_4 (4): "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9" => 326672;
+        chess960_2_3 (3): "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9" => 18002;
+        chess960_3_3 (3): "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9" => 10471;
+    }
+
+    #[test]
+    fn parallel_kiwipete_4() {
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        assert_eq!(perft(&pos, 4, true), perft_parallel(&pos, 4, 4));
+    }
+
+    #[test]
+    fn shared_pool_start_position() {
+        let pos = Position::from_start_position();
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        for depth in 0..=4 {
+            assert_eq!(perft(&pos, depth, true), perft_in_pool(&pos, depth, &pool));
+        }
+    }
+
+    #[test]
+    fn divide_start_2() {
+        let divide = perft_divide(&Position::from_start_position(), 2);
+        let moves: Vec<String> = divide.iter().map(|&(mov, _)| mov.as_uci()).collect();
+        let mut sorted = moves.clone();
+        sorted.sort();
+        assert_eq!(sorted, moves);
+        assert_eq!(20, divide.len());
+        for &(mov, count) in &divide {
+            assert_eq!(20, count, "{}", mov);
+        }
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let divide = perft_divide(&pos, 3);
+        assert_eq!(48, divide.len());
+        assert_eq!(97862, divide.iter().map(|&(_, count)| count).sum::<u64>());
+        assert!(perft_divide(&pos, 0).is_empty());
+    }
+
+    #[test]
+    fn verify_movegen_start() {
+        assert_eq!(
+            8902,
+            verify_movegen(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                3
+            )
+        );
+    }
+
+    #[test]
+    fn verify_movegen_kiwipete() {
+        assert_eq!(
+            97862,
+            verify_movegen(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                3
+            )
+        );
+    }
+
+    #[test]
+    fn verify_movegen_en_passant_pin() {
+        // bxc6 would expose the king on a5 to the rook on h5.
+        verify_movegen("8/8/3p4/KPp4r/1R3p1k/8/4P1P1/8 w - c6 0 2", 3);
+    }
+
+    #[test]
+    fn fuzz_movegen_fixed_seed() {
+        fuzz_movegen(0xa9011, 2000);
     }
 }
@@ -0,0 +1,288 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reading games in Portable Game Notation (PGN).
+use std::fmt;
+
+use crate::game::Game;
+use crate::position::{FenParseError, Position};
+
+/// An error that arose when parsing a PGN game.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnError {
+    /// A tag pair line wasn't of the form `[Name "Value"]`.
+    InvalidTag(String),
+    /// The game's `FEN` tag didn't contain a valid FEN string.
+    InvalidFen(FenParseError),
+    /// A move in the movetext wasn't legal, or couldn't be understood, in the position it was
+    /// played from.
+    IllegalMove(String),
+    /// A `{` comment or `(` variation wasn't closed before the end of the game.
+    Unterminated,
+    /// A `)` appeared outside of any variation.
+    UnmatchedParenthesis,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::InvalidTag(line) => write!(f, "invalid tag pair: {}", line),
+            PgnError::InvalidFen(err) => write!(f, "invalid FEN tag: {:?}", err),
+            PgnError::IllegalMove(san) => write!(f, "illegal move: {}", san),
+            PgnError::Unterminated => write!(f, "unterminated comment or variation"),
+            PgnError::UnmatchedParenthesis => write!(f, "unmatched ')' in movetext"),
+        }
+    }
+}
+
+/// Splits the text of a PGN file into the text of each of the games it contains. A new game
+/// starts at every line that begins with an `[Event` tag.
+pub(crate) fn split_games(text: &str) -> Vec<&str> {
+    let mut starts = vec![0];
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if offset != 0 && line.trim_start().starts_with("[Event") {
+            starts.push(offset);
+        }
+
+        offset += line.len();
+    }
+
+    starts.push(text.len());
+    starts
+        .windows(2)
+        .map(|bounds| &text[bounds[0]..bounds[1]])
+        .filter(|game| !game.trim().is_empty())
+        .collect()
+}
+
+/// Parses the text of a single PGN game. The game's moves are played from the position given by its
+/// `FEN` tag if it has one, and from the standard starting position otherwise, and its tag pairs
+/// are kept on the returned game.
+pub fn parse_pgn(text: &str) -> Result<Game, PgnError> {
+    let mut tags = vec![];
+    let mut movetext_start = text.len();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            tags.push(parse_tag(trimmed)?);
+        } else if !trimmed.is_empty() {
+            movetext_start = offset;
+            break;
+        }
+
+        offset += line.len();
+    }
+
+    let start = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Position::from_fen(fen).map_err(PgnError::InvalidFen)?,
+        None => Position::from_start_position(),
+    };
+
+    let mut game = Game::from_position(start);
+    for (name, value) in tags {
+        game.set_tag(&name, &value);
+    }
+
+    for token in movetext_tokens(&text[movetext_start..])? {
+        let san = token.trim_end_matches(['!', '?']);
+        match game.position().move_from_san(san) {
+            Some(mov) => game.make_move(mov),
+            None => return Err(PgnError::IllegalMove(token.to_owned())),
+        }
+    }
+
+    Ok(game)
+}
+
+/// Parses a tag pair line, e.g. `[Event "Casual Game"]`, into its name and value.
+fn parse_tag(line: &str) -> Result<(String, String), PgnError> {
+    let invalid = || PgnError::InvalidTag(line.to_owned());
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|l| l.strip_suffix(']'))
+        .ok_or_else(invalid)?;
+    let (name, value) = inner
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(invalid)?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    Ok((name.to_owned(), value.replace("\\\"", "\"")))
+}
+
+/// Breaks movetext into the SAN moves that it contains, discarding comments, variations, numeric
+/// annotation glyphs, move numbers, and the game termination marker.
+fn movetext_tokens(text: &str) -> Result<Vec<&str>, PgnError> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+    let mut variation_depth = 0;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' => {
+                if !chars.any(|(_, c)| c == '}') {
+                    return Err(PgnError::Unterminated);
+                }
+            }
+            ';' => while chars.next().is_some_and(|(_, c)| c != '\n') {},
+            '(' => variation_depth += 1,
+            ')' if variation_depth == 0 => return Err(PgnError::UnmatchedParenthesis),
+            ')' => variation_depth -= 1,
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, c)) = chars.peek() {
+                    if c.is_whitespace() || "{};()".contains(c) {
+                        break;
+                    }
+
+                    end = j + c.len_utf8();
+                    chars.next();
+                }
+
+                if variation_depth > 0 {
+                    continue;
+                }
+
+                // Move numbers may be attached to the move that follows them, e.g. `1.e4`.
+                let token = &text[i..end];
+                let token = match token.rfind('.') {
+                    Some(dot) if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                        &token[dot + 1..]
+                    }
+                    _ => token,
+                };
+
+                match token {
+                    "" => {}
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => break,
+                    _ if token.starts_with('$') => {}
+                    _ => tokens.push(token),
+                }
+            }
+        }
+    }
+
+    if variation_depth != 0 {
+        return Err(PgnError::Unterminated);
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{movetext_tokens, parse_pgn, split_games, PgnError};
+    use crate::game::Game;
+
+    const TWO_GAMES: &str = r#"[Event "First"]
+[Site "?"]
+[Result "1-0"]
+
+1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0
+
+[Event "Second"]
+[Site "?"]
+[Result "0-1"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+"#;
+
+    #[test]
+    fn split() {
+        let games = split_games(TWO_GAMES);
+        assert_eq!(2, games.len());
+        assert!(games[0].starts_with("[Event \"First\"]"));
+        assert!(games[1].starts_with("[Event \"Second\"]"));
+    }
+
+    #[test]
+    fn collection() {
+        let games: Vec<Game> = Game::from_pgn_collection(TWO_GAMES)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(2, games.len());
+        assert_eq!(7, games[0].moves().len());
+        assert_eq!("1-0", games[0].result_tag());
+        assert_eq!(4, games[1].moves().len());
+        assert_eq!("0-1", games[1].result_tag());
+    }
+
+    #[test]
+    fn malformed_game_does_not_abort_collection() {
+        let text = TWO_GAMES.replace("2. g4", "2. Ke3");
+        let games = Game::from_pgn_collection(&text);
+        assert_eq!(2, games.len());
+        assert_eq!(7, games[0].as_ref().unwrap().moves().len());
+        assert_eq!(
+            PgnError::IllegalMove("Ke3".to_owned()),
+            games[1].clone().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn skips_annotations() {
+        let tokens =
+            movetext_tokens("1.e4 {best by test} e5 $1 2. Nf3 (2. f4 exf4 (2... d5)) Nc6?! *")
+                .unwrap();
+        assert_eq!(vec!["e4", "e5", "Nf3", "Nc6?!"], tokens);
+    }
+
+    #[test]
+    fn unmatched_parenthesis() {
+        assert_eq!(
+            Err(PgnError::UnmatchedParenthesis),
+            movetext_tokens("1. e4 e5) 2. Nf3 (2. f4) Nc6 *")
+        );
+        assert_eq!(
+            Err(PgnError::Unterminated),
+            movetext_tokens("1. e4 e5 (1... c5 2. Nf3 *")
+        );
+    }
+
+    #[test]
+    fn fen_tag() {
+        let text = r#"[Event "Endgame"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+1. e4 Kd7 *
+"#;
+        let game = Game::from_pgn(text).unwrap();
+        assert_eq!(2, game.moves().len());
+        assert_eq!("8/3k4/8/8/4P3/8/8/4K3 w - - 1 2", game.position().as_fen());
+    }
+
+    #[test]
+    fn annotated_game() {
+        let text = r#"[Event "Casual Game"]
+[Site "London"]
+[White "Anderssen, Adolf"]
+[Black "Kieseritzky, Lionel"]
+[Result "1-0"]
+
+1. e4 e5 2. f4 exf4 {The King's Gambit, accepted.} 3. Bc4 Qh4+ $6 4. Kf1 b5?!
+(4... d6 5. Nf3) 5. Bxb5 Nf6 1-0
+"#;
+        let game = parse_pgn(text).unwrap();
+        assert_eq!(10, game.moves().len());
+        assert_eq!(
+            "rnb1kb1r/p1pp1ppp/5n2/1B6/4Pp1q/8/PPPP2PP/RNBQ1KNR w kq - 1 6",
+            game.position().as_fen()
+        );
+        assert_eq!(Some("Casual Game"), game.tag("Event"));
+        assert_eq!(Some("Kieseritzky, Lionel"), game.tag("Black"));
+        assert_eq!(Some("1-0"), game.tag("Result"));
+        assert_eq!(None, game.tag("FEN"));
+        assert_eq!(5, game.tags().len());
+    }
+}
@@ -5,12 +5,17 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use rand::seq::SliceRandom;
+use rand::Rng;
 use regex::Regex;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::{Serialize, Serializer};
 use std::convert::TryFrom;
+use std::error::Error;
 use std::fmt::{self, Write};
 
 use crate::attacks;
-use crate::bitboard::Bitboard;
+use crate::bitboard::{Bitboard, BB_DARK_SQUARES, BB_LIGHT_SQUARES};
 use crate::bitboard::{
     BB_FILE_A, BB_FILE_B, BB_FILE_C, BB_FILE_D, BB_FILE_E, BB_FILE_F, BB_FILE_G, BB_FILE_H,
 };
@@ -20,13 +25,36 @@ use crate::bitboard::{
 use crate::move_generator::{MoveGenerator, MoveVec};
 use crate::moves::Move;
 use crate::types::TableIndex;
-use crate::types::{CastleStatus, Color, Direction, File, Piece, PieceKind, Rank, Square};
-use crate::types::{FILES, PIECE_KINDS, RANKS};
+use crate::types::{
+    CastleStatus, Color, Direction, File, GameResult, Piece, PieceKind, Rank, Square,
+};
+use crate::types::{COLORS, FILES, PIECE_KINDS, RANKS, SQUARES};
 use crate::zobrist;
 
+/// An error that arose when parsing a FEN string into a `Position`, along with the byte offset into
+/// the FEN string where parsing failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FenParseError {
+    kind: FenParseErrorKind,
+    offset: usize,
+}
+
+impl FenParseError {
+    /// The reason that parsing failed.
+    pub fn kind(&self) -> FenParseErrorKind {
+        self.kind
+    }
+
+    /// The byte offset into the FEN string at which parsing failed. If parsing failed because
+    /// the string ended early, this is the length of the string.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
 /// Possible errors that can arise when parsing a FEN string into a `Position`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub enum FenParseError {
+pub enum FenParseErrorKind {
     UnexpectedChar(char),
     UnexpectedEnd,
     InvalidDigit,
@@ -41,6 +69,34 @@ pub enum FenParseError {
     InvalidFullmove,
 }
 
+/// The reason that a move is illegal in a position, as reported by `Position::explain_illegal`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IllegalReason {
+    /// There is no piece on the move's source square.
+    NoPieceAtSource,
+    /// The piece on the move's source square belongs to the side not to move.
+    WrongColor,
+    /// The move would leave the moving side's king in check.
+    LeavesKingInCheck,
+    /// The move is a castle out of or through check.
+    CastleThroughCheck,
+    /// The move is a castle, pawn push, or slide whose path is obstructed by another piece.
+    BlockedPath,
+    /// The move isn't one that the piece can make in this position, for some other reason.
+    NotPseudoLegal,
+}
+
+/// The parts of a position that `Position::make_move` destroys, which `Position::unmake_move` needs
+/// in order to restore the position as it was before the move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MoveUndo {
+    captured: Option<Piece>,
+    en_passant_square: Option<Square>,
+    castle_status: CastleStatus,
+    halfmove_clock: u32,
+    zobrist_hash: u64,
+}
+
 #[derive(Clone, Debug)]
 pub struct Position {
     boards_by_piece: [Bitboard; 12],
@@ -50,6 +106,13 @@ pub struct Position {
     fullmove_clock: u32,
     side_to_move: Color,
     castle_status: CastleStatus,
+    /// The files of the rooks that each side castles with, indexed by color and then by
+    /// `KINGSIDE` or `QUEENSIDE`. These are the h- and a-files in standard chess, but can be any
+    /// file on either side of the king in Chess960.
+    castle_rook_files: [[File; 2]; 2],
+    /// Whether this position's FEN named the file of every castling rook, as Shredder-FEN does,
+    /// rather than using `KQkq`. `as_fen` writes castle rights back out the same way.
+    shredder_castling: bool,
     zobrist_hash: u64,
     move_history: Vec<Move>,
 }
@@ -68,15 +131,112 @@ impl Position {
             fullmove_clock: 0,
             side_to_move: Color::White,
             castle_status: CastleStatus::NONE,
+            castle_rook_files: [[File::H, File::A]; 2],
+            shredder_castling: false,
             zobrist_hash: 0,
             move_history: Vec::new(),
         }
     }
 
+    /// Constructs a new position with pieces placed according to the given array, indexed by
+    /// `Square as usize`. Like `new`, the position has white to move and no castling rights.
+    pub fn from_array(board: &[Option<Piece>; 64]) -> Position {
+        let mut pos = Position::new();
+        for &sq in SQUARES.iter() {
+            if let Some(piece) = board[sq.as_index()] {
+                pos.add_piece(sq, piece).expect("array double-add piece?");
+            }
+        }
+
+        pos.zobrist_hash = zobrist::hash(&pos);
+        pos
+    }
+
+    /// Constructs a new position from a board diagram in the format that `Display` produces: one
+    /// row per rank, from the eighth rank down, with a piece letter or `.` for each square. Text
+    /// after a `|` on a row is ignored, as is everything from the first line that begins with `-`
+    /// onward, so the rank and file labels that `Display` prints may be left in or taken out.
+    ///
+    /// Diagrams only describe piece placement, so like `from_array` the position has white to move
+    /// and no castling rights. It starts at the first move of the game.
+    pub fn from_board_string<S: AsRef<str>>(board: S) -> Result<Position, FenParseError> {
+        let board = board.as_ref();
+        let error = |kind, offset| Err(FenParseError { kind, offset });
+        let mut pieces = [None; 64];
+        let mut ranks = RANKS.iter().rev();
+        let mut line_start = 0;
+        for line in board.split_inclusive('\n') {
+            let offset = line_start;
+            line_start += line.len();
+            let row = line.split('|').next().unwrap_or("");
+            if row.trim().is_empty() {
+                continue;
+            }
+
+            if row.trim_start().starts_with('-') {
+                break;
+            }
+
+            let rank = match ranks.next() {
+                Some(&rank) => rank,
+                None => {
+                    return error(
+                        FenParseErrorKind::UnexpectedChar(row.trim().chars().next().unwrap()),
+                        offset,
+                    )
+                }
+            };
+
+            let mut files = FILES.iter();
+            for (idx, c) in row.char_indices().filter(|(_, c)| !c.is_whitespace()) {
+                let file = match files.next() {
+                    Some(&file) => file,
+                    None => return error(FenParseErrorKind::FileDoesNotSumToEight, offset + idx),
+                };
+
+                if c == '.' {
+                    continue;
+                }
+
+                match Piece::try_from(c) {
+                    Ok(piece) => pieces[Square::of(rank, file).as_index()] = Some(piece),
+                    Err(()) => return error(FenParseErrorKind::UnknownPiece, offset + idx),
+                }
+            }
+
+            if files.next().is_some() {
+                return error(FenParseErrorKind::FileDoesNotSumToEight, offset + row.len());
+            }
+        }
+
+        if ranks.next().is_some() {
+            return error(FenParseErrorKind::UnexpectedEnd, board.len());
+        }
+
+        let mut pos = Position::from_array(&pieces);
+        pos.fullmove_clock = 1;
+        Ok(pos)
+    }
+
     pub fn en_passant_square(&self) -> Option<Square> {
         self.en_passant_square
     }
 
+    /// Returns the en passant square if a pawn of the side to move stands ready to capture on it,
+    /// or `None` otherwise. Only such squares are part of the zobrist hash, so that positions that
+    /// differ in nothing but an en passant square that no pawn can use share a hash. Like Polyglot,
+    /// this doesn't check whether the capture would leave the king in check.
+    pub fn capturable_en_passant_square(&self) -> Option<Square> {
+        let ep_square = self.en_passant_square?;
+        let side = self.side_to_move;
+        let capturers = attacks::pawn_attacks(ep_square, side.toggle()) & self.pawns(side);
+        if capturers.empty() {
+            None
+        } else {
+            Some(ep_square)
+        }
+    }
+
     pub fn halfmove_clock(&self) -> u32 {
         self.halfmove_clock
     }
@@ -107,16 +267,67 @@ impl Position {
         }
     }
 
+    /// Returns the square of the rook that the given side castles kingside with. This is the
+    /// h-file rook in standard chess, but can be any rook to the king's right in Chess960.
+    pub fn kingside_castle_rook(&self, color: Color) -> Square {
+        Square::of(
+            back_rank(color),
+            self.castle_rook_files[color.as_index()][KINGSIDE],
+        )
+    }
+
+    /// Returns the square of the rook that the given side castles queenside with. This is the
+    /// a-file rook in standard chess, but can be any rook to the king's left in Chess960.
+    pub fn queenside_castle_rook(&self, color: Color) -> Square {
+        Square::of(
+            back_rank(color),
+            self.castle_rook_files[color.as_index()][QUEENSIDE],
+        )
+    }
+
     pub fn pieces(&self, color: Color) -> Bitboard {
         self.boards_by_color[color.as_index()]
     }
 
+    /// Returns the set of squares occupied by a piece of either color.
+    pub fn occupied(&self) -> Bitboard {
+        self.pieces(Color::White) | self.pieces(Color::Black)
+    }
+
+    /// Returns one bitboard per piece, suitable for use as feature planes. The planes are ordered
+    /// by color and then by kind: white pawns, knights, bishops, rooks, queens, and king, followed
+    /// by black pawns, knights, bishops, rooks, queens, and king.
+    pub fn piece_planes(&self) -> [Bitboard; 12] {
+        self.boards_by_piece
+    }
+
+    /// Returns a bitboard with the en-passant square set, if there is one.
+    pub fn en_passant_plane(&self) -> Bitboard {
+        let mut plane = Bitboard::none();
+        if let Some(sq) = self.en_passant_square {
+            plane.set(sq);
+        }
+
+        plane
+    }
+
+    /// Returns a bitboard with the starting square of every rook that can still castle set.
+    pub fn castling_plane(&self) -> Bitboard {
+        let mut plane = Bitboard::none();
+        for &color in COLORS.iter() {
+            if self.can_castle_kingside(color) {
+                plane.set(self.kingside_castle_rook(color));
+            }
+            if self.can_castle_queenside(color) {
+                plane.set(self.queenside_castle_rook(color));
+            }
+        }
+
+        plane
+    }
+
     pub fn pieces_of_kind(&self, color: Color, kind: PieceKind) -> Bitboard {
-        let offset = match color {
-            Color::White => 0,
-            Color::Black => 6,
-        };
-        self.boards_by_piece[offset + kind.as_index()]
+        self.boards_by_piece[Piece::new(kind, color).board_index()]
     }
 
     pub fn pawns(&self, color: Color) -> Bitboard {
@@ -143,9 +354,73 @@ impl Position {
         self.pieces_of_kind(color, PieceKind::King)
     }
 
+    /// Returns the files that have no pawns of either color on them.
+    pub fn open_files(&self) -> Bitboard {
+        !file_fill(self.pawns(Color::White) | self.pawns(Color::Black))
+    }
+
+    /// Returns the files that have no pawns of the given color on them. Open files are half-open
+    /// for both colors.
+    pub fn half_open_files(&self, color: Color) -> Bitboard {
+        !file_fill(self.pawns(color))
+    }
+
+    /// Returns whether or not neither side has enough material left to checkmate the other: bare
+    /// kings, a king and a single bishop or knight against a king, or kings and any number of
+    /// bishops that all travel on squares of the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut majors_and_pawns = Bitboard::none();
+        let mut knights = Bitboard::none();
+        let mut bishops = Bitboard::none();
+        for &color in &COLORS {
+            majors_and_pawns |= self.pawns(color) | self.rooks(color) | self.queens(color);
+            knights |= self.knights(color);
+            bishops |= self.bishops(color);
+        }
+
+        if !majors_and_pawns.empty() {
+            return false;
+        }
+
+        if (knights | bishops).count() <= 1 {
+            return true;
+        }
+
+        knights.empty()
+            && ((bishops & BB_LIGHT_SQUARES).empty() || (bishops & BB_DARK_SQUARES).empty())
+    }
+
     pub fn move_history(&self) -> &[Move] {
         &self.move_history
     }
+
+    /// Lists the squares whose contents differ between this position and another position, along
+    /// with the piece on that square in this position and in the other position, respectively.
+    /// Squares are listed in ascending order. Useful for debugging move application.
+    pub fn diff(&self, other: &Position) -> Vec<(Square, Option<Piece>, Option<Piece>)> {
+        SQUARES
+            .iter()
+            .filter_map(|&sq| {
+                let ours = self.piece_at(sq);
+                let theirs = other.piece_at(sq);
+                if ours != theirs {
+                    Some((sq, ours, theirs))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the contents of every square on the board, indexed by `Square as usize`.
+    pub fn to_array(&self) -> [Option<Piece>; 64] {
+        let mut board = [None; 64];
+        for &sq in SQUARES.iter() {
+            board[sq.as_index()] = self.piece_at(sq);
+        }
+
+        board
+    }
 }
 
 //
@@ -159,8 +434,7 @@ impl Position {
         }
 
         self.boards_by_color[piece.color as usize].set(square);
-        let offset = if piece.color == Color::White { 0 } else { 6 };
-        self.boards_by_piece[piece.kind as usize + offset].set(square);
+        self.boards_by_piece[piece.board_index()].set(square);
         zobrist::modify_piece(&mut self.zobrist_hash, square, piece);
         Ok(())
     }
@@ -173,29 +447,24 @@ impl Position {
         };
 
         self.boards_by_color[existing_piece.color.as_index()].unset(square);
-        let offset = if existing_piece.color == Color::White {
-            0
-        } else {
-            6
-        };
-        self.boards_by_piece[existing_piece.kind.as_index() + offset].unset(square);
+        self.boards_by_piece[existing_piece.board_index()].unset(square);
         zobrist::modify_piece(&mut self.zobrist_hash, square, existing_piece);
         Ok(())
     }
 
     pub fn piece_at(&self, square: Square) -> Option<Piece> {
-        let (board_offset, color) = if self.boards_by_color[Color::White as usize].test(square) {
-            (0, Color::White)
+        let color = if self.boards_by_color[Color::White as usize].test(square) {
+            Color::White
         } else if self.boards_by_color[Color::Black as usize].test(square) {
-            (6, Color::Black)
+            Color::Black
         } else {
             return None;
         };
 
         for &kind in &PIECE_KINDS {
-            let board = self.boards_by_piece[kind as usize + board_offset];
-            if board.test(square) {
-                return Some(Piece::new(kind, color));
+            let piece = Piece::new(kind, color);
+            if self.boards_by_piece[piece.board_index()].test(square) {
+                return Some(piece);
             }
         }
 
@@ -203,12 +472,32 @@ impl Position {
         unreachable!()
     }
 
+    /// Returns a copy of this position with the given side to move. Since en passant is only legal
+    /// immediately after a double pawn push, the copy has no en passant square. Unlike a null move,
+    /// the clocks are left untouched.
+    ///
+    /// This is useful for asking what the opponent would do if it were their move.
+    pub fn with_side_to_move(&self, color: Color) -> Position {
+        let mut pos = self.clone();
+        let ep_square = pos.capturable_en_passant_square();
+        zobrist::modify_en_passant(&mut pos.zobrist_hash, ep_square, None);
+        pos.en_passant_square = None;
+        if pos.side_to_move != color {
+            pos.side_to_move = color;
+            zobrist::modify_side_to_move(&mut pos.zobrist_hash);
+        }
+
+        pos
+    }
+
     pub fn apply_move(&mut self, mov: Move) {
         // Quick out for null moves:
         //  1. EP is not legal next turn.
         //  2. Halfmove clock always increases.
         //  3. Fullmove clock increases if Black makes the null move.
         if mov.is_null() {
+            let ep_square = self.capturable_en_passant_square();
+            zobrist::modify_en_passant(&mut self.zobrist_hash, ep_square, None);
             self.en_passant_square = None;
             self.side_to_move = self.side_to_move.toggle();
             zobrist::modify_side_to_move(&mut self.zobrist_hash);
@@ -218,11 +507,25 @@ impl Position {
             return;
         }
 
+        // Applying a move that isn't even pseudo-legal corrupts the position, often without
+        // panicking, so catch it here in debug builds.
+        debug_assert!(
+            self.is_pseudo_legal(mov),
+            "apply_move: move {} is not pseudo-legal in position {}",
+            mov,
+            self.as_fen()
+        );
         self.move_history.push(mov);
         let moving_piece = self
             .piece_at(mov.source())
             .expect("invalid move: no piece at source square");
 
+        // Whether a pawn can capture en passant depends on where the pawns stand and whose move it
+        // is, both of which the move changes, so the en passant square comes out of the hash now
+        // and goes back in once the move has been made.
+        let ep_square = self.capturable_en_passant_square();
+        zobrist::modify_en_passant(&mut self.zobrist_hash, ep_square, None);
+
         // If this move is a capture, we need to remove the captured piece from the board before we
         // proceed.
         if mov.is_capture() {
@@ -248,48 +551,22 @@ impl Position {
             // Remove the piece from the board - it has been captured.
             self.remove_piece(target_square)
                 .expect("invalid move: no piece at capture target");
-
-            // If this piece is a rook on its starting square, invalidate the castle for the other
-            // player.
-            if target_square == kingside_rook(self.side_to_move.toggle()) {
-                self.castle_status &= !kingside_castle_mask(self.side_to_move.toggle());
-                zobrist::modify_kingside_castle(&mut self.zobrist_hash, self.side_to_move.toggle());
-            } else if target_square == queenside_rook(self.side_to_move.toggle()) {
-                self.castle_status &= !queenside_castle_mask(self.side_to_move.toggle());
-                zobrist::modify_queenside_castle(
-                    &mut self.zobrist_hash,
-                    self.side_to_move.toggle(),
-                );
-            }
         }
 
-        // The move destination square is now guaranteed to be empty. Next we need to handle moves
-        // that end up in places other than the destination square.
-        if mov.is_castle() {
-            // Castles are encoded using the king's start and stop position. Notably, the rook is
-            // not at the move's destination square.
-            //
-            // Castles are also interesting in that two pieces move, so we'll handle the move of
-            // the rook here and handle the movement of the king later on in the function.
-            let (post_castle_dir, pre_castle_dir, num_squares) = if mov.is_kingside_castle() {
-                (Direction::West, Direction::East, 1)
-            } else {
-                (Direction::East, Direction::West, 2)
-            };
-
-            let new_rook_square = mov.destination().towards(post_castle_dir);
-            let mut rook_square = mov.destination();
-            for _ in 0..num_squares {
-                rook_square = rook_square.towards(pre_castle_dir);
-            }
-
+        // Castles are the only moves that move two pieces. Castles are encoded using the king's
+        // start and stop squares, so the rook's squares come from the castle rights. In Chess960
+        // the king can land on the rook's square and vice versa, so the rook is taken off of the
+        // board until the king has moved.
+        let castling_rook = if mov.is_castle() {
+            let (rook_square, new_rook_square) = self.castle_rook_squares(mov);
             let rook = self
                 .piece_at(rook_square)
                 .expect("invalid move: castle without rook");
             self.remove_piece(rook_square).unwrap();
-            self.add_piece(new_rook_square, rook)
-                .expect("invalid move: piece at rook target square");
-        }
+            Some((rook, new_rook_square))
+        } else {
+            None
+        };
 
         // Now, we're going to add the moving piece to the destination square. Unless this is a
         // promotion, the piece that we add to the destination is the piece that is currently at
@@ -304,6 +581,11 @@ impl Position {
             .expect("invalid move: no piece at source square");
         self.add_piece(mov.destination(), piece_to_add)
             .expect("invalid move: piece at destination square");
+        if let Some((rook, new_rook_square)) = castling_rook {
+            self.add_piece(new_rook_square, rook)
+                .expect("invalid move: piece at rook target square");
+        }
+
         if mov.is_double_pawn_push() {
             // Double pawn pushes set the en-passant square.
             let ep_dir = if self.side_to_move == Color::White {
@@ -313,45 +595,31 @@ impl Position {
             };
 
             let ep_square = mov.destination().towards(ep_dir);
-            zobrist::modify_en_passant(
-                &mut self.zobrist_hash,
-                self.en_passant_square,
-                Some(ep_square),
-            );
             self.en_passant_square = Some(ep_square);
         } else {
             // All other moves clear the en-passant square.
-            zobrist::modify_en_passant(&mut self.zobrist_hash, self.en_passant_square, None);
             self.en_passant_square = None;
         }
 
-        // Re-calculate our castle status. Side to move may have invalidated their castle rights
-        // by moving their rooks or king.
-        if moving_piece.kind == PieceKind::Rook {
-            // Moving a rook invalidates the castle on that rook's side of the board.
-
-            if self.can_castle_queenside(self.side_to_move)
-                && mov.source() == queenside_rook(self.side_to_move)
-            {
-                // Move of the queenside rook. Can't castle queenside anymore.
-                self.castle_status &= !queenside_castle_mask(self.side_to_move);
-                zobrist::modify_queenside_castle(&mut self.zobrist_hash, self.side_to_move);
-            } else if self.can_castle_kingside(self.side_to_move)
-                && mov.source() == kingside_rook(self.side_to_move)
-            {
-                // Move of the kingside rook. Can't castle kingside anymore.
-                self.castle_status &= !kingside_castle_mask(self.side_to_move);
-                zobrist::modify_kingside_castle(&mut self.zobrist_hash, self.side_to_move);
-            }
-        } else if moving_piece.kind == PieceKind::King {
-            // Moving a king invalides the castle on both sides of the board.
-            self.castle_status &= !castle_mask(self.side_to_move);
-            zobrist::modify_queenside_castle(&mut self.zobrist_hash, self.side_to_move);
-            zobrist::modify_kingside_castle(&mut self.zobrist_hash, self.side_to_move);
+        // Re-calculate our castle status. A king move gives up both of that side's castles, and any
+        // move from or to a castling rook's square gives up the castle with that rook: either it
+        // has moved or it has been captured.
+        let old_castle_status = self.castle_status;
+        if moving_piece.kind == PieceKind::King {
+            self.castle_status &= !castle_mask(moving_piece.color);
         }
+        self.castle_status &=
+            !(self.castle_rights_of(mov.source()) | self.castle_rights_of(mov.destination()));
+        zobrist::modify_castle_status(
+            &mut self.zobrist_hash,
+            old_castle_status,
+            self.castle_status,
+        );
 
         self.side_to_move = self.side_to_move.toggle();
         zobrist::modify_side_to_move(&mut self.zobrist_hash);
+        let ep_square = self.capturable_en_passant_square();
+        zobrist::modify_en_passant(&mut self.zobrist_hash, None, ep_square);
         if mov.is_capture() || moving_piece.kind == PieceKind::Pawn {
             self.halfmove_clock = 0;
         } else {
@@ -362,6 +630,96 @@ impl Position {
             self.fullmove_clock += 1;
         }
     }
+
+    /// Applies a move, like `apply_move`, and returns the state needed to take the move back with
+    /// `unmake_move`. This is cheaper than cloning the position in order to keep the original.
+    pub fn make_move(&mut self, mov: Move) -> MoveUndo {
+        let captured = if mov.is_en_passant() {
+            Some(Piece::new(PieceKind::Pawn, self.side_to_move.toggle()))
+        } else if mov.is_capture() {
+            self.piece_at(mov.destination())
+        } else {
+            None
+        };
+
+        let undo = MoveUndo {
+            captured,
+            en_passant_square: self.en_passant_square,
+            castle_status: self.castle_status,
+            halfmove_clock: self.halfmove_clock,
+            zobrist_hash: self.zobrist_hash,
+        };
+
+        self.apply_move(mov);
+        undo
+    }
+
+    /// Takes back a move made by `make_move`, restoring the position exactly as it was before the
+    /// move. `mov` and `undo` must be the move most recently made and the state that making it
+    /// returned.
+    pub fn unmake_move(&mut self, mov: Move, undo: MoveUndo) {
+        self.side_to_move = self.side_to_move.toggle();
+        if self.side_to_move == Color::Black {
+            self.fullmove_clock -= 1;
+        }
+
+        if !mov.is_null() {
+            self.move_history.pop();
+            let moved_piece = self
+                .piece_at(mov.destination())
+                .expect("invalid unmake: no piece at destination square");
+            let original_piece = if mov.is_promotion() {
+                Piece::new(PieceKind::Pawn, self.side_to_move)
+            } else {
+                moved_piece
+            };
+
+            // As when making a castle, the rook comes off of the board first so that the king and
+            // rook can trade places.
+            let castling_rook = if mov.is_castle() {
+                let (rook_square, new_rook_square) = self.castle_rook_squares(mov);
+                let rook = self
+                    .piece_at(new_rook_square)
+                    .expect("invalid unmake: castle without rook");
+                self.remove_piece(new_rook_square).unwrap();
+                Some((rook, rook_square))
+            } else {
+                None
+            };
+
+            self.remove_piece(mov.destination()).unwrap();
+            self.add_piece(mov.source(), original_piece)
+                .expect("invalid unmake: piece at source square");
+            if let Some((rook, rook_square)) = castling_rook {
+                self.add_piece(rook_square, rook)
+                    .expect("invalid unmake: piece at rook square");
+            }
+
+            if let Some(captured) = undo.captured {
+                let target_square = if mov.is_en_passant() {
+                    let ep_dir = if self.side_to_move == Color::White {
+                        Direction::South
+                    } else {
+                        Direction::North
+                    };
+
+                    mov.destination().towards(ep_dir)
+                } else {
+                    mov.destination()
+                };
+
+                self.add_piece(target_square, captured)
+                    .expect("invalid unmake: piece at capture target");
+            }
+        }
+
+        // Everything else comes straight from the undo state, including the hash, which the piece
+        // movements above have scrambled.
+        self.en_passant_square = undo.en_passant_square;
+        self.castle_status = undo.castle_status;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.zobrist_hash = undo.zobrist_hash;
+    }
 }
 
 //
@@ -435,6 +793,59 @@ impl Position {
         attacks
     }
 
+    /// Returns the material balance, in pawns, from the perspective of the side to move. Kings
+    /// aren't counted.
+    pub fn material_for_side_to_move(&self) -> i32 {
+        let us = self.side_to_move();
+        let mut balance = 0;
+        for kind in PieceKind::iter().filter(|&kind| kind != PieceKind::King) {
+            let ours = self.pieces_of_kind(us, kind).count() as i32;
+            let theirs = self.pieces_of_kind(us.toggle(), kind).count() as i32;
+            balance += kind.value() * (ours - theirs);
+        }
+
+        balance
+    }
+
+    /// Returns every piece bearing on the given square, as a pair of bitboards of the white
+    /// attackers and the black attackers.
+    pub fn attack_defense(&self, sq: Square) -> (Bitboard, Bitboard) {
+        (
+            self.squares_attacking(Color::White, sq),
+            self.squares_attacking(Color::Black, sq),
+        )
+    }
+
+    /// Returns the square of the least valuable piece of the given color that attacks the target
+    /// square, if any. Only pieces present in the given occupancy are considered, and sliding
+    /// pieces are blocked only by pieces present in it; removing pieces from the occupancy as they
+    /// are exchanged uncovers any x-ray attackers behind them.
+    pub fn least_valuable_attacker(
+        &self,
+        color: Color,
+        target: Square,
+        occupancy: Bitboard,
+    ) -> Option<Square> {
+        for kind in PieceKind::iter() {
+            let reach = match kind {
+                // Pawns attack a square from the squares that an opposing pawn there would attack.
+                PieceKind::Pawn => attacks::pawn_attacks(target, color.toggle()),
+                PieceKind::Knight => attacks::knight_attacks(target),
+                PieceKind::Bishop => attacks::bishop_attacks(target, occupancy),
+                PieceKind::Rook => attacks::rook_attacks(target, occupancy),
+                PieceKind::Queen => attacks::queen_attacks(target, occupancy),
+                PieceKind::King => attacks::king_attacks(target),
+            };
+
+            let attackers = reach & self.pieces_of_kind(color, kind) & occupancy;
+            if let Some(attacker) = attackers.first() {
+                return Some(attacker);
+            }
+        }
+
+        None
+    }
+
     pub fn is_check(&self, color: Color) -> bool {
         for king in self.kings(color) {
             if !self.squares_attacking(color.toggle(), king).empty() {
@@ -490,20 +901,434 @@ impl Position {
             }
         }
 
-        false
+        false
+    }
+
+    /// Returns whether or not the given en-passant move exposes the moving player's king to a
+    /// horizontal attack. En-passant is the only move that removes two pieces from the same rank
+    /// at once, so it can uncover an attack along that rank that neither pawn was pinned against
+    /// individually. Returns false for moves that aren't en-passant.
+    pub fn en_passant_discovers_check(&self, mov: Move) -> bool {
+        if !mov.is_en_passant() {
+            return false;
+        }
+
+        // Both pawns sit on the source rank: the captured pawn is beside the capturing one, on the
+        // destination file.
+        let to_move = self.side_to_move();
+        let rank = mov.source().rank();
+        let captured = Square::of(rank, mov.destination().file());
+        let mut occupancy = self.occupied();
+        occupancy.unset(mov.source());
+        occupancy.unset(captured);
+        occupancy.set(mov.destination());
+
+        let rank_sliders = (self.rooks(to_move.toggle()) | self.queens(to_move.toggle()))
+            & Bitboard::all().rank(rank);
+        for king in self.kings(to_move) & Bitboard::all().rank(rank) {
+            if !(attacks::rook_attacks(king, occupancy) & rank_sliders).empty() {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns whether or not the given move captures a piece in this position. Unlike
+    /// `Move::is_capture`, this doesn't rely on the move's flags, only on the contents of the
+    /// board.
+    pub fn is_capture(&self, mov: Move) -> bool {
+        self.captured_piece(mov).is_some()
+    }
+
+    /// Returns the piece that the given move captures in this position, if any. Like
+    /// `is_capture`, this doesn't rely on the move's flags, with the exception of en-passant: a
+    /// pawn moving to the en-passant square is assumed to capture en-passant.
+    pub fn captured_piece(&self, mov: Move) -> Option<Piece> {
+        let mover = self.piece_at(mov.source())?;
+        if mover.kind == PieceKind::Pawn && self.en_passant_square == Some(mov.destination()) {
+            return Some(Piece::new(PieceKind::Pawn, mover.color.toggle()));
+        }
+
+        self.piece_at(mov.destination())
+            .filter(|piece| piece.color != mover.color)
+    }
+
+    /// Returns every legal move from this position.
+    pub fn legal_moves(&self) -> MoveVec {
+        let gen = MoveGenerator::new();
+        let mut moves = MoveVec::default();
+        gen.generate_legal_moves(self, &mut moves);
+        moves
+    }
+
+    /// Returns whether or not the given legal move checks the opponent's king. Apart from castling
+    /// and en-passant, which are rare enough to be handled by making the move, this doesn't need
+    /// to make the move to find out.
+    pub fn gives_check(&self, mov: Move) -> bool {
+        let us = self.side_to_move();
+        if mov.is_castle() || mov.is_en_passant() {
+            let mut child = self.clone();
+            child.apply_move(mov);
+            return child.is_check(us.toggle());
+        }
+
+        let king = match self.kings(us.toggle()).first() {
+            Some(king) => king,
+            None => return false,
+        };
+
+        let moving_piece = self
+            .piece_at(mov.source())
+            .expect("invalid move: no piece at source square");
+        let kind = if mov.is_promotion() {
+            mov.promotion_piece()
+        } else {
+            moving_piece.kind
+        };
+
+        let mut occupancy = self.occupied();
+        occupancy.unset(mov.source());
+        occupancy.set(mov.destination());
+
+        // Direct check, from the moved piece itself.
+        if Piece::new(kind, us)
+            .attacks(mov.destination(), occupancy)
+            .test(king)
+        {
+            return true;
+        }
+
+        // Discovered check, from a slider that the moved piece was blocking.
+        let mut sliders = self.bishops(us) | self.rooks(us) | self.queens(us);
+        sliders.unset(mov.source());
+        for slider in sliders {
+            let piece = self.piece_at(slider).unwrap();
+            if piece.attacks(slider, occupancy).test(king) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns whether or not the given legal move checkmates the opponent.
+    pub fn gives_checkmate(&self, mov: Move) -> bool {
+        if !self.gives_check(mov) {
+            return false;
+        }
+
+        let mut child = self.clone();
+        child.apply_move(mov);
+        child.legal_moves().is_empty()
+    }
+
+    /// Returns whether or not the side to move has been checkmated: it is in check and has no legal
+    /// moves.
+    pub fn is_checkmate(&self) -> bool {
+        self.legal_moves().is_empty() && self.is_check(self.side_to_move())
+    }
+
+    /// Returns whether or not the side to move has been stalemated: it is not in check, but has no
+    /// legal moves.
+    pub fn is_stalemate(&self) -> bool {
+        self.legal_moves().is_empty() && !self.is_check(self.side_to_move())
+    }
+
+    /// Returns the result of the game if it is over in this position, either by checkmate,
+    /// stalemate or the fifty-move rule.
+    pub fn outcome(&self) -> Option<GameResult> {
+        if self.legal_moves().is_empty() {
+            let to_move = self.side_to_move();
+            if self.is_check(to_move) {
+                return Some(GameResult::Win(to_move.toggle()));
+            }
+
+            return Some(GameResult::Draw);
+        }
+
+        if self.halfmove_clock >= 100 {
+            return Some(GameResult::Draw);
+        }
+
+        None
+    }
+
+    /// Move legality test. Returns true if this move is a legal move from the given position. If
+    /// the move is know to be psuedolegal, `is_legal_given_pseudolegal` will likely be faster.
+    pub fn is_legal(&self, mov: Move) -> bool {
+        self.is_pseudo_legal(mov) && self.is_legal_given_pseudolegal(mov)
+    }
+
+    /// Pseudo-legality test for a single move. Returns true if the move generator would produce
+    /// this move from this position, without paying the cost of generating every move. This is
+    /// useful for vetting moves that come from untrusted sources, such as a UCI client, before
+    /// applying them.
+    pub fn is_pseudo_legal(&self, mov: Move) -> bool {
+        if mov.is_null() {
+            return false;
+        }
+
+        let color = self.side_to_move();
+        let moving_piece = match self.piece_at(mov.source()) {
+            Some(piece) if piece.color == color => piece,
+            _ => return false,
+        };
+
+        // In Chess960, a castling king can land on its own rook's square.
+        if mov.is_castle() {
+            return moving_piece.kind == PieceKind::King && self.is_pseudolegal_castle(mov);
+        }
+
+        // No other move can land on an allied piece.
+        if self.pieces(color).test(mov.destination()) {
+            return false;
+        }
+
+        if moving_piece.kind == PieceKind::Pawn {
+            return self.is_pseudolegal_pawn_move(mov);
+        }
+
+        // The remaining special moves can only be made by pawns.
+        if mov.is_promotion() || mov.is_en_passant() || mov.is_double_pawn_push() {
+            return false;
+        }
+
+        // Captures must capture something and quiet moves must not.
+        let enemy_pieces = self.pieces(color.toggle());
+        if mov.is_capture() != enemy_pieces.test(mov.destination()) {
+            return false;
+        }
+
+        let occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+        moving_piece
+            .attacks(mov.source(), occupancy)
+            .test(mov.destination())
+    }
+
+    fn is_pseudolegal_pawn_move(&self, mov: Move) -> bool {
+        let color = self.side_to_move();
+        let (start_rank, promo_rank, pawn_dir) = match color {
+            Color::White => (Rank::Two, Rank::Eight, Direction::North),
+            Color::Black => (Rank::Seven, Rank::One, Direction::South),
+        };
+
+        let source = mov.source();
+        let dest = mov.destination();
+        if source.rank() == promo_rank {
+            // Pawns shouldn't be on the promotion rank, and there's nowhere for them to go anyway.
+            return false;
+        }
+
+        if mov.is_en_passant() {
+            return self.en_passant_square() == Some(dest)
+                && attacks::pawn_attacks(source, color).test(dest);
+        }
+
+        // Moves to the promotion rank must promote, and promotions must go to the promotion rank.
+        if (dest.rank() == promo_rank) != mov.is_promotion() {
+            return false;
+        }
+
+        if mov.is_capture() {
+            return self.pieces(color.toggle()).test(dest)
+                && attacks::pawn_attacks(source, color).test(dest);
+        }
+
+        let occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+        let one = source.towards(pawn_dir);
+        if occupancy.test(one) {
+            return false;
+        }
+
+        if mov.is_double_pawn_push() {
+            return source.rank() == start_rank
+                && dest == one.towards(pawn_dir)
+                && !occupancy.test(dest);
+        }
+
+        dest == one
+    }
+
+    fn is_pseudolegal_castle(&self, mov: Move) -> bool {
+        self.castle_illegality(mov).is_none()
+    }
+
+    /// Returns why the given castle isn't pseudo-legal, or `None` if it is.
+    ///
+    /// Castles follow the Chess960 rules, of which standard chess castles are a special case: the
+    /// king ends up on the g-file (kingside) or c-file (queenside) and the rook on the square on
+    /// the other side of it, every square that either piece travels across or lands on must be
+    /// empty other than the king and rook themselves, and no square the king travels across or
+    /// lands on may be attacked.
+    fn castle_illegality(&self, mov: Move) -> Option<IllegalReason> {
+        let color = self.side_to_move();
+        let (can_castle, king_file) = if mov.is_kingside_castle() {
+            (self.can_castle_kingside(color), File::G)
+        } else {
+            (self.can_castle_queenside(color), File::C)
+        };
+
+        let king = mov.source();
+        if !can_castle
+            || king.rank() != back_rank(color)
+            || mov.destination() != Square::of(back_rank(color), king_file)
+        {
+            return Some(IllegalReason::NotPseudoLegal);
+        }
+
+        let (rook, new_rook_square) = self.castle_rook_squares(mov);
+        match self.piece_at(rook) {
+            Some(piece) if piece.kind == PieceKind::Rook && piece.color == color => {}
+            _ => return Some(IllegalReason::NotPseudoLegal),
+        }
+
+        let mut occupancy = self.occupied();
+        occupancy.unset(king);
+        occupancy.unset(rook);
+        let king_path = rank_span(king, mov.destination());
+        if !((king_path | rank_span(rook, new_rook_square)) & occupancy).empty() {
+            return Some(IllegalReason::BlockedPath);
+        }
+
+        // The king can't castle out of, through, or into check. The king and rook are left out of
+        // the occupancy, since a rook that moves off of the back rank can uncover an attack.
+        if king_path.iter().any(|sq| {
+            self.least_valuable_attacker(color.toggle(), sq, occupancy)
+                .is_some()
+        }) {
+            return Some(IllegalReason::CastleThroughCheck);
+        }
+
+        None
+    }
+
+    /// Returns the castle that the side to move can make on the given side of the board, if it
+    /// has one that is pseudo-legal. Since castles can't be made out of, through, or into check,
+    /// a pseudo-legal castle is also legal.
+    pub(crate) fn castle_move(&self, kingside: bool) -> Option<Move> {
+        let color = self.side_to_move();
+        let king = self.kings(color).first()?;
+        let mov = if kingside {
+            Move::kingside_castle(king, Square::of(back_rank(color), File::G))
+        } else {
+            Move::queenside_castle(king, Square::of(back_rank(color), File::C))
+        };
+
+        if self.is_pseudolegal_castle(mov) {
+            Some(mov)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the square that the rook castles from and the square that it lands on, for a
+    /// castle made by the side to move.
+    fn castle_rook_squares(&self, mov: Move) -> (Square, Square) {
+        let color = self.side_to_move();
+        if mov.is_kingside_castle() {
+            (
+                self.kingside_castle_rook(color),
+                Square::of(back_rank(color), File::F),
+            )
+        } else {
+            (
+                self.queenside_castle_rook(color),
+                Square::of(back_rank(color), File::D),
+            )
+        }
+    }
+
+    /// Returns the file of the given side's rook that is furthest from its king on the given side
+    /// of the board, which is the rook that `K` and `Q` castle rights refer to in X-FEN.
+    fn outermost_rook_file(&self, color: Color, side: usize) -> Option<File> {
+        let king = self.kings(color).first()?;
+        let rank = back_rank(color);
+        if king.rank() != rank {
+            return None;
+        }
+
+        let is_rook = |&&file: &&File| {
+            let sq = Square::of(rank, file);
+            self.rooks(color).test(sq)
+        };
+        let (left, right) = FILES.split_at(king.file().as_index());
+        if side == KINGSIDE {
+            right.iter().rev().find(is_rook).copied()
+        } else {
+            left.iter().find(is_rook).copied()
+        }
+    }
+
+    /// Returns the castles that depend on a rook standing on the given square.
+    fn castle_rights_of(&self, sq: Square) -> CastleStatus {
+        let mut rights = CastleStatus::NONE;
+        for &color in &COLORS {
+            if self.kingside_castle_rook(color) == sq {
+                rights |= kingside_castle_mask(color);
+            }
+            if self.queenside_castle_rook(color) == sq {
+                rights |= queenside_castle_mask(color);
+            }
+        }
+
+        rights
     }
 
-    /// Move legality test. Returns true if this move is a legal move from the given position. If
-    /// the move is know to be psuedolegal, `is_legal_given_pseudolegal` will likely be faster.
-    pub fn is_legal(&self, mov: Move) -> bool {
-        let mut mov_vec = MoveVec::default();
-        let gen = MoveGenerator::new();
-        gen.generate_moves(self, &mut mov_vec);
-        if !mov_vec.contains(&mov) {
-            return false;
+    /// Explains why the given move is illegal in this position, or returns `None` if it is legal.
+    /// This is much slower than `is_legal` and is meant for diagnostics, such as telling a GUI why
+    /// a move was rejected.
+    pub fn explain_illegal(&self, mov: Move) -> Option<IllegalReason> {
+        if self.is_legal(mov) {
+            return None;
+        }
+
+        let piece = match self.piece_at(mov.source()) {
+            Some(piece) => piece,
+            None => return Some(IllegalReason::NoPieceAtSource),
+        };
+        if piece.color != self.side_to_move() {
+            return Some(IllegalReason::WrongColor);
+        }
+
+        if mov.is_castle() && piece.kind == PieceKind::King {
+            if let Some(reason) = self.castle_illegality(mov) {
+                return Some(reason);
+            }
+        }
+
+        if self.is_pseudo_legal(mov) {
+            return Some(IllegalReason::LeavesKingInCheck);
         }
 
-        self.is_legal_given_pseudolegal(mov)
+        // The move isn't pseudo-legal, but it might have been if nothing were in the way.
+        let occupancy = self.occupied();
+        let blocked = if mov.is_castle() || self.pieces(piece.color).test(mov.destination()) {
+            false
+        } else if piece.kind == PieceKind::Pawn {
+            let dir = match piece.color {
+                Color::White => Direction::North,
+                Color::Black => Direction::South,
+            };
+            let one = mov.source().towards(dir);
+            !mov.is_capture()
+                && mov.destination().file() == mov.source().file()
+                && occupancy.test(one)
+        } else {
+            piece.is_sliding()
+                && piece
+                    .attacks(mov.source(), Bitboard::none())
+                    .test(mov.destination())
+                && !piece
+                    .attacks(mov.source(), occupancy)
+                    .test(mov.destination())
+        };
+
+        if blocked {
+            Some(IllegalReason::BlockedPath)
+        } else {
+            Some(IllegalReason::NotPseudoLegal)
+        }
     }
 
     /// Legality test for moves that are already known to be pseudolegal. This is strictly faster
@@ -642,31 +1467,65 @@ impl Position {
         Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap()
     }
 
-    /// Constructs a new position from a FEN representation of a board position.
+    /// Plays the given number of random legal moves from the starting position, stopping early if
+    /// the game ends, and returns the resulting position. The same random number generator state
+    /// always produces the same position, which makes this useful for varying the starting points
+    /// of self-play games.
+    pub fn random_opening<R: Rng>(rng: &mut R, plies: u32) -> Position {
+        let mut pos = Position::from_start_position();
+        for _ in 0..plies {
+            match pos.legal_moves().choose(rng) {
+                Some(&mov) => pos.apply_move(mov),
+                None => break,
+            }
+        }
+
+        pos
+    }
+
+    /// Constructs a new position from a FEN representation of a board position. A fullmove clock
+    /// of 0, which is technically invalid, is accepted and treated as 1.
     pub fn from_fen<S: AsRef<str>>(fen: S) -> Result<Position, FenParseError> {
         use std::iter::Peekable;
-        use std::str::Chars;
+        use std::str::CharIndices;
+
+        struct Stream<'a> {
+            chars: Peekable<CharIndices<'a>>,
+            len: usize,
+        }
+
+        impl<'a> Stream<'a> {
+            /// The byte offset of the next character in the stream.
+            fn offset(&mut self) -> usize {
+                self.chars.peek().map(|&(idx, _)| idx).unwrap_or(self.len)
+            }
 
-        type Stream<'a> = Peekable<Chars<'a>>;
+            /// Produces an error of the given kind at the current position in the stream.
+            fn error<T>(&mut self, kind: FenParseErrorKind) -> Result<T, FenParseError> {
+                Err(FenParseError {
+                    kind,
+                    offset: self.offset(),
+                })
+            }
+        }
 
         fn eat<'a>(iter: &mut Stream<'a>, expected: char) -> Result<(), FenParseError> {
-            match iter.next() {
-                Some(c) if c == expected => Ok(()),
-                Some(c) => Err(FenParseError::UnexpectedChar(c)),
-                None => Err(FenParseError::UnexpectedEnd),
+            match peek(iter)? {
+                c if c == expected => advance(iter),
+                c => iter.error(FenParseErrorKind::UnexpectedChar(c)),
             }
         }
 
         fn advance<'a>(iter: &mut Stream<'a>) -> Result<(), FenParseError> {
-            let _ = iter.next();
+            let _ = iter.chars.next();
             Ok(())
         }
 
         fn peek<'a>(iter: &mut Stream<'a>) -> Result<char, FenParseError> {
-            if let Some(c) = iter.peek() {
-                Ok(*c)
+            if let Some(&(_, c)) = iter.chars.peek() {
+                Ok(c)
             } else {
-                Err(FenParseError::UnexpectedEnd)
+                iter.error(FenParseErrorKind::UnexpectedEnd)
             }
         }
 
@@ -674,34 +1533,74 @@ impl Position {
             let side = match peek(iter)? {
                 'w' => Color::White,
                 'b' => Color::Black,
-                _ => return Err(FenParseError::InvalidSideToMove),
+                _ => return iter.error(FenParseErrorKind::InvalidSideToMove),
             };
 
             advance(iter)?;
             Ok(side)
         }
 
-        fn eat_castle_status<'a>(iter: &mut Stream<'a>) -> Result<CastleStatus, FenParseError> {
+        /// Parses the castle rights of a position whose pieces have already been placed. Besides
+        /// `KQkq`, this accepts the Shredder-FEN style of naming the file of the castling rook,
+        /// e.g. `HAha`, which is needed for Chess960 positions. As in X-FEN, the two styles can be
+        /// mixed, and `K` and `Q` refer to the outermost rook on that side of the king.
+        fn eat_castle_status<'a>(
+            iter: &mut Stream<'a>,
+            pos: &mut Position,
+        ) -> Result<(), FenParseError> {
             if peek(iter)? == '-' {
                 advance(iter)?;
-                return Ok(CastleStatus::NONE);
+                return Ok(());
             }
 
-            let mut status = CastleStatus::NONE;
+            let mut files_only = true;
             for _ in 0..4 {
-                match peek(iter)? {
-                    'K' => status |= CastleStatus::WHITE_KINGSIDE,
-                    'k' => status |= CastleStatus::BLACK_KINGSIDE,
-                    'Q' => status |= CastleStatus::WHITE_QUEENSIDE,
-                    'q' => status |= CastleStatus::BLACK_QUEENSIDE,
+                let c = peek(iter)?;
+                let color = if c.is_ascii_uppercase() {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+
+                let (side, file) = match c.to_ascii_lowercase() {
+                    'k' => {
+                        files_only = false;
+                        let file = pos.outermost_rook_file(color, KINGSIDE);
+                        (KINGSIDE, file.unwrap_or(File::H))
+                    }
+                    'q' => {
+                        files_only = false;
+                        let file = pos.outermost_rook_file(color, QUEENSIDE);
+                        (QUEENSIDE, file.unwrap_or(File::A))
+                    }
                     ' ' => break,
-                    _ => return Err(FenParseError::InvalidCastle),
-                }
+                    c => {
+                        // The rook is on the kingside if it's to the right of the king.
+                        let file = File::try_from(c)
+                            .or_else(|_| iter.error(FenParseErrorKind::InvalidCastle))?;
+                        let king = match pos.kings(color).first() {
+                            Some(king) if king.rank() == back_rank(color) => king,
+                            _ => return iter.error(FenParseErrorKind::InvalidCastle),
+                        };
+
+                        if file.as_index() > king.file().as_index() {
+                            (KINGSIDE, file)
+                        } else {
+                            (QUEENSIDE, file)
+                        }
+                    }
+                };
 
+                pos.castle_status |= match side {
+                    KINGSIDE => kingside_castle_mask(color),
+                    _ => queenside_castle_mask(color),
+                };
+                pos.castle_rook_files[color.as_index()][side] = file;
                 advance(iter)?;
             }
 
-            Ok(status)
+            pos.shredder_castling = files_only;
+            Ok(())
         }
 
         fn eat_en_passant<'a>(iter: &mut Stream<'a>) -> Result<Option<Square>, FenParseError> {
@@ -718,14 +1617,15 @@ impl Position {
                     advance(iter)?;
                     Ok(Some(Square::of(rank, file)))
                 } else {
-                    Err(FenParseError::InvalidEnPassant)
+                    iter.error(FenParseErrorKind::InvalidEnPassant)
                 }
             } else {
-                Err(FenParseError::InvalidEnPassant)
+                iter.error(FenParseErrorKind::InvalidEnPassant)
             }
         }
 
         fn eat_halfmove<'a>(iter: &mut Stream<'a>) -> Result<u32, FenParseError> {
+            let start = iter.offset();
             let mut buf = String::new();
             loop {
                 let c = peek(iter)?;
@@ -738,38 +1638,45 @@ impl Position {
             }
 
             if buf.is_empty() {
-                return Err(FenParseError::EmptyHalfmove);
+                return iter.error(FenParseErrorKind::EmptyHalfmove);
             }
 
-            buf.parse::<u32>()
-                .map_err(|_| FenParseError::InvalidHalfmove)
+            buf.parse::<u32>().map_err(|_| FenParseError {
+                kind: FenParseErrorKind::InvalidHalfmove,
+                offset: start,
+            })
         }
 
         fn eat_fullmove<'a>(iter: &mut Stream<'a>) -> Result<u32, FenParseError> {
+            // The fullmove clock is the last thing in the string, so running out of characters
+            // here is fine.
+            let start = iter.offset();
             let mut buf = String::new();
-            for ch in iter {
+            while let Some(&(_, ch)) = iter.chars.peek() {
                 if !ch.is_digit(10) {
-                    if buf.is_empty() {
-                        return Err(FenParseError::EmptyFullmove);
-                    }
-
                     break;
                 }
 
                 buf.push(ch);
+                advance(iter)?;
             }
 
             if buf.is_empty() {
-                return Err(FenParseError::EmptyFullmove);
+                return iter.error(FenParseErrorKind::EmptyFullmove);
             }
 
-            buf.parse::<u32>()
-                .map_err(|_| FenParseError::InvalidFullmove)
+            buf.parse::<u32>().map_err(|_| FenParseError {
+                kind: FenParseErrorKind::InvalidFullmove,
+                offset: start,
+            })
         }
 
         let mut pos = Position::new();
         let str_ref = fen.as_ref();
-        let iter = &mut str_ref.chars().peekable();
+        let iter = &mut Stream {
+            chars: str_ref.char_indices().peekable(),
+            len: str_ref.len(),
+        };
         for &rank in RANKS.iter().rev() {
             let mut file = File::A as usize;
             while file <= File::H as usize {
@@ -777,13 +1684,13 @@ impl Position {
                 // digits 1 through 8 indicate empty squares.
                 if c.is_digit(10) {
                     if c < '1' || c > '8' {
-                        return Err(FenParseError::InvalidDigit);
+                        return iter.error(FenParseErrorKind::InvalidDigit);
                     }
 
                     let value = c as usize - 48;
                     file += value;
                     if file > 8 {
-                        return Err(FenParseError::FileDoesNotSumToEight);
+                        return iter.error(FenParseErrorKind::FileDoesNotSumToEight);
                     }
 
                     advance(iter)?;
@@ -794,7 +1701,7 @@ impl Position {
                 let piece = if let Ok(piece) = Piece::try_from(c) {
                     piece
                 } else {
-                    return Err(FenParseError::UnknownPiece);
+                    return iter.error(FenParseErrorKind::UnknownPiece);
                 };
 
                 let square = Square::of(rank, File::from_index(file));
@@ -811,13 +1718,15 @@ impl Position {
         eat(iter, ' ')?;
         pos.side_to_move = eat_side_to_move(iter)?;
         eat(iter, ' ')?;
-        pos.castle_status = eat_castle_status(iter)?;
+        eat_castle_status(iter, &mut pos)?;
         eat(iter, ' ')?;
         pos.en_passant_square = eat_en_passant(iter)?;
         eat(iter, ' ')?;
         pos.halfmove_clock = eat_halfmove(iter)?;
         eat(iter, ' ')?;
-        pos.fullmove_clock = eat_fullmove(iter)?;
+        // The fullmove clock starts at 1, but plenty of FENs in the wild (and in this crate's tests)
+        // give it as 0. Tolerate them, but normalize the clock so that move numbers come out right.
+        pos.fullmove_clock = eat_fullmove(iter)?.max(1);
         pos.zobrist_hash = zobrist::hash(&pos);
         Ok(pos)
     }
@@ -961,12 +1870,35 @@ impl Position {
                 }
             }
 
-            // 2.3. Is there a piece on the target square?
+            // 2.3. Is the king "capturing" its own castling rook? This is how Chess960 castles are
+            // written, since the king doesn't always move two squares.
+            if dest_piece.map(|piece| piece.color) == Some(self.side_to_move) {
+                let back_rank = back_rank(self.side_to_move);
+                if self.can_castle_kingside(self.side_to_move)
+                    && dest == self.kingside_castle_rook(self.side_to_move)
+                {
+                    return Some(Move::kingside_castle(
+                        source,
+                        Square::of(back_rank, File::G),
+                    ));
+                }
+
+                if self.can_castle_queenside(self.side_to_move)
+                    && dest == self.queenside_castle_rook(self.side_to_move)
+                {
+                    return Some(Move::queenside_castle(
+                        source,
+                        Square::of(back_rank, File::C),
+                    ));
+                }
+            }
+
+            // 2.4. Is there a piece on the target square?
             if dest_piece.is_some() {
                 return Some(Move::capture(source, dest));
             }
 
-            // 2.4. Else, it's quiet.
+            // 2.5. Else, it's quiet.
             return Some(Move::quiet(source, dest));
         }
 
@@ -988,26 +1920,23 @@ impl Position {
         // Some obvious outs, though: castles are treated specially. FIDE says that this must be the digit zero,
         // but PGN uses the letter O. Go figure.
         let to_move = self.side_to_move();
-        match san_str {
+        match san_str.trim_end_matches(['+', '#']) {
             "O-O" | "0-0" => {
                 return Some(Move::kingside_castle(
-                    king_start(to_move),
-                    kingside_rook(to_move).towards(Direction::West),
+                    self.kings(to_move).first()?,
+                    Square::of(back_rank(to_move), File::G),
                 ))
             }
             "O-O-O" | "0-0-0" => {
                 return Some(Move::queenside_castle(
-                    king_start(to_move),
-                    queenside_rook(to_move)
-                        .towards(Direction::East)
-                        .towards(Direction::East),
+                    self.kings(to_move).first()?,
+                    Square::of(back_rank(to_move), File::C),
                 ))
             }
             _ => {}
         }
 
-        let re = Regex::new(r"^(?P<piece>[BNRQK]?)(?P<file>[a-h]?)(?P<rank>[1-8]?)(?P<capture>x?)(?P<destination_file>[a-h])(?P<destination_rank>[1-8])[\+#]?$").unwrap();
-        let captures = re.captures(san_str)?;
+        let captures = SAN_REGEX.captures(san_str)?;
         let mut piece_mask = Bitboard::all();
         let moving_piece = match &captures["piece"] {
             "B" => PieceKind::Bishop,
@@ -1066,18 +1995,106 @@ impl Position {
             _ => return None,
         };
 
+        // A pawn that reaches the last rank must say what it promotes to.
+        let promotion = match captures.name("promotion").map(|m| m.as_str()) {
+            Some("N") => Some(PieceKind::Knight),
+            Some("B") => Some(PieceKind::Bishop),
+            Some("R") => Some(PieceKind::Rook),
+            Some("Q") => Some(PieceKind::Queen),
+            _ => None,
+        };
+
         let dest_square = Square::of(dest_rank, dest_file);
-        let gen = MoveGenerator::new();
-        let mut moves = MoveVec::default();
-        gen.generate_moves(self, &mut moves);
-        moves.retain(|&mut m| self.is_legal_given_pseudolegal(m));
+        self.legal_moves().into_iter().find(|&mov| {
+            let promotes_to = if mov.is_promotion() {
+                Some(mov.promotion_piece())
+            } else {
+                None
+            };
 
-        for mov in moves {
-            if piece_mask.test(mov.source()) && mov.destination() == dest_square {
-                return Some(mov);
+            piece_mask.test(mov.source())
+                && mov.destination() == dest_square
+                && promotes_to == promotion
+        })
+    }
+
+    /// Produces the SAN representation of a legal move from this position.
+    pub fn move_to_san(&self, mov: Move) -> String {
+        let mut san = String::new();
+        if mov.is_kingside_castle() {
+            san.push_str("O-O");
+        } else if mov.is_queenside_castle() {
+            san.push_str("O-O-O");
+        } else {
+            let source = mov.source();
+            let dest = mov.destination();
+            let moving_piece = self
+                .piece_at(source)
+                .expect("no piece at source of move being converted to SAN");
+            let is_capture = self.is_capture(mov);
+            if moving_piece.kind == PieceKind::Pawn {
+                // Pawn captures are identified by the file that the pawn came from.
+                if is_capture {
+                    write!(&mut san, "{}", source.file()).unwrap();
+                }
+            } else {
+                write!(&mut san, "{}", moving_piece.kind.to_string().to_uppercase()).unwrap();
+
+                // If any other piece of the same kind can move to the destination square, we need to say which
+                // one of them is moving. The file is preferred, then the rank, and then both if neither is enough.
+                let others: Vec<Square> = self
+                    .legal_moves()
+                    .iter()
+                    .filter(|m| m.destination() == dest && m.source() != source)
+                    .filter(|m| self.piece_at(m.source()) == Some(moving_piece))
+                    .map(|m| m.source())
+                    .collect();
+                if !others.is_empty() {
+                    if others.iter().all(|sq| sq.file() != source.file()) {
+                        write!(&mut san, "{}", source.file()).unwrap();
+                    } else if others.iter().all(|sq| sq.rank() != source.rank()) {
+                        write!(&mut san, "{}", source.rank()).unwrap();
+                    } else {
+                        write!(&mut san, "{}", source).unwrap();
+                    }
+                }
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+
+            write!(&mut san, "{}", dest).unwrap();
+            if mov.is_promotion() {
+                let promoted = mov.promotion_piece().to_string().to_uppercase();
+                write!(&mut san, "={}", promoted).unwrap();
+            }
+        }
+
+        let mut child = self.clone();
+        child.apply_move(mov);
+        if child.is_check(child.side_to_move()) {
+            if child.legal_moves().is_empty() {
+                san.push('#');
+            } else {
+                san.push('+');
             }
         }
-        return None;
+
+        san
+    }
+
+    /// Returns a canonical FEN for this position, so that positions that differ only cosmetically
+    /// produce the same string. Castle rights are always written in `KQkq` order and the en passant
+    /// square is only written if an en passant capture is actually legal.
+    pub fn normalized_fen(&self) -> String {
+        let mut pos = self.clone();
+        if !self.legal_moves().iter().any(|mov| mov.is_en_passant()) {
+            pos.en_passant_square = None;
+        }
+
+        pos.shredder_castling = false;
+        pos.as_fen()
     }
 
     pub fn as_fen(&self) -> String {
@@ -1112,17 +2129,33 @@ impl Position {
             Color::Black => buf.push('b'),
         }
         buf.push(' ');
-        if self.can_castle_kingside(Color::White) {
-            buf.push('K');
-        }
-        if self.can_castle_queenside(Color::White) {
-            buf.push('Q');
-        }
-        if self.can_castle_kingside(Color::Black) {
-            buf.push('k');
+        // Castles name the rook's file if the FEN that this position came from did, or, as in
+        // X-FEN, if the rook isn't the outermost one on its side of the king.
+        for &color in &COLORS {
+            let castles = [
+                (self.can_castle_kingside(color), KINGSIDE, File::H, 'k'),
+                (self.can_castle_queenside(color), QUEENSIDE, File::A, 'q'),
+            ];
+            for &(can_castle, side, corner, symbol) in &castles {
+                if !can_castle {
+                    continue;
+                }
+
+                let file = self.castle_rook_files[color.as_index()][side];
+                let outermost = self.outermost_rook_file(color, side).unwrap_or(corner);
+                let symbol = if !self.shredder_castling && file == outermost {
+                    symbol
+                } else {
+                    (b'a' + file.as_index() as u8) as char
+                };
+                match color {
+                    Color::White => buf.push(symbol.to_ascii_uppercase()),
+                    Color::Black => buf.push(symbol),
+                }
+            }
         }
-        if self.can_castle_queenside(Color::Black) {
-            buf.push('q');
+        if self.castle_status.is_empty() {
+            buf.push('-');
         }
         buf.push(' ');
         if let Some(ep_square) = self.en_passant_square() {
@@ -1181,24 +2214,93 @@ impl Default for Position {
     }
 }
 
+/// Positions are serialized as their FEN, which is compact and readable in JSON fixtures and
+/// debugging dumps. The FEN leaves out the moves that led to the position, so a deserialized
+/// position has no move history.
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.as_fen())
+    }
+}
+
+impl<'de> Deserialize<'de> for Position {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Position, D::Error> {
+        let fen = String::deserialize(deserializer)?;
+        Position::from_fen(&fen).map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for FenParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid FEN at offset {}: {}", self.offset, self.kind)
+    }
+}
+
+impl Error for FenParseError {}
+
+impl fmt::Display for FenParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FenParseErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            FenParseErrorKind::UnexpectedEnd => write!(f, "unexpected end of string"),
+            FenParseErrorKind::InvalidDigit => write!(f, "invalid digit"),
+            FenParseErrorKind::FileDoesNotSumToEight => write!(f, "rank does not have 8 squares"),
+            FenParseErrorKind::UnknownPiece => write!(f, "unknown piece"),
+            FenParseErrorKind::InvalidSideToMove => write!(f, "invalid side to move"),
+            FenParseErrorKind::InvalidCastle => write!(f, "invalid castle rights"),
+            FenParseErrorKind::InvalidEnPassant => write!(f, "invalid en passant square"),
+            FenParseErrorKind::EmptyHalfmove => write!(f, "missing halfmove clock"),
+            FenParseErrorKind::InvalidHalfmove => write!(f, "invalid halfmove clock"),
+            FenParseErrorKind::EmptyFullmove => write!(f, "missing fullmove clock"),
+            FenParseErrorKind::InvalidFullmove => write!(f, "invalid fullmove clock"),
+        }
+    }
+}
+
 //
 // Helper functions
 //
 
-fn king_start(color: Color) -> Square {
-    match color {
-        Color::White => Square::E1,
-        Color::Black => Square::E8,
-    }
+/// Returns the Polyglot key of the given position, which is the key that Polyglot opening books
+/// use to look up positions. This is a different hash from `Position::zobrist_hash`, which is
+/// the one that the transposition table uses.
+pub fn polyglot_hash(pos: &Position) -> u64 {
+    zobrist::polyglot_hash(pos)
 }
 
-fn kingside_rook(color: Color) -> Square {
+lazy_static! {
+    /// Matches a SAN move other than a castle, e.g. `Nbd7`, `exd5`, or `e8=Q+`.
+    static ref SAN_REGEX: Regex = Regex::new(
+        r"^(?P<piece>[BNRQK]?)(?P<file>[a-h]?)(?P<rank>[1-8]?)(?P<capture>x?)(?P<destination_file>[a-h])(?P<destination_rank>[1-8])(?:=?(?P<promotion>[NBRQ]))?[\+#]?$"
+    )
+    .unwrap();
+}
+
+/// Extends every set square in a bitboard to cover its entire file.
+fn file_fill(board: Bitboard) -> Bitboard {
+    board.fill(Direction::North) | board.fill(Direction::South)
+}
+
+/// Indices into `Position::castle_rook_files` for each side of the board.
+const KINGSIDE: usize = 0;
+const QUEENSIDE: usize = 1;
+
+/// The rank that the given side's pieces start on, and that it castles along.
+fn back_rank(color: Color) -> Rank {
     match color {
-        Color::White => Square::H1,
-        Color::Black => Square::H8,
+        Color::White => Rank::One,
+        Color::Black => Rank::Eight,
     }
 }
 
+/// Returns the squares from one square to another on the same rank, including both of them.
+fn rank_span(a: Square, b: Square) -> Bitboard {
+    let mut span = attacks::between(a, b);
+    span.set(a);
+    span.set(b);
+    span
+}
+
 fn kingside_castle_mask(color: Color) -> CastleStatus {
     match color {
         Color::White => CastleStatus::WHITE_KINGSIDE,
@@ -1206,13 +2308,6 @@ fn kingside_castle_mask(color: Color) -> CastleStatus {
     }
 }
 
-fn queenside_rook(color: Color) -> Square {
-    match color {
-        Color::White => Square::A1,
-        Color::Black => Square::A8,
-    }
-}
-
 fn queenside_castle_mask(color: Color) -> CastleStatus {
     match color {
         Color::White => CastleStatus::WHITE_QUEENSIDE,
@@ -1229,11 +2324,14 @@ fn castle_mask(color: Color) -> CastleStatus {
 
 #[cfg(test)]
 mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
     use std::mem;
 
+    use crate::bitboard::Bitboard;
     use crate::moves::Move;
     use crate::position::Position;
-    use crate::types::{Color, Square};
+    use crate::types::{Color, GameResult, Piece, PieceKind, Square};
 
     #[test]
     fn size_is_160() {
@@ -1254,22 +2352,533 @@ mod tests {
         assert!(pos.is_absolutely_pinned(Color::Black, Square::D3));
     }
 
-    #[test]
-    fn absolute_pin_smoke_neg() {
-        let pos = Position::from_fen("8/8/3q4/8/8/3B4/2K5/8 w - - 0 1").unwrap();
-        assert!(!pos.is_absolutely_pinned(Color::Black, Square::D3));
-    }
+    #[test]
+    fn absolute_pin_smoke_neg() {
+        let pos = Position::from_fen("8/8/3q4/8/8/3B4/2K5/8 w - - 0 1").unwrap();
+        assert!(!pos.is_absolutely_pinned(Color::Black, Square::D3));
+    }
+
+    #[test]
+    fn absolute_pin_smoke_neg_2() {
+        let pos = Position::from_fen("8/8/3q4/8/1K6/3B4/8/8 w - - 0 1").unwrap();
+        assert!(!pos.is_absolutely_pinned(Color::Black, Square::D3));
+    }
+
+    #[test]
+    fn absolute_pin_legality() {
+        let pos = Position::from_fen("8/8/8/q7/8/2B5/3K4/8 w - - 0 1").unwrap();
+        assert!(pos.is_legal(Move::quiet(Square::C3, Square::B4)));
+    }
+
+    #[test]
+    fn random_opening() {
+        let first = Position::random_opening(&mut StdRng::seed_from_u64(42), 12);
+        let second = Position::random_opening(&mut StdRng::seed_from_u64(42), 12);
+        assert_eq!(first.as_fen(), second.as_fen());
+        assert_eq!(12, first.move_history().len());
+        assert_eq!(Color::White, first.side_to_move());
+        assert!(!first.is_check(Color::Black));
+
+        let round_tripped = Position::from_fen(first.as_fen()).unwrap();
+        assert_eq!(first.as_fen(), round_tripped.as_fen());
+        assert_eq!(first.zobrist_hash(), round_tripped.zobrist_hash());
+    }
+
+    #[test]
+    fn array_round_trip() {
+        let pos = Position::from_start_position();
+        let board = pos.to_array();
+        assert_eq!(
+            Some(Piece::new(PieceKind::Rook, Color::White)),
+            board[Square::A1 as usize]
+        );
+        assert_eq!(
+            Some(Piece::new(PieceKind::King, Color::Black)),
+            board[Square::E8 as usize]
+        );
+        assert_eq!(None, board[Square::E4 as usize]);
+        assert_eq!(32, board.iter().filter(|p| p.is_some()).count());
+
+        let round_tripped = Position::from_array(&board);
+        assert!(pos.diff(&round_tripped).is_empty());
+        assert_eq!(board[..], round_tripped.to_array()[..]);
+    }
+
+    #[test]
+    fn piece_planes_occupancy() {
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 0 1",
+        )
+        .unwrap();
+        let planes = pos.piece_planes();
+        let occupancy = planes.iter().fold(Bitboard::none(), |acc, &p| acc | p);
+        assert_eq!(pos.occupied(), occupancy);
+        assert_eq!(pos.pawns(Color::White), planes[0]);
+        assert_eq!(pos.kings(Color::Black), planes[11]);
+
+        let mut castling = Bitboard::none();
+        castling.set(Square::H1);
+        castling.set(Square::A8);
+        assert_eq!(castling, pos.castling_plane());
+        assert!(pos.en_passant_plane().empty());
+    }
+
+    #[test]
+    fn with_side_to_move_round_trip() {
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let flipped = pos.with_side_to_move(Color::Black);
+        assert_eq!(Color::Black, flipped.side_to_move());
+        assert_ne!(pos.zobrist_hash(), flipped.zobrist_hash());
+        assert_eq!(pos.halfmove_clock(), flipped.halfmove_clock());
+        assert_eq!(pos.fullmove_clock(), flipped.fullmove_clock());
+
+        let back = flipped.with_side_to_move(Color::White);
+        assert_eq!(pos.as_fen(), back.as_fen());
+        assert_eq!(pos.zobrist_hash(), back.zobrist_hash());
+    }
+
+    #[test]
+    fn with_side_to_move_clears_en_passant() {
+        let mut pos = Position::from_start_position();
+        pos.apply_move(Move::double_pawn_push(Square::E2, Square::E4));
+        assert_eq!(Some(Square::E3), pos.en_passant_square());
+
+        let flipped = pos.with_side_to_move(Color::White);
+        assert_eq!(None, flipped.en_passant_square());
+        assert_eq!(
+            pos.as_fen().replace(" e3 ", " - ").replace(" b ", " w "),
+            flipped.as_fen()
+        );
+
+        // Flipping back yields the original position, minus the en passant square.
+        let back = flipped.with_side_to_move(Color::Black);
+        assert_eq!(None, back.en_passant_square());
+        // No black pawn can capture on e3, so the en passant square was never part of the hash.
+        assert_eq!(pos.zobrist_hash(), back.zobrist_hash());
+        assert_eq!(pos.as_fen().replace(" e3 ", " - "), back.as_fen());
+    }
+
+    #[test]
+    fn en_passant_horizontal_pin() {
+        // bxc6 removes both pawns from the fifth rank, exposing the king on a5 to the rook on h5.
+        let pos = Position::from_fen("8/8/3p4/KPp4r/1R3p1k/8/4P1P1/8 w - c6 0 2").unwrap();
+        let ep = Move::en_passant(Square::B5, Square::C6);
+        assert!(pos.en_passant_discovers_check(ep));
+        assert!(!pos.is_legal(ep));
+
+        // The same capture is fine once the king isn't on that rank.
+        let pos = Position::from_fen("8/8/3p4/1Pp4r/KR3p1k/8/4P1P1/8 w - c6 0 2").unwrap();
+        assert!(!pos.en_passant_discovers_check(ep));
+        assert!(pos.is_legal(ep));
+
+        // Moves that aren't en-passant never trip this check.
+        assert!(!pos.en_passant_discovers_check(Move::quiet(Square::B5, Square::B6)));
+    }
+
+    #[test]
+    fn material_for_side_to_move() {
+        assert_eq!(
+            0,
+            Position::from_start_position().material_for_side_to_move()
+        );
+
+        let white = Position::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/R3K3 w - - 0 1").unwrap();
+        assert_eq!(5, white.material_for_side_to_move());
+        let black = white.with_side_to_move(Color::Black);
+        assert_eq!(-5, black.material_for_side_to_move());
+    }
+
+    #[test]
+    fn attack_defense_contested_square() {
+        // e5 is attacked by the knight on f3 and the pawn on d4 and defended by the knight on c6,
+        // the pawn on d6 and the queen on e7.
+        let pos =
+            Position::from_fen("r1b1kbnr/ppp1qppp/2np4/8/3PP3/5N2/PPP2PPP/RNBQKB1R w KQkq - 1 5")
+                .unwrap();
+        let (white, black) = pos.attack_defense(Square::E5);
+        assert_eq!(
+            vec![Square::F3, Square::D4],
+            white.into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Square::C6, Square::D6, Square::E7],
+            black.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn least_valuable_attacker() {
+        // Both the pawn on c4 and the queen on d1 attack d5.
+        let pos = Position::from_fen("4k3/8/8/3p4/2P5/8/8/3QK3 w - - 0 1").unwrap();
+        let occupancy = pos.occupied();
+        assert_eq!(
+            Some(Square::C4),
+            pos.least_valuable_attacker(Color::White, Square::D5, occupancy)
+        );
+        assert_eq!(
+            None,
+            pos.least_valuable_attacker(Color::Black, Square::D5, occupancy)
+        );
+
+        // Once the pawn is gone, the queen is next in line.
+        let mut occupancy = occupancy;
+        occupancy.unset(Square::C4);
+        assert_eq!(
+            Some(Square::D1),
+            pos.least_valuable_attacker(Color::White, Square::D5, occupancy)
+        );
+    }
+
+    #[test]
+    fn least_valuable_attacker_x_ray() {
+        // The rook on d1 is behind the queen on d2 and only attacks d5 once the queen has moved.
+        let pos = Position::from_fen("4k3/8/8/3p4/8/8/3Q4/3RK3 w - - 0 1").unwrap();
+        let mut occupancy = pos.occupied();
+        assert_eq!(
+            Some(Square::D2),
+            pos.least_valuable_attacker(Color::White, Square::D5, occupancy)
+        );
+
+        occupancy.unset(Square::D2);
+        assert_eq!(
+            Some(Square::D1),
+            pos.least_valuable_attacker(Color::White, Square::D5, occupancy)
+        );
+    }
+
+    #[test]
+    fn gives_check_agrees_with_making_moves() {
+        for fen in &[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/8/3p4/KPp4r/1R3p1k/8/4P1P1/8 w - c6 0 2",
+            // Discovered check by moving the knight off the bishop's diagonal, and castling check.
+            "5k2/8/8/2N5/8/B7/8/4K2R w K - 0 1",
+        ] {
+            let pos = Position::from_fen(fen).unwrap();
+            for mov in pos.legal_moves() {
+                let mut child = pos.clone();
+                child.apply_move(mov);
+                assert_eq!(
+                    child.is_check(child.side_to_move()),
+                    pos.gives_check(mov),
+                    "{} in {}",
+                    mov,
+                    fen
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gives_checkmate() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert!(pos.gives_checkmate(Move::quiet(Square::A1, Square::A8)));
+        assert!(!pos.gives_checkmate(Move::quiet(Square::A1, Square::A7)));
+    }
+
+    #[test]
+    fn checkmate_and_stalemate() {
+        let start = Position::from_start_position();
+        assert!(!start.is_checkmate());
+        assert!(!start.is_stalemate());
+
+        let back_rank = Position::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1").unwrap();
+        assert!(back_rank.is_checkmate());
+        assert!(!back_rank.is_stalemate());
+
+        let smothered = Position::from_fen("6rk/5Npp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert!(smothered.is_checkmate());
+        assert!(!smothered.is_stalemate());
+
+        let stalemate = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!stalemate.is_checkmate());
+        assert!(stalemate.is_stalemate());
+
+        // In check, but the king can step out of it.
+        let check = Position::from_fen("R5k1/5pp1/7p/8/8/8/8/6K1 b - - 1 1").unwrap();
+        assert!(!check.is_checkmate());
+        assert!(!check.is_stalemate());
+    }
+
+    #[test]
+    fn outcomes() {
+        assert_eq!(None, Position::from_start_position().outcome());
+
+        // Fool's mate.
+        let mate =
+            Position::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")
+                .unwrap();
+        assert_eq!(Some(GameResult::Win(Color::Black)), mate.outcome());
+
+        let stalemate = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(Some(GameResult::Draw), stalemate.outcome());
+
+        let fifty_moves = Position::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 100 80").unwrap();
+        assert_eq!(Some(GameResult::Draw), fifty_moves.outcome());
+        let forty_nine_moves = Position::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 99 80").unwrap();
+        assert_eq!(None, forty_nine_moves.outcome());
+    }
+
+    #[test]
+    fn open_files() {
+        use crate::bitboard::{BB_FILE_D, BB_FILE_E};
+
+        let pos =
+            Position::from_fen("rnbqkbnr/pppp1ppp/8/8/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(BB_FILE_E, pos.open_files());
+        assert_eq!(BB_FILE_E, pos.half_open_files(Color::White));
+        assert_eq!(BB_FILE_E, pos.half_open_files(Color::Black));
+
+        // White has no d- or e-pawn, while Black has no e-pawn.
+        let pos =
+            Position::from_fen("rnbqkbnr/ppp2ppp/8/3p4/8/8/PPP2PPP/RNBQKBNR w KQkq - 0 4").unwrap();
+        assert_eq!(BB_FILE_E, pos.open_files());
+        assert_eq!(BB_FILE_D | BB_FILE_E, pos.half_open_files(Color::White));
+        assert_eq!(BB_FILE_E, pos.half_open_files(Color::Black));
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let insufficient = |fen| Position::from_fen(fen).unwrap().is_insufficient_material();
+        assert!(insufficient("8/8/4k3/8/8/3K4/8/8 w - - 0 1"));
+        assert!(insufficient("8/8/4k3/8/8/3KB3/8/8 w - - 0 1"));
+        assert!(insufficient("8/8/4k3/8/8/3KN3/8/8 b - - 0 1"));
+        assert!(insufficient("8/8/4kn2/8/8/3K4/8/8 w - - 0 1"));
+
+        // The bishops on c1 and f4 are both on dark squares, as are the two on b8 and h2.
+        assert!(insufficient("8/8/4k3/8/5b2/3K4/8/2B5 w - - 0 1"));
+        assert!(insufficient("1b6/8/4k3/8/5b2/3K4/7B/2B5 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/4b3/3K4/8/2B5 w - - 0 1"));
+
+        assert!(!insufficient("8/8/4k3/8/8/3KP3/8/8 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3KNN2/8/8 w - - 0 1"));
+        assert!(!insufficient("8/8/4kn2/8/8/3KB3/8/8 w - - 0 1"));
+        assert!(!insufficient("8/8/4k3/8/8/3KR3/8/8 w - - 0 1"));
+        assert!(!Position::from_start_position().is_insufficient_material());
+    }
+
+    #[test]
+    fn capture_queries() {
+        let pos = Position::from_fen("4k3/8/8/3pP3/8/8/8/R1n1K3 w - d6 0 1").unwrap();
+        let rook_move = Move::quiet(Square::A1, Square::A8);
+        assert!(!pos.is_capture(rook_move));
+        assert_eq!(None, pos.captured_piece(rook_move));
+
+        // Neither flavor of the capture flag matters, only the board.
+        let black_knight = Piece::new(PieceKind::Knight, Color::Black);
+        for &mov in &[
+            Move::quiet(Square::A1, Square::C1),
+            Move::capture(Square::A1, Square::C1),
+        ] {
+            assert!(pos.is_capture(mov));
+            assert_eq!(Some(black_knight), pos.captured_piece(mov));
+        }
+
+        let en_passant = Move::en_passant(Square::E5, Square::D6);
+        assert!(pos.is_capture(en_passant));
+        assert_eq!(
+            Some(Piece::new(PieceKind::Pawn, Color::Black)),
+            pos.captured_piece(en_passant)
+        );
+
+        let push = Move::quiet(Square::E5, Square::E6);
+        assert!(!pos.is_capture(push));
+        assert_eq!(None, pos.captured_piece(push));
+
+        // Our own pieces can't be captured.
+        assert!(!pos.is_capture(Move::quiet(Square::A1, Square::E1)));
+    }
+
+    mod legality {
+        use crate::move_generator::{MoveGenerator, MoveVec};
+        use crate::moves::Move;
+        use crate::position::{IllegalReason, Position};
+        use crate::types::{PieceKind, Square, SQUARES};
+
+        /// Every move that can be encoded between the two squares.
+        fn all_encodings(moves: &mut Vec<Move>) {
+            for &source in SQUARES.iter() {
+                for &dest in SQUARES.iter() {
+                    moves.push(Move::quiet(source, dest));
+                    moves.push(Move::capture(source, dest));
+                    moves.push(Move::en_passant(source, dest));
+                    moves.push(Move::double_pawn_push(source, dest));
+                    moves.push(Move::kingside_castle(source, dest));
+                    moves.push(Move::queenside_castle(source, dest));
+                    for &kind in &[
+                        PieceKind::Knight,
+                        PieceKind::Bishop,
+                        PieceKind::Rook,
+                        PieceKind::Queen,
+                    ] {
+                        moves.push(Move::promotion(source, dest, kind));
+                        moves.push(Move::promotion_capture(source, dest, kind));
+                    }
+                }
+            }
+        }
+
+        fn assert_agrees_with_generator(fen: &'static str) {
+            let pos = Position::from_fen(fen).unwrap();
+            let mut generated = MoveVec::default();
+            MoveGenerator::new().generate_moves(&pos, &mut generated);
+
+            let mut candidates = vec![Move::null()];
+            all_encodings(&mut candidates);
+            for mov in candidates {
+                assert_eq!(
+                    generated.contains(&mov),
+                    pos.is_pseudo_legal(mov),
+                    "pseudo-legality of {:?} disagrees with the move generator in {}",
+                    mov,
+                    fen
+                );
+                let expected = generated.contains(&mov) && pos.is_legal_given_pseudolegal(mov);
+                assert_eq!(
+                    expected,
+                    pos.is_legal(mov),
+                    "legality of {:?} disagrees with the move generator in {}",
+                    mov,
+                    fen
+                );
+            }
+        }
+
+        #[test]
+        fn uci_moves_legal() {
+            let mut pos = Position::from_start_position();
+            for &uci in &["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"] {
+                let mov = pos.move_from_uci(uci).unwrap();
+                assert!(pos.is_legal(mov), "{} should be legal", uci);
+                pos.apply_move(mov);
+            }
+        }
+
+        #[test]
+        fn uci_moves_illegal() {
+            let pos = Position::from_fen("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1").unwrap();
+            // Castling out of check, staying in check, moving into check, and moving a piece that
+            // isn't ours.
+            for &uci in &["e1g1", "e1c1", "a1a2", "e1d2", "e2e1", "e8e7", "0000"] {
+                let mov = pos.move_from_uci(uci).unwrap();
+                assert!(!pos.is_legal(mov), "{} should be illegal", uci);
+            }
+
+            assert!(pos.is_legal(pos.move_from_uci("e1e2").unwrap()));
+            assert!(pos.is_legal(pos.move_from_uci("e1f1").unwrap()));
+        }
+
+        #[test]
+        fn explain_illegal() {
+            // The pawn on d2 is pinned by the bishop on b4.
+            let pos = Position::from_fen("r3k2r/8/8/8/1b6/8/3P4/RN2K2R w KQkq - 0 1").unwrap();
+            let explain = |uci: &str| pos.explain_illegal(pos.move_from_uci(uci).unwrap());
+            assert_eq!(None, explain("h1h2"));
+            assert_eq!(
+                Some(IllegalReason::NoPieceAtSource),
+                pos.explain_illegal(Move::quiet(Square::E4, Square::E5))
+            );
+            assert_eq!(Some(IllegalReason::WrongColor), explain("e8e7"));
+            assert_eq!(Some(IllegalReason::LeavesKingInCheck), explain("d2d3"));
+            assert_eq!(Some(IllegalReason::BlockedPath), explain("e1c1"));
+            assert_eq!(Some(IllegalReason::BlockedPath), explain("a1c1"));
+            assert_eq!(Some(IllegalReason::NotPseudoLegal), explain("b1b3"));
+            assert_eq!(Some(IllegalReason::NotPseudoLegal), explain("d2h6"));
+
+            // The rook on f8 covers f1, which the king would pass through.
+            let pos = Position::from_fen("4kr2/8/8/8/8/8/3p4/4K2R w K - 0 1").unwrap();
+            assert_eq!(
+                Some(IllegalReason::CastleThroughCheck),
+                pos.explain_illegal(Move::kingside_castle(Square::E1, Square::G1))
+            );
+
+            let pos = Position::from_fen("4k3/8/8/8/8/4p3/4P3/4K3 w - - 0 1").unwrap();
+            assert_eq!(
+                Some(IllegalReason::BlockedPath),
+                pos.explain_illegal(Move::quiet(Square::E2, Square::E3))
+            );
+        }
+
+        #[test]
+        fn malformed_moves() {
+            let pos = Position::from_start_position();
+            let malformed = [
+                // Right squares, wrong flags.
+                Move::capture(Square::E2, Square::E4),
+                Move::quiet(Square::E2, Square::E4),
+                Move::double_pawn_push(Square::E2, Square::E3),
+                Move::en_passant(Square::E2, Square::D3),
+                Move::promotion(Square::E2, Square::E3, PieceKind::Queen),
+                Move::double_pawn_push(Square::G1, Square::G3),
+                Move::kingside_castle(Square::G1, Square::F3),
+                // Nothing to move.
+                Move::quiet(Square::E4, Square::E5),
+                // Not a legal move for the piece.
+                Move::quiet(Square::G1, Square::G3),
+                Move::quiet(Square::F1, Square::C4),
+                Move::capture(Square::D1, Square::D7),
+                // Landing on our own piece.
+                Move::quiet(Square::G1, Square::E2),
+            ];
+
+            for &mov in malformed.iter() {
+                assert!(!pos.is_legal(mov), "{} should be illegal", mov);
+            }
+        }
+
+        #[test]
+        fn pseudo_legal_rejects_teleports() {
+            let pos = Position::from_start_position();
+            assert!(pos.is_pseudo_legal(Move::double_pawn_push(Square::E2, Square::E4)));
+            assert!(!pos.is_pseudo_legal(Move::quiet(Square::A2, Square::H6)));
+            assert!(!pos.is_pseudo_legal(Move::capture(Square::E2, Square::E7)));
+            assert!(!pos.is_pseudo_legal(Move::promotion(
+                Square::E2,
+                Square::E8,
+                PieceKind::Queen
+            )));
+        }
+
+        #[test]
+        fn start_position() {
+            assert_agrees_with_generator(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            );
+        }
+
+        #[test]
+        fn kiwipete() {
+            assert_agrees_with_generator(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            );
+            assert_agrees_with_generator(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            );
+        }
 
-    #[test]
-    fn absolute_pin_smoke_neg_2() {
-        let pos = Position::from_fen("8/8/3q4/8/1K6/3B4/8/8 w - - 0 1").unwrap();
-        assert!(!pos.is_absolutely_pinned(Color::Black, Square::D3));
-    }
+        #[test]
+        fn en_passant() {
+            assert_agrees_with_generator("8/8/3p4/KPp4r/1R3p1k/8/4P1P1/8 w - c6 0 2");
+            assert_agrees_with_generator("8/8/8/K2pP2q/8/8/8/7k w - d6 0 2");
+        }
 
-    #[test]
-    fn absolute_pin_legality() {
-        let pos = Position::from_fen("8/8/8/q7/8/2B5/3K4/8 w - - 0 1").unwrap();
-        assert!(pos.is_legal(Move::quiet(Square::C3, Square::B4)));
+        #[test]
+        fn promotions() {
+            assert_agrees_with_generator("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1");
+            assert_agrees_with_generator("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1");
+        }
+
+        #[test]
+        fn check() {
+            assert_agrees_with_generator("r3k2r/8/8/8/8/8/8/R3K1r1 w KQkq - 0 1");
+            assert_agrees_with_generator("4k3/8/8/8/8/8/3q4/R3K2R w KQ - 0 1");
+        }
     }
 
     mod fen {
@@ -1279,7 +2888,7 @@ mod tests {
         use crate::types::TableIndex;
         use crate::types::{Color, File, Piece, PieceKind, Rank, Square};
 
-        use crate::position::{FenParseError, Position};
+        use crate::position::{FenParseErrorKind, Position};
 
         #[test]
         fn fen_smoke() {
@@ -1297,9 +2906,10 @@ mod tests {
             // no en passant.
             assert!(pos.en_passant_square().is_none());
 
-            // both clocks are zero.
+            // the halfmove clock is zero, and the fullmove clock of zero is normalized to one.
             assert_eq!(0, pos.halfmove_clock());
-            assert_eq!(0, pos.fullmove_clock());
+            assert_eq!(1, pos.fullmove_clock());
+            assert!(pos.as_fen().ends_with(" 0 1"));
         }
 
         #[test]
@@ -1371,73 +2981,99 @@ mod tests {
         #[test]
         fn empty() {
             let err = Position::from_fen("").unwrap_err();
-            assert_eq!(FenParseError::UnexpectedEnd, err);
+            assert_eq!(FenParseErrorKind::UnexpectedEnd, err.kind());
         }
 
         #[test]
         fn unknown_piece() {
             let err = Position::from_fen("z7/8/8/8/8/8/8/8 w - - 0 0").unwrap_err();
-            assert_eq!(FenParseError::UnknownPiece, err);
+            assert_eq!(FenParseErrorKind::UnknownPiece, err.kind());
         }
 
         #[test]
         fn invalid_digit() {
             let err = Position::from_fen("9/8/8/8/8/8/8/8 w - - 0 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidDigit, err);
+            assert_eq!(FenParseErrorKind::InvalidDigit, err.kind());
         }
 
         #[test]
         fn not_sum_to_8() {
             let err = Position::from_fen("pppp5/8/8/8/8/8/8/8 w - - 0 0").unwrap_err();
-            assert_eq!(FenParseError::FileDoesNotSumToEight, err);
+            assert_eq!(FenParseErrorKind::FileDoesNotSumToEight, err.kind());
         }
 
         #[test]
         fn bad_side_to_move() {
             let err = Position::from_fen("8/8/8/8/8/8/8/8 c - - 0 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidSideToMove, err);
+            assert_eq!(FenParseErrorKind::InvalidSideToMove, err.kind());
         }
 
         #[test]
         fn bad_castle_status() {
             let err = Position::from_fen("8/8/8/8/8/8/8/8 w a - 0 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidCastle, err);
+            assert_eq!(FenParseErrorKind::InvalidCastle, err.kind());
         }
 
         #[test]
         fn bad_en_passant() {
             let err = Position::from_fen("8/8/8/8/8/8/8/8 w - 88 0 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidEnPassant, err);
+            assert_eq!(FenParseErrorKind::InvalidEnPassant, err.kind());
         }
 
         #[test]
         fn empty_halfmove() {
             let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - q 0").unwrap_err();
-            assert_eq!(FenParseError::EmptyHalfmove, err);
+            assert_eq!(FenParseErrorKind::EmptyHalfmove, err.kind());
         }
 
         #[test]
         fn invalid_halfmove() {
             let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 4294967296 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidHalfmove, err);
+            assert_eq!(FenParseErrorKind::InvalidHalfmove, err.kind());
         }
 
         #[test]
         fn empty_fullmove() {
             let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0 q").unwrap_err();
-            assert_eq!(FenParseError::EmptyFullmove, err);
+            assert_eq!(FenParseErrorKind::EmptyFullmove, err.kind());
         }
 
         #[test]
         fn fullmove_early_end() {
             let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0").unwrap_err();
-            assert_eq!(FenParseError::UnexpectedEnd, err);
+            assert_eq!(FenParseErrorKind::UnexpectedEnd, err.kind());
         }
 
         #[test]
         fn invalid_fullmove() {
             let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0 4294967296").unwrap_err();
-            assert_eq!(FenParseError::InvalidFullmove, err);
+            assert_eq!(FenParseErrorKind::InvalidFullmove, err.kind());
+        }
+
+        #[test]
+        fn error_offsets() {
+            let err = Position::from_fen("").unwrap_err();
+            assert_eq!(0, err.offset());
+
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 c - - 0 0").unwrap_err();
+            assert_eq!(16, err.offset());
+
+            let err =
+                Position::from_fen("rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+                    .unwrap_err();
+            assert_eq!(FenParseErrorKind::UnknownPiece, err.kind());
+            assert_eq!(13, err.offset());
+
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - -  0 0").unwrap_err();
+            assert_eq!(FenParseErrorKind::EmptyHalfmove, err.kind());
+            assert_eq!(22, err.offset());
+
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 4294967296 0").unwrap_err();
+            assert_eq!(22, err.offset());
+
+            let fen = "8/8/8/8/8/8/8/8 w - - 0";
+            let err = Position::from_fen(fen).unwrap_err();
+            assert_eq!(fen.len(), err.offset());
         }
 
         #[test]
@@ -1446,6 +3082,21 @@ mod tests {
             assert_eq!(Move::null(), pos.move_from_uci("0000").unwrap());
         }
 
+        #[test]
+        fn uci_move_list() {
+            let mut pos = Position::from_start_position();
+            for uci in &["e2e4", "e7e5", "g1f3"] {
+                let mov = pos.move_from_uci(uci).unwrap();
+                assert!(pos.is_legal(mov), "{}", uci);
+                pos.apply_move(mov);
+            }
+
+            assert_eq!(
+                "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+                pos.as_fen()
+            );
+        }
+
         #[test]
         fn uci_sliding_moves() {
             let pos = Position::from_fen("8/3q4/8/8/8/3R4/8/8 w - - 0 1").unwrap();
@@ -1541,12 +3192,182 @@ mod tests {
                 pos.move_from_uci("e7f8q").unwrap()
             );
         }
+
+        #[test]
+        fn normalized_fen() {
+            // Nothing can capture the pawn on e4 en passant.
+            let first =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b qkQK e3 0 1")
+                    .unwrap();
+            let second =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+                    .unwrap();
+            assert_ne!(first.as_fen(), second.as_fen());
+            assert_eq!(first.normalized_fen(), second.normalized_fen());
+            assert_eq!(
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+                first.normalized_fen()
+            );
+
+            // Here the pawn on d4 can capture en passant, so the square stays.
+            let fen = "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1";
+            assert_eq!(fen, Position::from_fen(fen).unwrap().normalized_fen());
+
+            // Unless the capture would expose the king.
+            let pinned = Position::from_fen("8/8/8/8/k2pP2Q/8/8/4K3 b - e3 0 1").unwrap();
+            assert_eq!("8/8/8/8/k2pP2Q/8/8/4K3 b - - 0 1", pinned.normalized_fen());
+        }
+
+        #[test]
+        fn as_fen_round_trip() {
+            for &fen in &[
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 0 1",
+                "8/8/8/3pP3/8/8/8/4K2k w - d6 4 30",
+            ] {
+                assert_eq!(fen, Position::from_fen(fen).unwrap().as_fen());
+            }
+        }
+
+        #[test]
+        fn board_string_round_trip() {
+            let pos = Position::from_fen(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            )
+            .unwrap();
+            let parsed = Position::from_board_string(pos.to_string()).unwrap();
+            assert!(pos.diff(&parsed).is_empty());
+            assert_eq!(Color::White, parsed.side_to_move());
+            assert!(!parsed.can_castle_kingside(Color::White));
+        }
+
+        #[test]
+        fn board_string_without_labels() {
+            let pos = Position::from_board_string(
+                "
+                . . . . k . . .
+                . . . . . . . .
+                . . . . . . . .
+                . . . . . . . .
+                . . . . P . . .
+                . . . . . . . .
+                . . . . . . . .
+                . . . . K . . .
+                ",
+            )
+            .unwrap();
+            assert_eq!("4k3/8/8/8/4P3/8/8/4K3 w - - 0 1", pos.as_fen());
+        }
+
+        #[test]
+        fn board_string_errors() {
+            let short_row = ". . . . k . .\n";
+            let err = Position::from_board_string(short_row).unwrap_err();
+            assert_eq!(FenParseErrorKind::FileDoesNotSumToEight, err.kind());
+
+            let unknown = ". . . . x . . .\n";
+            let err = Position::from_board_string(unknown).unwrap_err();
+            assert_eq!(FenParseErrorKind::UnknownPiece, err.kind());
+            assert_eq!(8, err.offset());
+
+            let one_row = ". . . . k . . .\n";
+            let err = Position::from_board_string(one_row).unwrap_err();
+            assert_eq!(FenParseErrorKind::UnexpectedEnd, err.kind());
+        }
+
+        #[test]
+        fn shredder_castle_rights() {
+            let standard = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+            let shredder = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w AHah - 0 1";
+            let pos = Position::from_fen(shredder).unwrap();
+            for &color in &[Color::White, Color::Black] {
+                assert!(pos.can_castle_kingside(color));
+                assert!(pos.can_castle_queenside(color));
+            }
+            assert_eq!(Square::H1, pos.kingside_castle_rook(Color::White));
+            assert_eq!(Square::A8, pos.queenside_castle_rook(Color::Black));
+            assert_eq!(
+                Position::from_fen(standard).unwrap().zobrist_hash(),
+                pos.zobrist_hash()
+            );
+
+            // Shredder-FEN writes the kingside castle first.
+            assert_eq!(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1",
+                pos.as_fen()
+            );
+            assert_eq!(standard, pos.normalized_fen());
+        }
+
+        #[test]
+        fn shredder_round_trip() {
+            for &fen in &[
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1",
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w Hh - 1 9",
+            ] {
+                assert_eq!(fen, Position::from_fen(fen).unwrap().as_fen());
+            }
+        }
+
+        #[test]
+        fn mixed_castle_rights() {
+            // K and Q refer to the outermost rooks, so the inner rook on b1 has to be named.
+            let fen = "1r2k1r1/8/8/8/8/8/8/RR2K2R w KBgq - 0 1";
+            let pos = Position::from_fen(fen).unwrap();
+            assert_eq!(Square::H1, pos.kingside_castle_rook(Color::White));
+            assert_eq!(Square::B1, pos.queenside_castle_rook(Color::White));
+            assert_eq!(Square::G8, pos.kingside_castle_rook(Color::Black));
+            assert_eq!(Square::B8, pos.queenside_castle_rook(Color::Black));
+            assert_eq!("1r2k1r1/8/8/8/8/8/8/RR2K2R w KBkq - 0 1", pos.as_fen());
+
+            // In X-FEN, KQkq in a Chess960 position mean the outermost rooks too.
+            let pos = Position::from_fen(
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9",
+            )
+            .unwrap();
+            assert_eq!(Square::F1, pos.queenside_castle_rook(Color::White));
+            assert_eq!(Square::H8, pos.kingside_castle_rook(Color::Black));
+        }
+
+        #[test]
+        fn invalid_castle_file() {
+            // There's no king on the back rank to put the rook to one side of.
+            let err = Position::from_fen("8/8/8/8/8/8/4K3/R7 w A - 0 1").unwrap_err();
+            assert_eq!(FenParseErrorKind::InvalidCastle, err.kind());
+            let err = Position::from_fen("4k3/8/8/8/8/8/8/R3K3 w Z - 0 1").unwrap_err();
+            assert_eq!(FenParseErrorKind::InvalidCastle, err.kind());
+        }
+
+        #[test]
+        fn serde_round_trip() {
+            let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+            let pos = Position::from_fen(fen).unwrap();
+            let json = serde_json::to_string(&pos).unwrap();
+            assert_eq!(format!("\"{}\"", fen), json);
+
+            let round_tripped: Position = serde_json::from_str(&json).unwrap();
+            assert_eq!(fen, round_tripped.as_fen());
+            assert_eq!(pos.zobrist_hash(), round_tripped.zobrist_hash());
+        }
+
+        #[test]
+        fn serde_invalid_fen() {
+            let err =
+                serde_json::from_str::<Position>("\"8/8/8/8/8/8/8/8 x - - 0 1\"").unwrap_err();
+            assert!(
+                err.to_string()
+                    .starts_with("invalid FEN at offset 16: invalid side to move"),
+                "{}",
+                err
+            );
+        }
     }
 
     mod san {
         use crate::moves::Move;
         use crate::position::Position;
-        use crate::types::Square;
+        use crate::types::{PieceKind, Square};
 
         #[test]
         fn pawn_move() {
@@ -1619,13 +3440,154 @@ mod tests {
             let mov = pos.move_from_san("Kf2").unwrap();
             assert_eq!(mov, Move::quiet(Square::E1, Square::F2));
         }
+
+        #[test]
+        fn to_san_pieces_and_pawns() {
+            let pos = Position::from_start_position();
+            assert_eq!(
+                "e4",
+                pos.move_to_san(Move::double_pawn_push(Square::E2, Square::E4))
+            );
+            assert_eq!("Nf3", pos.move_to_san(Move::quiet(Square::G1, Square::F3)));
+
+            let pos = Position::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+            assert_eq!(
+                "exd5",
+                pos.move_to_san(Move::capture(Square::E4, Square::D5))
+            );
+        }
+
+        #[test]
+        fn to_san_disambiguation() {
+            let pos = Position::from_fen("3r3r/b7/3b4/R7/4Q2Q/8/8/R6Q b - - 0 1").unwrap();
+            assert_eq!("Bdb8", pos.move_to_san(Move::quiet(Square::D6, Square::B8)));
+
+            let pos = Position::from_fen("3r3r/b7/3b4/R7/4Q2Q/8/8/R6Q w - - 0 1").unwrap();
+            assert_eq!("R1a3", pos.move_to_san(Move::quiet(Square::A1, Square::A3)));
+            assert_eq!(
+                "Qh4e1",
+                pos.move_to_san(Move::quiet(Square::H4, Square::E1))
+            );
+            assert_eq!("Qhg2", pos.move_to_san(Move::quiet(Square::H1, Square::G2)));
+        }
+
+        #[test]
+        fn to_san_castles() {
+            let pos = Position::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+            assert_eq!(
+                "O-O",
+                pos.move_to_san(Move::kingside_castle(Square::E1, Square::G1))
+            );
+            assert_eq!(
+                "O-O-O",
+                pos.move_to_san(Move::queenside_castle(Square::E1, Square::C1))
+            );
+        }
+
+        #[test]
+        fn to_san_promotion() {
+            let pos = Position::from_fen("1n5k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+            assert_eq!(
+                "a8=Q",
+                pos.move_to_san(Move::promotion(Square::A7, Square::A8, PieceKind::Queen))
+            );
+            assert_eq!(
+                "axb8=N",
+                pos.move_to_san(Move::promotion_capture(
+                    Square::A7,
+                    Square::B8,
+                    PieceKind::Knight
+                ))
+            );
+        }
+
+        #[test]
+        fn to_san_checkmate() {
+            let pos = Position::from_fen(
+                "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2",
+            )
+            .unwrap();
+            assert_eq!("Qh4#", pos.move_to_san(Move::quiet(Square::D8, Square::H4)));
+        }
+
+        #[test]
+        fn from_san_promotion() {
+            let pos = Position::from_fen("1n5k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+            assert_eq!(
+                Some(Move::promotion(Square::A7, Square::A8, PieceKind::Queen)),
+                pos.move_from_san("a8=Q+")
+            );
+            assert_eq!(
+                Some(Move::promotion(Square::A7, Square::A8, PieceKind::Rook)),
+                pos.move_from_san("a8R")
+            );
+            assert_eq!(
+                Some(Move::promotion_capture(
+                    Square::A7,
+                    Square::B8,
+                    PieceKind::Knight
+                )),
+                pos.move_from_san("axb8=N")
+            );
+            assert_eq!(None, pos.move_from_san("a8"));
+            assert_eq!(None, pos.move_from_san("a8=K"));
+        }
+
+        #[test]
+        fn from_san_castle_with_check() {
+            let pos = Position::from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+            assert_eq!(
+                Some(Move::kingside_castle(Square::E1, Square::G1)),
+                pos.move_from_san("O-O+")
+            );
+        }
+
+        #[test]
+        fn round_trip_game() {
+            // Morphy vs. the Duke of Brunswick and Count Isouard, Paris 1858.
+            let game =
+                "e4 e5 Nf3 d6 d4 Bg4 dxe5 Bxf3 Qxf3 dxe5 Bc4 Nf6 Qb3 Qe7 Nc3 c6 Bg5 b5 Nxb5 \
+                        cxb5 Bxb5+ Nbd7 O-O-O Rd8 Rxd7 Rxd7 Rd1 Qe6 Bxd7+ Nxd7 Qb8+ Nxb8 Rd8#";
+            let mut pos = Position::from_start_position();
+            for san in game.split_whitespace() {
+                let mov = pos
+                    .move_from_san(san)
+                    .unwrap_or_else(|| panic!("{} in {}", san, pos.as_fen()));
+                assert_eq!(san, pos.move_to_san(mov));
+                pos.apply_move(mov);
+            }
+
+            assert!(pos.legal_moves().is_empty());
+        }
+
+        #[test]
+        fn round_trip_every_move() {
+            let fens = [
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+                "3r3r/b7/3b4/R7/4Q2Q/8/8/R6Q w - - 0 1",
+                "1n2k3/P1P5/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            ];
+            for &fen in &fens {
+                let pos = Position::from_fen(fen).unwrap();
+                for mov in pos.legal_moves() {
+                    let san = pos.move_to_san(mov);
+                    assert_eq!(Some(mov), pos.move_from_san(&san), "{} in {}", san, fen);
+                }
+            }
+        }
     }
 
     mod apply {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        use crate::move_generator::{MoveGenerator, MoveVec};
         use crate::position::Position;
 
         use crate::moves::Move;
-        use crate::types::{Color, PieceKind, Square};
+        use crate::types::{Color, Piece, PieceKind, Square};
+        use crate::zobrist;
 
         #[test]
         fn smoke_test_opening_pawn() {
@@ -1700,6 +3662,24 @@ mod tests {
             assert_eq!(0, pos.halfmove_clock());
         }
 
+        #[test]
+        fn capture_diff() {
+            let before = Position::from_fen("8/8/8/8/5p2/4P3/8/8 w - - 2 1").unwrap();
+            let mut after = before.clone();
+            after.apply_move(Move::capture(Square::E3, Square::F4));
+
+            let white_pawn = Piece::new(PieceKind::Pawn, Color::White);
+            let black_pawn = Piece::new(PieceKind::Pawn, Color::Black);
+            assert_eq!(
+                before.diff(&after),
+                vec![
+                    (Square::E3, Some(white_pawn), None),
+                    (Square::F4, Some(black_pawn), Some(white_pawn)),
+                ]
+            );
+            assert!(after.diff(&after).is_empty());
+        }
+
         #[test]
         fn non_pawn_quiet_move() {
             let mut pos = Position::from_fen("8/8/8/8/8/8/4B3/8 w - - 5 2").unwrap();
@@ -1775,6 +3755,109 @@ mod tests {
             assert!(!pos.can_castle_kingside(Color::White));
         }
 
+        #[test]
+        fn castle_status_hash() {
+            // Castle rights are only ever lost once, no matter how many times the king and rooks
+            // move, and the incrementally-updated hash always agrees with a from-scratch hash.
+            let mut pos = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+            let moves = [
+                (Move::capture(Square::A1, Square::A8), "Kk"),
+                (Move::quiet(Square::E8, Square::D7), "K"),
+                (Move::quiet(Square::A8, Square::A1), "K"),
+                (Move::quiet(Square::D7, Square::E8), "K"),
+                (Move::quiet(Square::E1, Square::F1), "-"),
+                (Move::quiet(Square::H8, Square::H7), "-"),
+                (Move::quiet(Square::F1, Square::E1), "-"),
+            ];
+
+            for &(mov, castles) in moves.iter() {
+                pos.apply_move(mov);
+                assert_eq!(castles.contains('K'), pos.can_castle_kingside(Color::White));
+                assert_eq!(
+                    castles.contains('Q'),
+                    pos.can_castle_queenside(Color::White)
+                );
+                assert_eq!(castles.contains('k'), pos.can_castle_kingside(Color::Black));
+                assert_eq!(
+                    castles.contains('q'),
+                    pos.can_castle_queenside(Color::Black)
+                );
+                assert_eq!(zobrist::hash(&pos), pos.zobrist_hash(), "after {}", mov);
+            }
+        }
+
+        #[test]
+        fn en_passant_hash_transposition() {
+            // The same position arises with and without an en passant square, but no black pawn
+            // can capture on e3, so the square doesn't matter and the hashes agree.
+            let play = |moves: &[&str]| {
+                let mut pos = Position::from_start_position();
+                for uci in moves {
+                    let mov = pos.move_from_uci(uci).unwrap();
+                    pos.apply_move(mov);
+                }
+                pos
+            };
+
+            let pushed_last = play(&["g1f3", "g8f6", "e2e4"]);
+            let pushed_first = play(&["e2e4", "g8f6", "g1f3"]);
+            assert_eq!(Some(Square::E3), pushed_last.en_passant_square());
+            assert_eq!(None, pushed_first.en_passant_square());
+            assert_eq!(pushed_first.zobrist_hash(), pushed_last.zobrist_hash());
+            assert_eq!(zobrist::hash(&pushed_last), pushed_last.zobrist_hash());
+
+            // From a FEN, too.
+            let fen = pushed_last.as_fen();
+            assert!(fen.contains(" e3 "));
+            let from_fen = Position::from_fen(&fen).unwrap();
+            assert_eq!(pushed_first.zobrist_hash(), from_fen.zobrist_hash());
+        }
+
+        #[test]
+        fn en_passant_hash_with_capturer() {
+            // White's pawn on e5 can capture on d6, so the en passant square is part of the hash
+            // until White's next move, capture or not.
+            let mut pos =
+                Position::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3")
+                    .unwrap();
+            let without =
+                Position::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3")
+                    .unwrap();
+            assert_eq!(Some(Square::D6), pos.capturable_en_passant_square());
+            assert_ne!(without.zobrist_hash(), pos.zobrist_hash());
+
+            pos.apply_move(Move::quiet(Square::G1, Square::F3));
+            assert_eq!(zobrist::hash(&pos), pos.zobrist_hash());
+
+            let mut null = without.clone();
+            null.apply_move(Move::null());
+            let mut pos =
+                Position::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3")
+                    .unwrap();
+            pos.apply_move(Move::null());
+            assert_eq!(null.zobrist_hash(), pos.zobrist_hash());
+            assert_eq!(zobrist::hash(&pos), pos.zobrist_hash());
+        }
+
+        #[test]
+        fn castling_clears_castle_status() {
+            let mut pos = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+            pos.apply_move(Move::queenside_castle(Square::E8, Square::C8));
+            assert!(pos.can_castle_kingside(Color::White));
+            assert!(pos.can_castle_queenside(Color::White));
+            assert!(!pos.can_castle_kingside(Color::Black));
+            assert!(!pos.can_castle_queenside(Color::Black));
+            assert_eq!(zobrist::hash(&pos), pos.zobrist_hash());
+        }
+
+        #[test]
+        #[cfg(debug_assertions)]
+        #[should_panic(expected = "apply_move: move a2h6 is not pseudo-legal")]
+        fn illegal_move_panics() {
+            let mut pos = Position::from_start_position();
+            pos.apply_move(Move::quiet(Square::A2, Square::H6));
+        }
+
         #[test]
         fn en_passant_capture() {
             // tests that we remove an ep-captured piece from its
@@ -1856,5 +3939,224 @@ mod tests {
             assert_eq!(Color::White, king.color);
             assert_eq!(PieceKind::King, king.kind);
         }
+
+        fn assert_identical(expected: &Position, actual: &Position, context: &str) {
+            assert_eq!(
+                expected.boards_by_piece, actual.boards_by_piece,
+                "{}",
+                context
+            );
+            assert_eq!(
+                expected.boards_by_color, actual.boards_by_color,
+                "{}",
+                context
+            );
+            assert_eq!(
+                expected.en_passant_square, actual.en_passant_square,
+                "{}",
+                context
+            );
+            assert_eq!(
+                expected.halfmove_clock, actual.halfmove_clock,
+                "{}",
+                context
+            );
+            assert_eq!(
+                expected.fullmove_clock, actual.fullmove_clock,
+                "{}",
+                context
+            );
+            assert_eq!(expected.side_to_move, actual.side_to_move, "{}", context);
+            assert_eq!(expected.castle_status, actual.castle_status, "{}", context);
+            assert_eq!(expected.zobrist_hash, actual.zobrist_hash, "{}", context);
+            assert_eq!(expected.move_history, actual.move_history, "{}", context);
+        }
+
+        #[test]
+        fn make_unmake_round_trip() {
+            // Positions with castles, en passant, and promotions on the board, plus a few hundred
+            // random ones.
+            let mut positions: Vec<Position> = [
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1",
+                "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                "4k3/8/8/8/8/8/8/1R3KR1 w GB - 0 1",
+            ]
+            .iter()
+            .map(|fen| Position::from_fen(fen).unwrap())
+            .collect();
+            let mut rng = StdRng::seed_from_u64(1501);
+            for _ in 0..300 {
+                let plies = rng.gen_range(0, 60);
+                positions.push(Position::random_opening(&mut rng, plies));
+            }
+
+            let movegen = MoveGenerator::new();
+            for pos in &positions {
+                let mut moves = MoveVec::default();
+                movegen.generate_moves(pos, &mut moves);
+                moves.push(Move::null());
+                let mut scratch = pos.clone();
+                for &mov in &moves {
+                    let undo = scratch.make_move(mov);
+                    let mut applied = pos.clone();
+                    applied.apply_move(mov);
+                    let context = format!("{} in {}", mov, pos.as_fen());
+                    assert_identical(&applied, &scratch, &context);
+                    scratch.unmake_move(mov, undo);
+                    assert_identical(pos, &scratch, &context);
+                }
+            }
+        }
+    }
+
+    mod chess960 {
+        use crate::moves::Move;
+        use crate::position::Position;
+        use crate::types::{Color, Square};
+
+        fn castle_result(fen: &'static str, mov: Move) -> String {
+            let pos = Position::from_fen(fen).unwrap();
+            assert!(pos.legal_moves().contains(&mov), "{} in {}", mov, fen);
+            let mut child = pos.clone();
+            let undo = child.make_move(mov);
+            let result = child.as_fen();
+            child.unmake_move(mov, undo);
+            assert_eq!(fen, child.as_fen());
+            result
+        }
+
+        #[test]
+        fn shredder_castle_rights() {
+            let pos = Position::from_fen(
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            )
+            .unwrap();
+            assert_eq!(Square::H1, pos.kingside_castle_rook(Color::White));
+            assert_eq!(Square::F1, pos.queenside_castle_rook(Color::White));
+            assert_eq!(Square::H8, pos.kingside_castle_rook(Color::Black));
+            assert_eq!(Square::F8, pos.queenside_castle_rook(Color::Black));
+            assert!(pos.can_castle_queenside(Color::Black));
+        }
+
+        #[test]
+        fn king_and_rook_trade_places() {
+            // The king on f1 castles onto the rook's square on g1, and the rook onto the king's.
+            assert_eq!(
+                "4k3/8/8/8/8/8/8/5RK1 b - - 1 1",
+                castle_result(
+                    "4k3/8/8/8/8/8/8/5KR1 w G - 0 1",
+                    Move::kingside_castle(Square::F1, Square::G1)
+                )
+            );
+        }
+
+        #[test]
+        fn king_stays_put() {
+            // The king already stands on g1, so castling kingside only moves the rook.
+            let fen = "4k3/8/8/8/8/8/8/1R4KR w HB - 0 1";
+            assert_eq!(
+                "4k3/8/8/8/8/8/8/1R3RK1 b - - 1 1",
+                castle_result(fen, Move::kingside_castle(Square::G1, Square::G1))
+            );
+            assert_eq!(
+                "4k3/8/8/8/8/8/8/2KR3R b - - 1 1",
+                castle_result(fen, Move::queenside_castle(Square::G1, Square::C1))
+            );
+        }
+
+        #[test]
+        fn castle_uncovering_attack_on_king() {
+            // The rook on b1 shields c1 from the queen on a1 until it castles over to d1.
+            let pos = Position::from_fen("4k3/8/8/8/8/8/8/qR1K4 w B - 0 1").unwrap();
+            let castle = Move::queenside_castle(Square::D1, Square::C1);
+            assert!(!pos.is_legal(castle));
+            assert!(!pos.legal_moves().contains(&castle));
+        }
+
+        #[test]
+        fn king_captures_rook_notation() {
+            let pos = Position::from_fen("4k3/8/8/8/8/8/8/5KR1 w G - 0 1").unwrap();
+            assert_eq!(
+                Some(Move::kingside_castle(Square::F1, Square::G1)),
+                pos.move_from_uci("f1g1")
+            );
+            assert_eq!(
+                Some(Move::kingside_castle(Square::F1, Square::G1)),
+                pos.move_from_san("O-O")
+            );
+        }
+    }
+
+    mod polyglot {
+        use crate::position::{polyglot_hash, Position};
+
+        #[test]
+        fn start_position() {
+            assert_eq!(
+                0x463b96181691fc9c,
+                polyglot_hash(&Position::from_start_position())
+            );
+        }
+
+        #[test]
+        fn reference_keys() {
+            // The keys published along with the Polyglot book format.
+            let positions: &[(&str, u64)] = &[
+                (
+                    "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+                    0x823c9b50fd114196,
+                ),
+                (
+                    "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+                    0x0756b94461c50fb0,
+                ),
+                (
+                    "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+                    0x662fafb965db29d4,
+                ),
+                (
+                    "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                    0x22a48b5a8e47ff78,
+                ),
+                (
+                    "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3",
+                    0x652a607ca3f242c1,
+                ),
+                (
+                    "rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4",
+                    0x00fdd303c946bdd9,
+                ),
+                (
+                    "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+                    0x3c8123ea7b067637,
+                ),
+                (
+                    "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4",
+                    0x5c3f9b829b279560,
+                ),
+            ];
+
+            for &(fen, key) in positions {
+                let pos = Position::from_fen(fen).unwrap();
+                assert_eq!(key, polyglot_hash(&pos), "{}", fen);
+            }
+        }
+
+        #[test]
+        fn en_passant_needs_a_capturer() {
+            // After 1. e4, no black pawn can capture on e3, so the position hashes the same as it
+            // would without an en-passant square.
+            let with_ep =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+                    .unwrap();
+            let without_ep =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+                    .unwrap();
+            assert_eq!(polyglot_hash(&with_ep), polyglot_hash(&without_ep));
+        }
     }
 }
@@ -30,6 +30,45 @@ pub struct Record {
     pub hash_move_node: u64,
     pub hash_move_beta_cutoff: u64,
     pub hash_move_improved_alpha: u64,
+
+    /// The number of beta cutoffs, counting both `cut_nodes` and `hash_move_beta_cutoff`, that were
+    /// caused by the first move searched at the node.
+    pub first_move_cutoffs: u64,
+
+    /// The number of nodes that were cut because passing the move to the opponent still failed high.
+    pub null_move_cutoffs: u64,
+
+    /// The number of moves that principal variation search had to search again with the full
+    /// window, because they turned out to be better than the moves searched before them.
+    pub pvs_re_searches: u64,
+
+    /// The number of late moves that had to be searched again to the full depth, because the
+    /// reduced search said that they might be better than the moves searched before them.
+    pub lmr_re_searches: u64,
+
+    /// The number of times that the root had to be searched again because its score fell outside
+    /// of the aspiration window.
+    pub aspiration_re_searches: u64,
+
+    /// The deepest ply past the horizon that quiescence search reached.
+    pub quiescence_depth: u32,
+
+    /// The effective branching factor: the ratio of the nodes searched at this depth to the nodes
+    /// searched at the previous depth. Absent for the first depth of a search.
+    pub branching_factor: Option<f64>,
+}
+
+impl Record {
+    /// The fraction of beta cutoffs that were caused by the first move searched. Well-ordered moves
+    /// put the refutation first, so this is a measure of move ordering quality.
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        let cutoffs = self.cut_nodes + self.hash_move_beta_cutoff;
+        if cutoffs == 0 {
+            return 0.0;
+        }
+
+        self.first_move_cutoffs as f64 / cutoffs as f64
+    }
 }
 
 pub trait DataRecorder {
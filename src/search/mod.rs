@@ -7,9 +7,15 @@
 // except according to those terms.
 
 mod data;
+mod observer;
+mod params;
 mod searcher;
+mod time_management;
 mod transposition_table;
 
 pub use data::{CsvDataRecorder, DataRecorder, NullDataRecorder, Record};
+pub use observer::{NullSearchObserver, SearchInfo, SearchObserver};
+pub use params::SearchParams;
 pub use searcher::{SearchResult, Searcher};
+pub use time_management::{allocate_time, SearchRequest};
 pub use transposition_table::{NodeKind, TableEntry, TableStats, TranspositionTable};
@@ -0,0 +1,41 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::time::Duration;
+
+use crate::eval::Score;
+use crate::moves::Move;
+
+/// The state of a search after it finished searching to some depth.
+pub struct SearchInfo<'a> {
+    pub depth: u32,
+    pub score: Score,
+    /// The number of nodes searched since the search began, counting every depth so far.
+    pub nodes: u64,
+    /// The time since the search began.
+    pub elapsed: Duration,
+    /// The principal variation found at this depth, starting with the best move.
+    pub pv: &'a [Move],
+}
+
+impl<'a> SearchInfo<'a> {
+    /// The number of nodes searched per second since the search began.
+    pub fn nodes_per_second(&self) -> u64 {
+        let micros = self.elapsed.as_micros().max(1);
+        (u128::from(self.nodes) * 1_000_000 / micros) as u64
+    }
+}
+
+/// An observer of a search's progress, which is told about each depth as the search completes it.
+pub trait SearchObserver {
+    fn depth_completed(&self, info: &SearchInfo);
+}
+
+pub struct NullSearchObserver;
+impl SearchObserver for NullSearchObserver {
+    fn depth_completed(&self, _info: &SearchInfo) {}
+}
@@ -0,0 +1,79 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/// Tunable constants that control the searcher's pruning and reduction decisions. Grouping them
+/// here, instead of scattering constants through the searcher, lets them be adjusted through UCI
+/// options or tuning experiments without recompiling.
+///
+/// Features whose parameter is `None` are disabled. The default parameters disable all of the
+/// pruning and reduction features, which can change the result of a search, and enable the move
+/// ordering heuristics, which only change how quickly the search arrives at its result.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchParams {
+    /// The margin, in pawns, by which the side to move must be ahead at the root before the
+    /// searcher starts steering away from repeated positions.
+    pub repetition_margin: f32,
+
+    /// The depth reduction applied to the verification search after a null move.
+    pub null_move_reduction: Option<u32>,
+
+    /// The depth reduction applied to moves that are ordered late in the move list.
+    pub late_move_reduction: Option<u32>,
+
+    /// The margin, in pawns, by which a quiet move at a frontier node must be able to improve the
+    /// static evaluation to be worth searching.
+    pub futility_margin: Option<f32>,
+
+    /// The half-width, in pawns, of the window around the previous iteration's score that each
+    /// iteration of iterative deepening begins searching with.
+    pub aspiration_delta: Option<f32>,
+
+    /// The margin, in pawns, below alpha at which nodes just above the horizon are dropped
+    /// straight into quiescence search.
+    pub razor_margin: Option<f32>,
+
+    /// The depth of the verification search that the chosen move gets once the main search
+    /// finishes. The verification search looks for refutations that pruning hid from the main
+    /// search.
+    pub blunder_check_depth: Option<u32>,
+
+    /// The amount, in pawns, by which the verification search's score for the chosen move must fall
+    /// short of the main search's score before the searcher looks for a different move.
+    pub blunder_margin: f32,
+
+    /// The number of plies that quiescence search may extend past the horizon. Quiescence nodes
+    /// this far past the horizon settle for the static evaluation, which bounds the search in
+    /// positions with long chains of captures.
+    pub qsearch_max_depth: u32,
+
+    /// Whether quiet moves that caused beta cutoffs elsewhere at the same ply are tried right after
+    /// the winning captures.
+    pub killer_moves: bool,
+
+    /// Whether every move after the first at each node is searched with a null window first, on
+    /// the assumption that the first move is the best one.
+    pub principal_variation_search: bool,
+}
+
+impl Default for SearchParams {
+    fn default() -> SearchParams {
+        SearchParams {
+            repetition_margin: 0.0,
+            null_move_reduction: None,
+            late_move_reduction: None,
+            futility_margin: None,
+            aspiration_delta: None,
+            razor_margin: None,
+            blunder_check_depth: None,
+            blunder_margin: 1.0,
+            qsearch_max_depth: 8,
+            killer_moves: true,
+            principal_variation_search: true,
+        }
+    }
+}
@@ -7,50 +7,149 @@
 // except according to those terms.
 use rand::seq::SliceRandom;
 use rand::thread_rng;
+use std::cmp::Reverse;
+use std::mem;
 use std::time::{Duration, Instant};
 
+use crate::bitboard::{BB_RANK_2, BB_RANK_7};
 use crate::book::OpeningBook;
 use crate::eval::{BoardEvaluator, Score};
+use crate::history::{is_repetition, PositionHistory};
 use crate::move_generator::{MoveGenerator, MoveVec};
 use crate::moves::Move;
 use crate::position::Position;
-use crate::search::{DataRecorder, NodeKind, Record, TranspositionTable};
-use crate::types::{Color, PieceKind, Square};
+use crate::search::{
+    DataRecorder, NodeKind, NullDataRecorder, NullSearchObserver, Record, SearchInfo,
+    SearchObserver, SearchParams, TranspositionTable,
+};
+use crate::types::{Color, PieceKind, Rank, Square, TableIndex};
 
 pub struct SearchResult {
     pub best_move: Move,
     pub nodes_searched: u64,
     pub score: Score,
+    /// The principal variation: the line of play that the searcher expects, starting with the best move.
+    pub pv: Vec<Move>,
+}
+
+impl SearchResult {
+    /// The move that the searcher expects the opponent to reply to the best move with, if any. This is the move
+    /// that an engine would ponder on while waiting for the opponent to move.
+    pub fn ponder_move(&self) -> Option<Move> {
+        self.pv.get(1).copied()
+    }
 }
 
 pub struct Searcher<E> {
     evaluator: E,
     ttable: TranspositionTable,
     book: Option<OpeningBook>,
+    game_history: Vec<u64>,
+    params: SearchParams,
 }
 
 impl<E: BoardEvaluator> Searcher<E> {
     pub fn new(book: Option<OpeningBook>) -> Searcher<E> {
+        Searcher::with_evaluator(Default::default(), book)
+    }
+
+    /// Constructs a new searcher that evaluates positions with the given evaluator.
+    pub fn with_evaluator(evaluator: E, book: Option<OpeningBook>) -> Searcher<E> {
         Searcher {
-            evaluator: Default::default(),
+            evaluator,
             ttable: TranspositionTable::new(),
-            book: book,
+            book,
+            game_history: vec![],
+            params: Default::default(),
         }
     }
 
+    /// Sets the zobrist hashes of the positions that occurred in the game prior to the position
+    /// that is going to be searched. The searcher scores lines that repeat one of these positions
+    /// as draws.
+    pub fn set_game_history(&mut self, history: Vec<u64>) {
+        self.game_history = history;
+    }
+
+    /// Replaces the evaluator that this searcher uses for subsequent searches. Since the transposition table holds
+    /// scores from the old evaluator, this also clears the table.
+    pub fn set_evaluator(&mut self, evaluator: E) {
+        self.evaluator = evaluator;
+        self.clear_hash();
+    }
+
+    /// The tunable parameters that this searcher is currently using.
+    pub fn params(&self) -> &SearchParams {
+        &self.params
+    }
+
+    /// Replaces the tunable parameters that this searcher uses for subsequent searches.
+    pub fn set_params(&mut self, params: SearchParams) {
+        self.params = params;
+    }
+
+    /// The transposition table that this searcher records its results in.
+    pub fn transposition_table(&self) -> &TranspositionTable {
+        &self.ttable
+    }
+
+    /// Forgets everything that previous searches have recorded in the transposition table.
+    pub fn clear_hash(&mut self) {
+        self.ttable.clear();
+    }
+
     pub fn search(
         &mut self,
         pos: &Position,
         max_depth: u32,
         time_budget: Option<Duration>,
         recorder: &dyn DataRecorder,
+    ) -> SearchResult {
+        self.search_with_limits(pos, max_depth, time_budget, time_budget, recorder)
+    }
+
+    /// Searches the given position with separate soft and hard time limits. Once the soft limit has
+    /// passed, the searcher won't start searching another depth, but it will finish the depth that
+    /// it's on. Once the hard limit has passed, the searcher abandons the depth that it's on and
+    /// returns the results of the last depth that it finished.
+    pub fn search_with_limits(
+        &mut self,
+        pos: &Position,
+        max_depth: u32,
+        soft_limit: Option<Duration>,
+        hard_limit: Option<Duration>,
+        recorder: &dyn DataRecorder,
+    ) -> SearchResult {
+        self.search_observed(
+            pos,
+            max_depth,
+            soft_limit,
+            hard_limit,
+            recorder,
+            &NullSearchObserver,
+        )
+    }
+
+    /// Searches the given position with separate soft and hard time limits, like `search_with_limits`, and tells
+    /// the given observer about each depth as the search completes it.
+    pub fn search_observed(
+        &mut self,
+        pos: &Position,
+        max_depth: u32,
+        soft_limit: Option<Duration>,
+        hard_limit: Option<Duration>,
+        recorder: &dyn DataRecorder,
+        observer: &dyn SearchObserver,
     ) -> SearchResult {
         // Play a random book move, if we're still in the book.
         if let Some(book) = self.book.as_ref() {
             let history = pos.move_history();
             if book.is_in_book(history) {
                 let mut rng = thread_rng();
-                let book_moves = book.book_moves(history);
+                // The book is keyed on move history alone, which doesn't account for games that
+                // didn't begin at the starting position. Don't trust it blindly.
+                let mut book_moves = book.book_moves(history);
+                book_moves.retain(|&(mov, _)| pos.is_legal(mov));
                 if let Some((book_move, entry)) = book_moves.choose(&mut rng) {
                     info!("playing book move: {} ({})", book_move, entry.lead_name);
                     let mut new_pos = pos.clone();
@@ -60,6 +159,7 @@ impl<E: BoardEvaluator> Searcher<E> {
                         best_move: *book_move,
                         nodes_searched: 1,
                         score: score,
+                        pv: vec![*book_move],
                     };
                 } else {
                     info!("not playing book move, book departure at {:?}", history);
@@ -67,8 +167,35 @@ impl<E: BoardEvaluator> Searcher<E> {
             }
         }
 
-        let mut search = IterativeSearch::new(self, max_depth, time_budget);
-        search.search(pos, recorder)
+        let mut search = IterativeSearch::new(self, max_depth, soft_limit, hard_limit, observer);
+        let result = search.search(pos, recorder);
+        match self.params.blunder_check_depth {
+            Some(depth) => search.check_for_blunder(pos, result, depth),
+            None => result,
+        }
+    }
+
+    /// Searches the given position for the `n` best moves, returning a result for each of them, best first. Each
+    /// result's score and principal variation are those of the line starting with its move.
+    ///
+    /// The best moves are found one at a time, by searching again with the moves already found excluded from the root.
+    /// Fewer than `n` results are returned if there are fewer than `n` legal moves.
+    pub fn search_multipv(
+        &mut self,
+        pos: &Position,
+        max_depth: u32,
+        n: usize,
+    ) -> Vec<SearchResult> {
+        let mut search = IterativeSearch::new(self, max_depth, None, None, &NullSearchObserver);
+        let mut results: Vec<SearchResult> = vec![];
+        let lines = n.min(pos.legal_moves().len());
+        while results.len() < lines {
+            let result = search.search(pos, &NullDataRecorder);
+            search.excluded_root_moves.push(result.best_move);
+            results.push(result);
+        }
+
+        results
     }
 }
 
@@ -81,61 +208,220 @@ impl<E: BoardEvaluator> Default for Searcher<E> {
 struct IterativeSearch<'a, E> {
     searcher: &'a Searcher<E>,
     max_depth: u32,
-    time_budget: Option<Duration>,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
     start_time: Instant,
 
+    /// Told about each depth as the search completes it.
+    observer: &'a dyn SearchObserver,
+
+    /// Moves that the search must not consider at the root.
+    excluded_root_moves: Vec<Move>,
+
+    /// The number of nodes searched at each completed depth, starting from depth 1.
+    depth_nodes: Vec<u64>,
+
+    /// The positions on the path from the root to the node being searched.
+    path: PositionHistory,
+
+    /// The two most recent quiet moves that caused beta cutoffs at each ply, most recent first.
+    /// Empty slots hold the null move.
+    killers: Vec<[Move; 2]>,
+
+    /// How often each quiet move, indexed by its source and destination squares, has caused a beta cutoff or improved
+    /// alpha, weighted towards the deeper searches.
+    history: HistoryTable,
+
+    /// The ply of the most recent null move on the path from the root to the node being searched, if there is one.
+    null_move_ply: Option<u32>,
+
     stats: Record,
 }
 
+/// Scores for quiet moves, indexed by their source and destination squares.
+type HistoryTable = [[u32; 64]; 64];
+
 impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
     pub fn new(
         searcher: &'a Searcher<E>,
         max_depth: u32,
-        budget: Option<Duration>,
+        soft_limit: Option<Duration>,
+        hard_limit: Option<Duration>,
+        observer: &'a dyn SearchObserver,
     ) -> IterativeSearch<'a, E> {
         IterativeSearch {
             searcher: searcher,
             max_depth: max_depth,
-            time_budget: budget,
+            soft_limit,
+            hard_limit,
             start_time: Instant::now(),
+            observer,
+            excluded_root_moves: vec![],
+            depth_nodes: vec![],
+            path: PositionHistory::new(),
+            killers: vec![],
+            history: [[0; 64]; 64],
+            null_move_ply: None,
             stats: Default::default(),
         }
     }
 
-    /// Does a toplevel search of a given depth.
+    /// Does a toplevel search of a given depth. `previous_score` is the score that the search of the previous depth
+    /// produced, if there was one.
     fn search_depth(
         &mut self,
         pos: &Position,
         depth: u32,
+        previous_score: Option<Score>,
         recorder: &dyn DataRecorder,
     ) -> SearchResult {
         self.stats = Default::default();
         self.stats.depth = depth;
-        let alpha = Score::Loss(0);
-        let beta = Score::Win(0);
-        let score = self.alpha_beta(pos, alpha, beta, depth);
+        self.killers.clear();
+        // Deeper searches produce bigger history scores anyway, but halving the old ones keeps moves that were good at
+        // the last depth from crowding out the ones that are good at this depth.
+        for scores in self.history.iter_mut() {
+            for score in scores.iter_mut() {
+                *score /= 2;
+            }
+        }
+
+        // The score usually doesn't change much from one depth to the next, so the search begins with a narrow window
+        // around the previous depth's score, which prunes a lot more than the full window does. If the score turns out
+        // to lie outside of the window, the search is repeated with that side of the window opened up all the way.
+        let (mut alpha, mut beta) = match (self.searcher.params.aspiration_delta, previous_score) {
+            (Some(delta), Some(score @ Score::Evaluated(_))) => (
+                score + Score::Evaluated(-delta),
+                score + Score::Evaluated(delta),
+            ),
+            _ => (Score::Loss(0), Score::Win(0)),
+        };
+
+        let score = loop {
+            let score = self.alpha_beta(&mut pos.clone(), alpha, beta, depth, 0);
+            if self.out_of_time() {
+                break score;
+            }
+
+            if score <= alpha && alpha != Score::Loss(0) {
+                debug!("aspiration window failed low at {}", score);
+                alpha = Score::Loss(0);
+            } else if score >= beta && beta != Score::Win(0) {
+                debug!("aspiration window failed high at {}", score);
+                beta = Score::Win(0);
+            } else {
+                break score;
+            }
+
+            self.stats.aspiration_re_searches += 1;
+        };
+
+        let out_of_time = self.out_of_time();
         let best_move = self.searcher.ttable.query(pos, |entry| {
+            // A search that ran out of time can leave the root missing from the table, e.g. when it was aborted during
+            // an aspiration re-search, or looking like an all-node when it failed low, but its results get thrown away
+            // anyway.
+            if out_of_time {
+                return entry
+                    .and_then(|entry| entry.best_move)
+                    .unwrap_or_else(Move::null);
+            }
+
             entry
                 .expect("search_depth yielded t-table miss after search")
                 .best_move
                 .expect("search_depth thinks that root node is an all-node")
         });
 
+        self.stats.branching_factor = self
+            .depth_nodes
+            .last()
+            .map(|&previous| self.stats.nodes as f64 / previous as f64);
+        self.depth_nodes.push(self.stats.nodes);
         recorder.record(pos, &self.stats);
         SearchResult {
             best_move: best_move,
             score: score,
             nodes_searched: self.stats.nodes,
+            pv: self.get_pv(pos, depth),
         }
     }
 
-    fn quiesce(&mut self, pos: &Position, _alpha: Score, _beta: Score) -> Score {
+    /// Searches past the horizon until the position is quiet. `qdepth` is the number of plies
+    /// that this node lies past the horizon.
+    ///
+    /// Only captures that don't lose material and promotions are searched here. The side to move
+    /// is assumed to be able to do at least as well as the static evaluation by making some quiet
+    /// move ("standing pat"), so the static evaluation serves as a lower bound on the score.
+    fn quiesce(
+        &mut self,
+        pos: &mut Position,
+        mut alpha: Score,
+        beta: Score,
+        ply: u32,
+        qdepth: u32,
+    ) -> Score {
         self.stats.nodes += 1;
+        self.stats.quiescence_depth = self.stats.quiescence_depth.max(qdepth);
+        let stand_pat = self.static_evaluation(pos, ply);
+        if qdepth >= self.searcher.params.qsearch_max_depth {
+            return stand_pat;
+        }
+
+        if stand_pat >= beta {
+            return beta;
+        }
+
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let gen = MoveGenerator::new();
+        let mut moves = MoveVec::default();
+        gen.generate_captures(pos, &mut moves);
+        gen.retain_legal(pos, &mut moves);
+        filter_good_captures(pos, &mut moves);
+
+        // Promotions that don't capture are quiet moves, which only exist with a pawn one step from promoting.
+        let seventh_rank = match pos.side_to_move() {
+            Color::White => BB_RANK_7,
+            Color::Black => BB_RANK_2,
+        };
+        if !(pos.pawns(pos.side_to_move()) & seventh_rank).empty() {
+            let mut promotions = MoveVec::default();
+            gen.generate_quiets(pos, &mut promotions);
+            promotions.retain(|mov| mov.is_promotion());
+            gen.retain_legal(pos, &mut promotions);
+            moves.extend(promotions);
+        }
+
+        order_moves(pos, &mut moves, &[], &self.history);
+        for mov in moves {
+            let undo = pos.make_move(mov);
+            let score = -self.quiesce(pos, -beta, -alpha, ply + 1, qdepth + 1);
+            pos.unmake_move(mov, undo);
+            if score >= beta {
+                return beta;
+            }
+
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        alpha
+    }
+
+    /// Evaluates a position from the perspective of the side to move.
+    fn static_evaluation(&self, pos: &Position, ply: u32) -> Score {
         let value = self.searcher.evaluator.evaluate(pos);
-        match pos.side_to_move() {
+        let value = match pos.side_to_move() {
             Color::White => value,
             Color::Black => -value,
-        }
+        };
+
+        // The evaluator reports mates as occurring at this position, which is `ply` plies away from the root.
+        to_root_relative(value, ply)
     }
 
     fn consider_transposition(
@@ -144,6 +430,7 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
         alpha: &mut Score,
         beta: Score,
         depth: u32,
+        ply: u32,
     ) -> (Option<Move>, Option<Score>) {
         // The alpha-beta function in this searcher is designed to exploit the transposition table to take the best
         // known path through the game tree. The transposition table serves two purposes:
@@ -166,28 +453,29 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
             self.stats.tt_absolute_hit += 1;
             let hash_move = entry.best_move;
             if entry.depth >= depth && (hash_move.is_none() || pos.is_legal(hash_move.unwrap())) {
+                // Mate scores are stored relative to the node that they were found in, since the same position can
+                // be reached at different distances from the root.
                 match entry.node {
                     NodeKind::PrincipalVariation(score) => {
+                        let score = to_root_relative(score, ply);
                         // The last time we searched at this depth or greater, this move was a PV-node. This is the
                         // best case scenario; we know exactly what the score is. We don't have to search this subtree
                         // at all.
-                        debug!("exiting with score {} due to TT PV hit", score.step());
+                        debug!("exiting with score {} due to TT PV hit", score);
                         self.stats.tt_absolute_hit_pv += 1;
-                        return (hash_move, Some(score.step()));
+                        return (hash_move, Some(score));
                     }
                     NodeKind::Cut(score) => {
+                        let score = to_root_relative(score, ply);
                         // The last time we searched at this depth or greater, this move caused a beta cutoff. The score
                         // here is a lower-bound on the exact score of the node.
                         //
                         // If the lower bound is greater than beta, we don't need to search this node and can instead
                         // return beta.
                         if score >= beta {
-                            debug!(
-                                "exiting with score {} due to TT hit beta cutoff",
-                                beta.step()
-                            );
+                            debug!("exiting with score {} due to TT hit beta cutoff", beta);
                             self.stats.tt_absolute_hit_cut += 1;
-                            return (hash_move, Some(beta.step()));
+                            return (hash_move, Some(beta));
                         }
 
                         // If the lower bound is greater than alpha, bump up alpha to match.
@@ -200,18 +488,16 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
                         // Otherwise, we should search the hash move first - it'll probably cause a beta cutoff.
                     }
                     NodeKind::All(score) => {
+                        let score = to_root_relative(score, ply);
                         // The last time we searched at this depth or greater, we searched all children of this node and
                         // none of them improved alpha. The score here is an upper-bound on the exact score of the node.
                         //
                         // If the upper bound is worse than alpha, we're not going to find anything better if we search
                         // here.
                         if score <= *alpha {
-                            debug!(
-                                "exiting with score {} due to TT hit alpha cutoff",
-                                alpha.step()
-                            );
+                            debug!("exiting with score {} due to TT hit alpha cutoff", alpha);
                             self.stats.tt_absolute_hit_all += 1;
-                            return (hash_move, Some(alpha.step()));
+                            return (hash_move, Some(*alpha));
                         }
 
                         // Otherwise, we'll need to search everything, starting at the hash move.
@@ -229,24 +515,74 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
         (hash_move, None)
     }
 
-    fn alpha_beta(&mut self, pos: &Position, mut alpha: Score, beta: Score, depth: u32) -> Score {
+    fn alpha_beta(
+        &mut self,
+        pos: &mut Position,
+        alpha: Score,
+        beta: Score,
+        depth: u32,
+        ply: u32,
+    ) -> Score {
+        // A position that has occurred before, either earlier in the game or earlier on the path from the root, is a
+        // draw: whichever side benefits from the repetition can keep repeating it.
+        self.path.push(pos);
+        let score = if ply > 0 && self.is_repetition(pos) {
+            Score::Evaluated(0.0f32)
+        } else {
+            self.search_node(pos, alpha, beta, depth, ply)
+        };
+        self.path.pop();
+        score
+    }
+
+    fn search_node(
+        &mut self,
+        pos: &mut Position,
+        mut alpha: Score,
+        beta: Score,
+        depth: u32,
+        ply: u32,
+    ) -> Score {
         //debug!("{}", pos.as_fen());
         debug!("depth: {}", depth);
         debug!("alpha: {}", alpha);
         debug!("beta:  {}", beta);
         if depth == 0 {
             debug!("quiescing due to depth 0");
-            return self.quiesce(pos, alpha, beta);
+            return self.quiesce(pos, alpha, beta, ply, 0);
         }
 
+        // If we're at the root and winning, we don't want to repeat a position that we've already seen in this game;
+        // the static evaluator has no idea that repeating positions is how games get drawn, so left to its own devices
+        // the search is happy to shuffle pieces back and forth forever.
+        let avoid_repetition = ply == 0 && self.is_ahead(pos);
+
+        // The hash move at the root may well be one of the moves that we've been told not to consider.
+        let excluding_moves = ply == 0 && !self.excluded_root_moves.is_empty();
+
         // Consult the transposition table. Have we seen this position before and, if so, does it produce a cutoff?
         // If so, there's no need to continue processing this position.
-        let (mut hash_move, cutoff_score) =
-            self.consider_transposition(pos, &mut alpha, beta, depth);
+        //
+        // The transposition table doesn't know anything about the game history, so don't trust it at the root if we're
+        // trying to avoid repetitions.
+        let (mut hash_move, cutoff_score) = if avoid_repetition || excluding_moves {
+            (None, None)
+        } else {
+            self.consider_transposition(pos, &mut alpha, beta, depth, ply)
+        };
         if let Some(cutoff) = cutoff_score {
             return cutoff;
         }
 
+        if self.null_move_fails_high(pos, beta, depth, ply) {
+            self.stats.null_move_cutoffs += 1;
+            return beta;
+        }
+
+        if self.razor_fails_low(pos, alpha, beta, depth, ply) {
+            return alpha;
+        }
+
         // Even if we didn't get a cutoff from the transposition table, we can at least begin the search with
         // the hash move.
         //
@@ -262,15 +598,21 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
             debug!("inspecting hash move {} for cutoffs", hash_move);
             debug_assert!(pos.is_legal(hash_move));
             self.stats.hash_move_node += 1;
-            let mut hash_pos = pos.clone();
-            hash_pos.apply_move(hash_move);
-            let score = -self.alpha_beta(&hash_pos, -beta, -alpha, depth - 1);
+            let undo = pos.make_move(hash_move);
+            let score = -self.alpha_beta(pos, -beta, -alpha, depth - 1, ply + 1);
+            pos.unmake_move(hash_move, undo);
             if score >= beta {
-                self.searcher
-                    .ttable
-                    .record_cut(pos, hash_move, depth, score);
+                self.searcher.ttable.record_cut(
+                    pos,
+                    hash_move,
+                    depth,
+                    to_node_relative(score, ply),
+                );
                 self.stats.hash_move_beta_cutoff += 1;
-                return beta.step();
+                self.stats.first_move_cutoffs += 1;
+                self.record_killer(hash_move, ply);
+                self.record_history(hash_move, depth);
+                return beta;
             }
 
             if score > alpha {
@@ -280,9 +622,13 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
                     hash_move, score
                 );
                 self.stats.hash_move_improved_alpha += 1;
-                self.searcher
-                    .ttable
-                    .record_principal_variation(pos, hash_move, depth, score);
+                self.record_history(hash_move, depth);
+                self.searcher.ttable.record_principal_variation(
+                    pos,
+                    hash_move,
+                    depth,
+                    to_node_relative(score, ply),
+                );
                 alpha = score;
             }
         }
@@ -298,43 +644,95 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
         debug!("generating moves");
         let gen = MoveGenerator::new();
         let mut moves = MoveVec::default();
-        gen.generate_moves(pos, &mut moves);
-        moves.retain(|&mut m| pos.is_legal_given_pseudolegal(m));
+        gen.generate_legal_moves(pos, &mut moves);
+        if excluding_moves {
+            moves.retain(|m| !self.excluded_root_moves.contains(m));
+        }
         // Order our moves to favor good ones earlier.
-        order_moves(pos, &mut moves);
+        let killers = self.killers(ply);
+        order_moves(pos, &mut moves, &killers, &self.history);
+        if avoid_repetition {
+            // Moves that repeat a position go last, so that any move that makes progress wins ties with them.
+            moves.sort_by_key(|&mov| self.repeats_position(pos, mov));
+        }
         if moves.len() == 0 {
             // No legal moves available. Are we in check?
             let score = if pos.is_check(pos.side_to_move()) {
-                // We lost.
-                Score::Loss(0)
+                // We lost, `ply` plies away from the root. Counting the distance from the root means that the search
+                // will always prefer the shortest path to a mate and the longest path out of one.
+                Score::Loss(ply)
             } else {
                 // We've drawn.
                 Score::Evaluated(0.0f32)
             };
 
             //debug!("{} is checkmate or draw position", pos.as_fen());
-            self.searcher
-                .ttable
-                .record_principal_variation(pos, Move::null(), depth, score);
+            self.searcher.ttable.record_principal_variation(
+                pos,
+                Move::null(),
+                depth,
+                to_node_relative(score, ply),
+            );
             self.stats.pv_nodes += 1;
-            return score.step();
+            return score;
         }
 
-        for mov in moves {
-            let mut child = pos.clone();
-            child.apply_move(mov);
-            let score = -self.alpha_beta(&child, -beta, -alpha, depth - 1);
+        let in_check = pos.is_check(pos.side_to_move());
+        let futile = self.is_futile(pos, alpha, depth, ply, in_check);
+        for (i, mov) in moves.into_iter().enumerate() {
+            // At a futile node, quiet moves can't raise the score to alpha, so only the moves that change the material
+            // balance or give check are worth searching.
+            if futile && !mov.is_capture() && !mov.is_promotion() && !pos.gives_check(mov) {
+                continue;
+            }
+
+            let reduction = if in_check || alpha.is_mate() || killers.contains(&mov) {
+                0
+            } else {
+                self.late_move_reduction(pos, mov, i, depth)
+            };
+
+            let undo = pos.make_move(mov);
+            let mut score = None;
+            if reduction > 0 {
+                // Only bother searching a late move to the full depth if a shallower search says that it might be
+                // better than alpha.
+                let null_beta = alpha + Score::Evaluated(SCORE_GRANULARITY);
+                let reduced =
+                    self.search_child(pos, alpha, null_beta, depth - reduction, ply, false);
+                if reduced <= alpha {
+                    score = Some(reduced);
+                } else {
+                    self.stats.lmr_re_searches += 1;
+                }
+            }
+
+            let score = score.unwrap_or_else(|| {
+                self.search_child(pos, alpha, beta, depth, ply, i == 0 && hash_move.is_none())
+            });
+            pos.unmake_move(mov, undo);
             if score >= beta {
-                self.searcher.ttable.record_cut(pos, mov, depth, score);
+                self.searcher
+                    .ttable
+                    .record_cut(pos, mov, depth, to_node_relative(score, ply));
                 self.stats.cut_nodes += 1;
-                return beta.step();
+                if i == 0 && hash_move.is_none() {
+                    self.stats.first_move_cutoffs += 1;
+                }
+                self.record_killer(mov, ply);
+                self.record_history(mov, depth);
+                return beta;
             }
 
             if score > alpha {
                 improved_alpha = true;
-                self.searcher
-                    .ttable
-                    .record_principal_variation(pos, mov, depth, score);
+                self.record_history(mov, depth);
+                self.searcher.ttable.record_principal_variation(
+                    pos,
+                    mov,
+                    depth,
+                    to_node_relative(score, ply),
+                );
                 alpha = score;
             }
         }
@@ -342,38 +740,332 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
         if !improved_alpha {
             //debug!("recording {} as all node", pos.as_fen());
             self.stats.all_nodes += 1;
-            self.searcher.ttable.record_all(pos, depth, alpha);
+            self.searcher
+                .ttable
+                .record_all(pos, depth, to_node_relative(alpha, ply));
         } else {
             self.stats.pv_nodes += 1;
         }
 
-        alpha.step()
+        alpha
+    }
+
+    /// Searches the position after one of the moves of a node, returning the score of the move from the perspective of
+    /// the side that made it.
+    ///
+    /// This is a principal variation search. Move ordering usually puts the best move first, so after that move is
+    /// searched with the full window, the rest are only searched hard enough to prove that they're no better than
+    /// alpha, with a window that leaves no room between alpha and beta. Searching with a window like that is much
+    /// cheaper, and the rare move that turns out better than alpha is searched again with the full window to find its
+    /// score.
+    fn search_child(
+        &mut self,
+        pos: &mut Position,
+        alpha: Score,
+        beta: Score,
+        depth: u32,
+        ply: u32,
+        first_move: bool,
+    ) -> Score {
+        // Mate scores don't change when nudged, so there's no such thing as a window that's just above a mate score.
+        if first_move || alpha.is_mate() || !self.searcher.params.principal_variation_search {
+            return -self.alpha_beta(pos, -beta, -alpha, depth - 1, ply + 1);
+        }
+
+        let null_beta = alpha + Score::Evaluated(SCORE_GRANULARITY);
+        let score = -self.alpha_beta(pos, -null_beta, -alpha, depth - 1, ply + 1);
+        if score > alpha && score < beta {
+            self.stats.pvs_re_searches += 1;
+            -self.alpha_beta(pos, -beta, -alpha, depth - 1, ply + 1)
+        } else {
+            score
+        }
+    }
+
+    /// The number of plies by which to reduce the search of the given move, which is the `index`th move searched at a
+    /// node of the given depth, or zero if it shouldn't be reduced.
+    ///
+    /// Move ordering puts the moves that are most likely to be good first, so the moves at the end of the list are
+    /// rarely any good. Those moves get a shallower search, the later in the list the shallower, unless they're
+    /// captures, promotions, or checks, which are too forcing to judge that way.
+    fn late_move_reduction(&self, pos: &Position, mov: Move, index: usize, depth: u32) -> u32 {
+        let reduction = match self.searcher.params.late_move_reduction {
+            Some(reduction) => reduction,
+            None => return 0,
+        };
+
+        if index < LMR_MIN_MOVES
+            || depth < 3
+            || mov.is_capture()
+            || mov.is_promotion()
+            || pos.gives_check(mov)
+        {
+            return 0;
+        }
+
+        let reduction = if index >= LMR_DEEP_MOVES {
+            reduction + 1
+        } else {
+            reduction
+        };
+
+        // Reduced searches still search at least one ply before quiescence search takes over.
+        reduction.min(depth - 2)
     }
 
     fn search(&mut self, pos: &Position, recorder: &dyn DataRecorder) -> SearchResult {
-        let mut current_best_move = Move::null();
-        let mut current_best_score = Score::Loss(0);
+        let mut best = SearchResult {
+            best_move: Move::null(),
+            score: Score::Loss(0),
+            nodes_searched: 0,
+            pv: vec![],
+        };
         for depth in 1..=self.max_depth {
+            // There's no point in starting a depth that we won't have time to finish, but we need to finish at
+            // least one to have a move to play at all.
+            if depth > 1 && self.past_soft_limit() {
+                debug!("not starting search of depth {} due to soft limit", depth);
+                break;
+            }
+
             debug!("beginning search of depth {}", depth);
-            let result = self.search_depth(pos, depth, recorder);
-            if self.out_of_time() {
-                return SearchResult {
-                    best_move: current_best_move,
-                    score: current_best_score,
-                    nodes_searched: self.stats.nodes,
-                };
+            let previous_score = if depth > 1 { Some(best.score) } else { None };
+            let result = self.search_depth(pos, depth, previous_score, recorder);
+            if depth > 1 && self.out_of_time() {
+                break;
             }
 
-            current_best_move = result.best_move;
-            current_best_score = result.score;
-            info!("pv ({}): {:?}", current_best_score, self.get_pv(pos, depth));
+            best = result;
+            self.observer.depth_completed(&SearchInfo {
+                depth,
+                score: best.score,
+                nodes: self.depth_nodes.iter().sum(),
+                elapsed: self.start_time.elapsed(),
+                pv: &best.pv,
+            });
+            info!("pv ({}): {:?}", best.score, best.pv);
+            if let Some(branching_factor) = self.stats.branching_factor {
+                info!("effective branching factor: {:.2}", branching_factor);
+            }
         }
 
-        SearchResult {
-            best_move: current_best_move,
-            score: current_best_score,
-            nodes_searched: self.stats.nodes,
+        best.nodes_searched = self.stats.nodes;
+        best
+    }
+
+    /// Verifies the move that the search chose with a search of the given depth of the position after it. Pruning can
+    /// hide tactical refutations from the main search; if the verification search finds one that makes the move look
+    /// much worse than the main search thought, searches again without that move and plays the alternative if it's
+    /// better than the refuted move.
+    ///
+    /// The alternative may well be thrown away, so its search isn't reported to the recorder or the observer.
+    fn check_for_blunder(
+        &mut self,
+        pos: &Position,
+        result: SearchResult,
+        depth: u32,
+    ) -> SearchResult {
+        if result.best_move.is_null() || result.score.is_mate() {
+            return result;
+        }
+
+        let mut child = pos.clone();
+        child.apply_move(result.best_move);
+        self.path.push(pos);
+        let verified = -self.alpha_beta(&mut child, Score::Loss(0), Score::Win(0), depth, 1);
+        self.path.pop();
+        let margin = Score::Evaluated(-self.searcher.params.blunder_margin);
+        if verified >= result.score + margin {
+            return result;
+        }
+
+        info!(
+            "blunder check: {} scores {} on verification, not {}",
+            result.best_move, verified, result.score
+        );
+
+        // There's nothing to do about it if it's the only move.
+        let other_moves = pos.legal_moves().iter().any(|&mov| mov != result.best_move);
+        if !other_moves {
+            return result;
+        }
+
+        self.depth_nodes.clear();
+        self.stats = Default::default();
+        let observer = mem::replace(&mut self.observer, &NullSearchObserver);
+        self.excluded_root_moves.push(result.best_move);
+        let alternative = self.search(pos, &NullDataRecorder);
+        self.excluded_root_moves.clear();
+        self.observer = observer;
+        if !alternative.best_move.is_null() && alternative.score > verified {
+            alternative
+        } else {
+            result
+        }
+    }
+
+    /// Tries razoring at the given node. When the static evaluation of a node just above the horizon is so far below
+    /// alpha that no quiet move is likely to make up the difference, only a capture could save the node, and
+    /// quiescence search looks at all of those. If quiescence search fails low too, the node fails low without
+    /// searching any of its moves.
+    fn razor_fails_low(
+        &mut self,
+        pos: &mut Position,
+        alpha: Score,
+        beta: Score,
+        depth: u32,
+        ply: u32,
+    ) -> bool {
+        let margin = match self.searcher.params.razor_margin {
+            Some(margin) => margin,
+            None => return false,
+        };
+
+        if ply == 0 || depth > 2 || alpha.is_mate() || pos.is_check(pos.side_to_move()) {
+            return false;
+        }
+
+        let static_score = self.static_evaluation(pos, ply);
+        if static_score.is_mate() || static_score + Score::Evaluated(margin) > alpha {
+            return false;
+        }
+
+        self.quiesce(pos, alpha, beta, ply, 0) <= alpha
+    }
+
+    /// Returns whether the given node is a frontier node, one ply above the horizon, whose static evaluation is far
+    /// enough below alpha that no quiet move is likely to raise it above alpha. Quiet moves can be skipped at such a
+    /// node. Nodes in check are never futile, since every move there is an evasion.
+    fn is_futile(
+        &self,
+        pos: &Position,
+        alpha: Score,
+        depth: u32,
+        ply: u32,
+        in_check: bool,
+    ) -> bool {
+        let margin = match self.searcher.params.futility_margin {
+            Some(margin) => margin,
+            None => return false,
+        };
+
+        if ply == 0 || depth != 1 || in_check || alpha.is_mate() {
+            return false;
+        }
+
+        let static_score = self.static_evaluation(pos, ply);
+        !static_score.is_mate() && static_score + Score::Evaluated(margin) <= alpha
+    }
+
+    /// Tries null-move pruning at the given node. If the side to move can pass and still do well enough that a
+    /// reduced-depth search fails high, a real move would almost certainly do at least as well, so the node can be cut
+    /// without searching any of its moves.
+    ///
+    /// Passing is illegal when in check, and it's a poor stand-in for a real move in zugzwang, when every real move
+    /// makes things worse. Zugzwang mostly happens in endgames where the side to move has nothing but pawns left, so
+    /// null moves aren't tried there.
+    fn null_move_fails_high(
+        &mut self,
+        pos: &mut Position,
+        beta: Score,
+        depth: u32,
+        ply: u32,
+    ) -> bool {
+        let reduction = match self.searcher.params.null_move_reduction {
+            Some(reduction) => reduction,
+            None => return false,
+        };
+
+        // Two null moves in a row would just search the same position again at a lower depth.
+        if ply == 0 || depth < 3 || self.null_move_ply == Some(ply - 1) {
+            return false;
+        }
+
+        let side = pos.side_to_move();
+        let non_pawn_material =
+            pos.knights(side) | pos.bishops(side) | pos.rooks(side) | pos.queens(side);
+        if beta.is_mate() || pos.is_check(side) || non_pawn_material.empty() {
+            return false;
+        }
+
+        let saved_null_move_ply = self.null_move_ply.replace(ply);
+        let undo = pos.make_move(Move::null());
+        let null_depth = depth.saturating_sub(1 + reduction);
+        // The search only needs to find out whether the score is at least beta, so it can search with a window that
+        // leaves no room for anything in between.
+        let null_beta = -beta + Score::Evaluated(SCORE_GRANULARITY);
+        let score = -self.alpha_beta(pos, -beta, null_beta, null_depth, ply + 1);
+        pos.unmake_move(Move::null(), undo);
+        self.null_move_ply = saved_null_move_ply;
+        score >= beta
+    }
+
+    /// Returns whether or not the side to move is ahead in the given position, according to the static evaluator.
+    fn is_ahead(&self, pos: &Position) -> bool {
+        if self.searcher.game_history.is_empty() {
+            return false;
+        }
+
+        let value = self.searcher.evaluator.evaluate(pos);
+        let value = match pos.side_to_move() {
+            Color::White => value,
+            Color::Black => -value,
+        };
+
+        value > Score::Evaluated(self.searcher.params.repetition_margin)
+    }
+
+    /// The killer moves for nodes `ply` plies from the root.
+    fn killers(&self, ply: u32) -> [Move; 2] {
+        if !self.searcher.params.killer_moves {
+            return [Move::null(); 2];
+        }
+
+        self.killers
+            .get(ply as usize)
+            .copied()
+            .unwrap_or([Move::null(); 2])
+    }
+
+    /// Records a move that caused a beta cutoff `ply` plies from the root as a killer move, if it's a quiet move.
+    /// Captures and promotions are already ordered early without any help.
+    fn record_killer(&mut self, mov: Move, ply: u32) {
+        if mov.is_capture() || mov.is_promotion() {
+            return;
+        }
+
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [Move::null(); 2]);
+        }
+
+        let slots = &mut self.killers[ply];
+        if slots[0] != mov {
+            slots[1] = slots[0];
+            slots[0] = mov;
+        }
+    }
+
+    /// Credits a move that caused a beta cutoff or improved alpha in a search of the given depth, if it's a quiet move.
+    fn record_history(&mut self, mov: Move, depth: u32) {
+        if mov.is_capture() || mov.is_promotion() {
+            return;
         }
+
+        let score = &mut self.history[mov.source().as_index()][mov.destination().as_index()];
+        *score = score.saturating_add(depth * depth);
+    }
+
+    /// Returns whether or not the given position has already occurred in the game or on the path from the root.
+    fn is_repetition(&self, pos: &Position) -> bool {
+        is_repetition(&self.path, 2) || self.searcher.game_history.contains(&pos.zobrist_hash())
+    }
+
+    /// Returns whether or not making the given move produces a position that has already occurred in the game.
+    fn repeats_position(&self, pos: &Position, mov: Move) -> bool {
+        let mut child = pos.clone();
+        child.apply_move(mov);
+        self.searcher.game_history.contains(&child.zobrist_hash())
     }
 
     fn get_pv(&self, pos: &Position, depth: u32) -> Vec<Move> {
@@ -384,7 +1076,10 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
                 .searcher
                 .ttable
                 .query(&pv_clone, |e| e.and_then(|e| e.best_move));
-            if let Some(best_move) = best_move {
+            // Terminal positions record a null best move, and hash collisions can produce illegal ones.
+            if let Some(best_move) =
+                best_move.filter(|&mov| !mov.is_null() && pv_clone.is_legal(mov))
+            {
                 pv.push(best_move);
                 pv_clone.apply_move(best_move);
             } else {
@@ -395,8 +1090,19 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
         pv
     }
 
+    /// Returns whether or not the hard time limit has passed, in which case the search must stop immediately. The first
+    /// depth always runs to completion no matter how little time there is, since without it there's no move to play.
     fn out_of_time(&self) -> bool {
-        if let Some(budget) = self.time_budget {
+        !self.depth_nodes.is_empty() && self.past_limit(self.hard_limit)
+    }
+
+    /// Returns whether or not the soft time limit has passed, in which case the search shouldn't start another depth.
+    fn past_soft_limit(&self) -> bool {
+        self.past_limit(self.soft_limit)
+    }
+
+    fn past_limit(&self, limit: Option<Duration>) -> bool {
+        if let Some(budget) = limit {
             let start = self.start_time;
             let now = Instant::now();
             now - start > budget
@@ -406,6 +1112,33 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
     }
 }
 
+/// The number of moves at each node that are always searched to the full depth.
+const LMR_MIN_MOVES: usize = 4;
+
+/// The number of moves at each node after which late move reductions reduce the search by an extra ply.
+const LMR_DEEP_MOVES: usize = 12;
+
+/// The smallest difference between evaluated scores that the transposition table can tell apart.
+const SCORE_GRANULARITY: f32 = 0.01;
+
+/// Converts a mate score relative to a node `ply` plies from the root into one relative to the root.
+fn to_root_relative(score: Score, ply: u32) -> Score {
+    match score {
+        Score::Win(plies) => Score::Win(plies + ply),
+        Score::Loss(plies) => Score::Loss(plies + ply),
+        s => s,
+    }
+}
+
+/// Converts a mate score relative to the root into one relative to a node `ply` plies from the root.
+fn to_node_relative(score: Score, ply: u32) -> Score {
+    match score {
+        Score::Win(plies) => Score::Win(plies.saturating_sub(ply)),
+        Score::Loss(plies) => Score::Loss(plies.saturating_sub(ply)),
+        s => s,
+    }
+}
+
 /// Performs move ordering for a list of legal moves from a given position. Move ordering is crucial
 /// for alpha-beta search. It is our best defense against combinatorial explosion of the state space
 /// of chess.
@@ -413,13 +1146,28 @@ impl<'a, E: BoardEvaluator> IterativeSearch<'a, E> {
 /// This function heuristically orders all moves in order of how good they appear to be, without searching
 /// the tree of moves directly.
 ///
-/// Note that the hash move is not included here, since the searcher handles that already.
-fn order_moves(pos: &Position, moves: &mut [Move]) {
+/// Note that the hash move is not included here, since the searcher handles that already. The killer moves, on the
+/// other hand, are: quiet moves that refuted other positions at the same ply are likely to refute this one too, so
+/// they're tried right after the captures and promotions that look good. Quiet moves that otherwise look alike are
+/// ordered by their history scores, so that moves that have worked elsewhere in the tree are tried first.
+fn order_moves(pos: &Position, moves: &mut [Move], killers: &[Move], history: &HistoryTable) {
     // For the purposes of move ordering, we derive a total order of moves by ranking them
     // by their static exchange scores. Static exchange generally refers to captures, but for move
     // ordering we'll also consider promotions to count for a score.
     //
     // We'll drive a move score for every move and use that as the sorting key.
+    //
+    // Queen promotions are nearly always best. The underpromotions still go ahead of the quiet
+    // moves, since they're sometimes the only way to avoid stalemate, and knight promotions come
+    // first among them because they can give check or fork where a queen can't.
+    fn promotion_score(kind: PieceKind) -> i32 {
+        match kind {
+            PieceKind::Queen => kind.value() - 1,
+            PieceKind::Knight => 2,
+            _ => 1,
+        }
+    }
+
     fn move_score(pos: &Position, mov: Move) -> i32 {
         match mov {
             // En-passant is an annoying edge case in everything, SEE is no exception. Put it before
@@ -427,16 +1175,37 @@ fn order_moves(pos: &Position, moves: &mut [Move]) {
             mov if mov.is_en_passant() => 1,
             // TODO(swgillespie) - This probably overestimates the value of promotion captures...
             mov if mov.is_capture() && mov.is_promotion() => {
-                mov.promotion_piece().value() - 1
+                promotion_score(mov.promotion_piece())
                     + static_exchange_evaluation(pos, mov.destination())
             }
             mov if mov.is_capture() => static_exchange_evaluation(pos, mov.destination()),
-            mov if mov.is_promotion() => mov.promotion_piece().value() - 1,
+            mov if mov.is_promotion() => promotion_score(mov.promotion_piece()),
             _ => 0,
         }
     }
 
-    moves.sort_by_cached_key(|&mov| -move_score(pos, mov));
+    moves.sort_by_cached_key(|&mov| {
+        let score = move_score(pos, mov);
+        // Checks are forcing, so they're worth trying before the other moves, but after the winning
+        // captures and the killers, which are more likely to cause a cutoff.
+        let group = if (mov.is_capture() || mov.is_promotion()) && score > 0 {
+            0
+        } else if killers.contains(&mov) {
+            1
+        } else if pos.gives_check(mov) {
+            2
+        } else {
+            3
+        };
+
+        let history_score = if mov.is_capture() || mov.is_promotion() {
+            0
+        } else {
+            history[mov.source().as_index()][mov.destination().as_index()]
+        };
+
+        (group, -score, Reverse(history_score))
+    });
 }
 
 fn static_exchange_evaluation(pos: &Position, target: Square) -> i32 {
@@ -444,7 +1213,14 @@ fn static_exchange_evaluation(pos: &Position, target: Square) -> i32 {
     if let Some(attacker) = smallest_attacker(pos, target) {
         let target_piece = pos.piece_at(target).unwrap();
         let mut child = pos.clone();
-        let mov = Move::capture(attacker, target);
+        // Pawns capturing onto the last rank have to promote.
+        let promotes = pos.piece_at(attacker).unwrap().kind == PieceKind::Pawn
+            && (target.rank() == Rank::One || target.rank() == Rank::Eight);
+        let mov = if promotes {
+            Move::promotion_capture(attacker, target, PieceKind::Queen)
+        } else {
+            Move::capture(attacker, target)
+        };
         child.apply_move(mov);
         value = target_piece.kind.value() - static_exchange_evaluation(&child, target);
     }
@@ -452,32 +1228,50 @@ fn static_exchange_evaluation(pos: &Position, target: Square) -> i32 {
     value
 }
 
-fn smallest_attacker(pos: &Position, target: Square) -> Option<Square> {
-    let attackers = pos.squares_attacking(pos.side_to_move(), target);
-    if attackers.empty() {
-        return None;
-    }
+/// Returns whether or not the static exchange that begins with the given capture yields at least
+/// `threshold` for the side making the capture. Unlike `static_exchange_evaluation`, the exchange
+/// begins with the given move rather than with the smallest attacker, and the opponent is free to
+/// stop recapturing once doing so would lose material.
+fn see_ge(pos: &Position, mov: Move, threshold: i32) -> bool {
+    debug_assert!(mov.is_capture());
+    let captured_value = pos
+        .captured_piece(mov)
+        .map(|piece| piece.kind.value())
+        .unwrap_or(0);
 
-    let mut values: Vec<(Square, PieceKind)> = attackers
-        .into_iter()
-        .map(|sq| (sq, pos.piece_at(sq).unwrap().kind))
-        .collect();
+    let mut child = pos.clone();
+    child.apply_move(mov);
+    let recapture_value = static_exchange_evaluation(&child, mov.destination()).max(0);
+    captured_value - recapture_value >= threshold
+}
+
+/// Filters a list of captures down to those that do not lose material according to static
+/// exchange evaluation. This is the set of moves worth considering in a quiescence search.
+fn filter_good_captures(pos: &Position, moves: &mut MoveVec) {
+    moves.retain(|&mut mov| see_ge(pos, mov, 0));
+}
 
-    values.sort_by_key(|(_, kind)| kind.value());
-    return values.first().map(|(sq, _)| sq).cloned();
+fn smallest_attacker(pos: &Position, target: Square) -> Option<Square> {
+    pos.least_valuable_attacker(pos.side_to_move(), target, pos.occupied())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::eval::ShannonEvaluator;
+    use crate::eval::{Score, ShannonEvaluator};
     use crate::move_generator::{MoveGenerator, MoveVec};
     use crate::moves::Move;
     use crate::position::Position;
-    use crate::search::NullDataRecorder;
-    use crate::types::Square;
+    use std::cell::RefCell;
+    use std::time::Duration;
 
-    use super::Searcher;
-    use super::{order_moves, static_exchange_evaluation};
+    use crate::search::{
+        DataRecorder, NullDataRecorder, NullSearchObserver, Record, SearchInfo, SearchObserver,
+        SearchParams,
+    };
+    use crate::types::{PieceKind, Square};
+
+    use super::{filter_good_captures, order_moves, see_ge, static_exchange_evaluation};
+    use super::{IterativeSearch, Searcher};
 
     #[test]
     // Test to ensure that we don't regress our alpha-beta prune too badly.
@@ -486,6 +1280,447 @@ mod tests {
         let mut search: Searcher<ShannonEvaluator> = Default::default();
         let result = search.search(&pos, 2, None, &NullDataRecorder);
         assert!(result.nodes_searched <= 80);
+
+        // The default parameters should leave the search exactly as it was without them.
+        let mut with_params: Searcher<ShannonEvaluator> = Default::default();
+        with_params.set_params(SearchParams::default());
+        let with_params = with_params.search(&pos, 2, None, &NullDataRecorder);
+        assert_eq!(result.nodes_searched, with_params.nodes_searched);
+    }
+
+    #[test]
+    fn soft_limit_finishes_depth() {
+        struct DepthRecorder {
+            depths: RefCell<Vec<u32>>,
+        }
+
+        impl DataRecorder for DepthRecorder {
+            fn record(&self, _pos: &Position, rec: &Record) {
+                self.depths.borrow_mut().push(rec.depth);
+            }
+        }
+
+        // The soft limit has passed by the time the first depth completes, so that depth gets
+        // finished and no others are started.
+        let pos = Position::from_start_position();
+        let recorder = DepthRecorder {
+            depths: RefCell::new(vec![]),
+        };
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        let result =
+            search.search_with_limits(&pos, 4, Some(Duration::from_nanos(1)), None, &recorder);
+        assert_eq!(vec![1], *recorder.depths.borrow());
+        assert!(pos.is_legal(result.best_move));
+    }
+
+    #[test]
+    fn hard_limit_aborts_aspiration_search() {
+        // The hard limit passes as soon as the search starts, so every depth after the first is aborted, some of them in
+        // the middle of an aspiration re-search.
+        let pos = Position::from_start_position();
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        search.set_params(SearchParams {
+            aspiration_delta: Some(0.01),
+            ..Default::default()
+        });
+        let result = search.search_with_limits(
+            &pos,
+            4,
+            None,
+            Some(Duration::from_nanos(1)),
+            &NullDataRecorder,
+        );
+        assert!(pos.is_legal(result.best_move));
+    }
+
+    #[test]
+    fn observer_sees_each_depth() {
+        struct DepthEvent {
+            depth: u32,
+            score: Score,
+            nodes: u64,
+            pv: Vec<Move>,
+        }
+
+        struct DepthObserver {
+            events: RefCell<Vec<DepthEvent>>,
+        }
+
+        impl SearchObserver for DepthObserver {
+            fn depth_completed(&self, info: &SearchInfo) {
+                self.events.borrow_mut().push(DepthEvent {
+                    depth: info.depth,
+                    score: info.score,
+                    nodes: info.nodes,
+                    pv: info.pv.to_vec(),
+                });
+            }
+        }
+
+        let pos = Position::from_start_position();
+        let observer = DepthObserver {
+            events: RefCell::new(vec![]),
+        };
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        let result = search.search_observed(&pos, 3, None, None, &NullDataRecorder, &observer);
+        let events = observer.events.borrow();
+        let depths: Vec<u32> = events.iter().map(|event| event.depth).collect();
+        assert_eq!(vec![1, 2, 3], depths);
+
+        // Node counts add up over the depths, and the last depth is the result of the search.
+        assert!(events.windows(2).all(|pair| pair[0].nodes < pair[1].nodes));
+        let last = events.last().unwrap();
+        assert_eq!(result.score, last.score);
+        assert_eq!(result.pv, last.pv);
+        assert_eq!(result.best_move, last.pv[0]);
+    }
+
+    #[test]
+    fn quiescence_depth_is_capped() {
+        struct QuiescenceRecorder {
+            depth: RefCell<u32>,
+        }
+
+        impl DataRecorder for QuiescenceRecorder {
+            fn record(&self, _pos: &Position, rec: &Record) {
+                let mut depth = self.depth.borrow_mut();
+                *depth = (*depth).max(rec.quiescence_depth);
+            }
+        }
+
+        // Both sides have piled up attackers and defenders on d4, so there's a long chain of
+        // captures available below the horizon.
+        let pos = Position::from_fen("1k1r4/3r4/2nq4/8/3p4/4PN2/3R4/1K1Q3R w - - 0 1").unwrap();
+        let recorder = QuiescenceRecorder {
+            depth: RefCell::new(0),
+        };
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        search.set_params(SearchParams {
+            qsearch_max_depth: 2,
+            ..Default::default()
+        });
+        let result = search.search(&pos, 2, None, &recorder);
+        assert!(pos.is_legal(result.best_move));
+        assert_eq!(2, *recorder.depth.borrow());
+    }
+
+    #[test]
+    fn quiescence_sees_hanging_queen() {
+        // White's knight can take Black's undefended queen.
+        let mut pos = Position::from_fen("4k3/4p3/8/3q4/8/4N3/4P3/4K3 w - - 0 1").unwrap();
+        let searcher: Searcher<ShannonEvaluator> = Default::default();
+        let mut search = IterativeSearch::new(&searcher, 0, None, None, &NullSearchObserver);
+        let static_score = search.static_evaluation(&pos, 0);
+        let score = search.alpha_beta(&mut pos, Score::Loss(0), Score::Win(0), 0, 0);
+        assert!(static_score < Score::Evaluated(0.0));
+        assert!(score > Score::Evaluated(0.0), "{}", score);
+        assert_eq!("4k3/4p3/8/3q4/8/4N3/4P3/4K3 w - - 0 1", pos.as_fen());
+    }
+
+    #[test]
+    fn effective_branching_factor() {
+        struct BranchingRecorder {
+            factors: RefCell<Vec<Option<f64>>>,
+        }
+
+        impl DataRecorder for BranchingRecorder {
+            fn record(&self, _pos: &Position, rec: &Record) {
+                self.factors.borrow_mut().push(rec.branching_factor);
+            }
+        }
+
+        let pos = Position::from_start_position();
+        let recorder = BranchingRecorder {
+            factors: RefCell::new(vec![]),
+        };
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        search.search(&pos, 2, None, &recorder);
+        let factors = recorder.factors.borrow();
+        assert_eq!(2, factors.len());
+        assert_eq!(None, factors[0]);
+        let factor = factors[1].unwrap();
+        assert!(factor.is_finite() && factor > 0.0, "{}", factor);
+    }
+
+    #[test]
+    fn first_move_cutoff_rate() {
+        struct CutoffRecorder {
+            record: RefCell<Record>,
+        }
+
+        impl DataRecorder for CutoffRecorder {
+            fn record(&self, _pos: &Position, rec: &Record) {
+                *self.record.borrow_mut() = rec.clone();
+            }
+        }
+
+        // A handful of tactical positions, each with a capture or a mate that refutes most moves.
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        ];
+        for fen in &fens {
+            let pos = Position::from_fen(fen).unwrap();
+            let recorder = CutoffRecorder {
+                record: RefCell::new(Default::default()),
+            };
+            let mut search: Searcher<ShannonEvaluator> = Default::default();
+            search.search(&pos, 3, None, &recorder);
+            let rate = recorder.record.borrow().first_move_cutoff_rate();
+            assert!(rate > 0.85, "first move cutoff rate {} for {}", rate, fen);
+        }
+    }
+
+    #[test]
+    fn ponder_move() {
+        let pos = Position::from_start_position();
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        let result = search.search(&pos, 3, None, &NullDataRecorder);
+        assert_eq!(Some(&result.best_move), result.pv.first());
+        let ponder = result.ponder_move().unwrap();
+        let mut child = pos.clone();
+        child.apply_move(result.best_move);
+        assert!(child.is_legal(ponder));
+    }
+
+    #[test]
+    fn multipv_finds_both_captures() {
+        // Either pawn can capture the knight, and every other move lets it get away.
+        let pos = Position::from_fen("4k3/8/8/8/4n3/3P1P2/8/4K3 w - - 0 1").unwrap();
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        let results = search.search_multipv(&pos, 3, 3);
+        assert_eq!(3, results.len());
+
+        let mut captures: Vec<Move> = results[..2].iter().map(|r| r.best_move).collect();
+        captures.sort_by_key(|mov| mov.as_uci());
+        assert_eq!(
+            vec![
+                Move::capture(Square::D3, Square::E4),
+                Move::capture(Square::F3, Square::E4)
+            ],
+            captures
+        );
+
+        for result in &results {
+            assert_eq!(Some(&result.best_move), result.pv.first());
+        }
+
+        assert!(results[1].score <= results[0].score);
+        assert!(results[2].score < results[1].score);
+        match (results[0].score, results[1].score) {
+            (Score::Evaluated(first), Score::Evaluated(second)) => {
+                assert!(second > 0.0, "{}", second);
+                assert!(first - second < 1.0, "{} and {}", first, second);
+            }
+            scores => panic!("unexpected scores {:?}", scores),
+        }
+    }
+
+    #[test]
+    fn multipv_with_few_moves() {
+        // The king has only two legal moves.
+        let pos = Position::from_fen("7k/8/5K2/8/8/8/8/6R1 b - - 0 1").unwrap();
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        let results = search.search_multipv(&pos, 2, 5);
+        assert_eq!(pos.legal_moves().len(), results.len());
+    }
+
+    #[test]
+    fn no_ponder_move_after_mate() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        let result = search.search(&pos, 3, None, &NullDataRecorder);
+        assert_eq!(vec![Move::quiet(Square::A1, Square::A8)], result.pv);
+        assert_eq!(None, result.ponder_move());
+    }
+
+    // Deeper searches lean on the killer and history heuristics to order the quiet moves.
+    #[test]
+    fn opening_position_prune_depth_4() {
+        let pos = Position::from_start_position();
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        let result = search.search(&pos, 4, None, &NullDataRecorder);
+        assert!(result.nodes_searched <= 1300, "{}", result.nodes_searched);
+    }
+
+    // Principal variation search proves most moves worse than the first one without finding their exact scores.
+    #[test]
+    fn opening_position_prune_depth_5() {
+        let pos = Position::from_start_position();
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        let result = search.search(&pos, 5, None, &NullDataRecorder);
+        assert!(result.nodes_searched <= 14000, "{}", result.nodes_searched);
+
+        let mut full_window: Searcher<ShannonEvaluator> = Default::default();
+        full_window.set_params(SearchParams {
+            principal_variation_search: false,
+            ..Default::default()
+        });
+        let full_window = full_window.search(&pos, 5, None, &NullDataRecorder);
+        assert!(
+            result.nodes_searched < full_window.nodes_searched,
+            "{} nodes with PVS, {} without",
+            result.nodes_searched,
+            full_window.nodes_searched
+        );
+        assert_eq!(full_window.best_move, result.best_move);
+        assert_eq!(full_window.score, result.score);
+    }
+
+    #[test]
+    fn avoids_repetition_when_ahead() {
+        // White is up a queen, so any move at all is winning.
+        let pos = Position::from_fen("7k/8/8/8/8/8/1Q6/K7 w - - 0 1").unwrap();
+        let mut naive: Searcher<ShannonEvaluator> = Default::default();
+        let naive_move = naive.search(&pos, 2, None, &NullDataRecorder).best_move;
+
+        // Pretend that the position that the naive search wants to move into already occurred earlier in the
+        // game. Knowing that, the searcher should pick something else.
+        let mut repeated = pos.clone();
+        repeated.apply_move(naive_move);
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        search.set_game_history(vec![repeated.zobrist_hash()]);
+        let best_move = search.search(&pos, 2, None, &NullDataRecorder).best_move;
+        assert_ne!(naive_move, best_move);
+
+        let mut child = pos.clone();
+        child.apply_move(best_move);
+        assert_ne!(repeated.zobrist_hash(), child.zobrist_hash());
+    }
+
+    #[test]
+    fn repetition_is_a_draw_when_behind() {
+        // Black is down a rook. Ka3 walks back into a position from earlier in the game, which is a draw and so much
+        // better than anything else Black can do.
+        let pos = Position::from_fen("8/8/8/8/8/8/k7/2K3R1 b - - 0 1").unwrap();
+        let ka3 = Move::quiet(Square::A2, Square::A3);
+        let mut repeated = pos.clone();
+        repeated.apply_move(ka3);
+
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        search.set_game_history(vec![repeated.zobrist_hash()]);
+        let result = search.search(&pos, 3, None, &NullDataRecorder);
+        assert_eq!(ka3, result.best_move);
+        assert_eq!(Score::Evaluated(0.0), result.score);
+    }
+
+    #[test]
+    fn perpetual_check_is_a_draw() {
+        // White is down two rooks, but Qe8+ Kh7 Qh5+ Kg8 repeats this position with Black's king unable to escape the
+        // checks.
+        let pos = Position::from_fen("6k1/6p1/8/7Q/8/7K/rr6/q7 w - - 0 1").unwrap();
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        let result = search.search(&pos, 4, None, &NullDataRecorder);
+        assert_eq!(Move::quiet(Square::H5, Square::E8), result.best_move);
+        assert_eq!(Score::Evaluated(0.0), result.score);
+    }
+
+    #[test]
+    fn blunder_check() {
+        // At depth 1 and without a quiescence search, the search can't see past Qxd5 to the recapture exd5, so it grabs
+        // the pawn and loses the queen.
+        let pos = Position::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let blunder = Move::capture(Square::D1, Square::D5);
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        search.set_params(SearchParams {
+            qsearch_max_depth: 0,
+            ..Default::default()
+        });
+        assert_eq!(
+            blunder,
+            search.search(&pos, 1, None, &NullDataRecorder).best_move
+        );
+
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        search.set_params(SearchParams {
+            blunder_check_depth: Some(1),
+            qsearch_max_depth: 0,
+            ..Default::default()
+        });
+        let result = search.search(&pos, 1, None, &NullDataRecorder);
+        assert_ne!(blunder, result.best_move);
+        assert!(pos.is_legal(result.best_move));
+    }
+
+    #[test]
+    fn blunder_check_alternative_is_not_reported() {
+        struct CountingRecorder {
+            records: RefCell<u32>,
+        }
+
+        impl DataRecorder for CountingRecorder {
+            fn record(&self, _pos: &Position, _rec: &Record) {
+                *self.records.borrow_mut() += 1;
+            }
+        }
+
+        struct CountingObserver {
+            depths: RefCell<u32>,
+        }
+
+        impl SearchObserver for CountingObserver {
+            fn depth_completed(&self, _info: &SearchInfo) {
+                *self.depths.borrow_mut() += 1;
+            }
+        }
+
+        // The same position as `blunder_check`, where the search has to look for an alternative to Qxd5.
+        let pos = Position::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let recorder = CountingRecorder {
+            records: RefCell::new(0),
+        };
+        let observer = CountingObserver {
+            depths: RefCell::new(0),
+        };
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        search.set_params(SearchParams {
+            blunder_check_depth: Some(1),
+            qsearch_max_depth: 0,
+            ..Default::default()
+        });
+        let result = search.search_observed(&pos, 1, None, None, &recorder, &observer);
+        assert_ne!(Move::capture(Square::D1, Square::D5), result.best_move);
+        assert_eq!(1, *recorder.records.borrow());
+        assert_eq!(1, *observer.depths.borrow());
+    }
+
+    #[test]
+    fn prefers_shortest_mate() {
+        // Ra8# mates immediately, but at higher depths there are plenty of slower mates available too.
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        for depth in 1..=4 {
+            let mut search: Searcher<ShannonEvaluator> = Default::default();
+            let result = search.search(&pos, depth, None, &NullDataRecorder);
+            assert_eq!(result.best_move, Move::quiet(Square::A1, Square::A8));
+            assert_eq!(result.score, Score::Win(1));
+        }
+    }
+
+    #[test]
+    fn mate_in_two() {
+        // The rooks mate in two moves, by cutting the king off on the seventh rank and then checking it on the eighth.
+        // There is no mate in one.
+        let pos = Position::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        for depth in 3..=5 {
+            let mut search: Searcher<ShannonEvaluator> = Default::default();
+            let result = search.search(&pos, depth, None, &NullDataRecorder);
+            assert_eq!(Some(3), result.score.mate_in(), "depth {}", depth);
+
+            // Black's best defense still ends in mate.
+            assert_eq!(3, result.pv.len(), "depth {}", depth);
+            let mut mated = pos.clone();
+            for &mov in &result.pv {
+                mated.apply_move(mov);
+            }
+            assert!(
+                mated.is_checkmate(),
+                "{} at depth {}",
+                mated.as_fen(),
+                depth
+            );
+        }
     }
 
     #[test]
@@ -516,10 +1751,347 @@ mod tests {
         gen.generate_moves(&pos, &mut moves);
         moves.retain(|&mut m| pos.is_legal_given_pseudolegal(m));
 
-        order_moves(&pos, &mut moves);
+        order_moves(&pos, &mut moves, &[], &[[0; 64]; 64]);
         assert_eq!(
             moves.first().cloned().unwrap(),
             Move::capture(Square::C5, Square::D6)
         );
     }
+
+    #[test]
+    fn move_ordering_knight_underpromotion() {
+        // e8=N is mate, since the black king is boxed in by its own pieces.
+        let pos = Position::from_fen("5bnr/4Ppkp/5ppp/8/8/8/8/K7 w - - 0 1").unwrap();
+        let mut moves = pos.legal_moves();
+        order_moves(&pos, &mut moves, &[], &[[0; 64]; 64]);
+
+        let knight_promo = Move::promotion(Square::E7, Square::E8, PieceKind::Knight);
+        let knight_idx = moves.iter().position(|&m| m == knight_promo).unwrap();
+        let queen_promo = Move::promotion(Square::E7, Square::E8, PieceKind::Queen);
+        assert!(moves.iter().position(|&m| m == queen_promo).unwrap() < knight_idx);
+
+        // Rook and bishop promotions on e8 come after the knight promotion, but still ahead of
+        // every quiet move.
+        let last_promo = moves
+            .iter()
+            .rposition(|m| m.is_promotion() && !m.is_capture())
+            .unwrap();
+        assert!(knight_idx < last_promo);
+        for (idx, mov) in moves.iter().enumerate() {
+            if !mov.is_promotion() && !mov.is_capture() {
+                assert!(
+                    last_promo < idx,
+                    "{} ordered before {}",
+                    mov,
+                    moves[last_promo]
+                );
+            }
+        }
+
+        let mut child = pos.clone();
+        child.apply_move(knight_promo);
+        assert!(child.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn move_ordering_checks_first() {
+        // Ra8 is mate, but nothing else is a capture or a check.
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut moves = pos.legal_moves();
+        order_moves(&pos, &mut moves, &[], &[[0; 64]; 64]);
+        assert_eq!(Move::quiet(Square::A1, Square::A8), moves[0]);
+
+        // Bb5+ checks, while the other bishop moves don't.
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/4KB2 w - - 0 1").unwrap();
+        let mut moves = pos.legal_moves();
+        order_moves(&pos, &mut moves, &[], &[[0; 64]; 64]);
+        assert_eq!(Move::quiet(Square::F1, Square::B5), moves[0]);
+        assert!(!pos.gives_check(moves[1]));
+
+        // Nxg3 wins a pawn, which comes before the check.
+        let pos = Position::from_fen("4k3/8/8/8/8/6p1/8/4KB1N w - - 0 1").unwrap();
+        let mut moves = pos.legal_moves();
+        order_moves(&pos, &mut moves, &[], &[[0; 64]; 64]);
+        assert_eq!(Move::capture(Square::H1, Square::G3), moves[0]);
+        assert_eq!(Move::quiet(Square::F1, Square::B5), moves[1]);
+    }
+
+    #[test]
+    fn late_move_reductions_keep_best_move() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r1bq1rk1/pp2ppbp/2np1np1/8/3NP3/2N1BP2/PPPQ2PP/R3KB1R w KQ - 3 9",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        ];
+        let mut reduced_nodes = 0;
+        let mut full_nodes = 0;
+        for fen in &fens {
+            let pos = Position::from_fen(fen).unwrap();
+            let mut reducing: Searcher<ShannonEvaluator> = Default::default();
+            reducing.set_params(SearchParams {
+                late_move_reduction: Some(1),
+                ..Default::default()
+            });
+            let reduced = reducing.search(&pos, 4, None, &NullDataRecorder);
+
+            let mut full: Searcher<ShannonEvaluator> = Default::default();
+            let unreduced = full.search(&pos, 4, None, &NullDataRecorder);
+            assert_eq!(unreduced.best_move, reduced.best_move, "{}", fen);
+            reduced_nodes += reduced.nodes_searched;
+            full_nodes += unreduced.nodes_searched;
+        }
+
+        assert!(
+            reduced_nodes * 2 < full_nodes,
+            "{} nodes with reductions, {} without",
+            reduced_nodes,
+            full_nodes
+        );
+    }
+
+    #[test]
+    fn aspiration_windows_keep_best_move() {
+        struct TotalRecorder {
+            nodes: RefCell<u64>,
+        }
+
+        impl DataRecorder for TotalRecorder {
+            fn record(&self, _pos: &Position, rec: &Record) {
+                *self.nodes.borrow_mut() += rec.nodes;
+            }
+        }
+
+        // Quiet positions, where the score doesn't move much from one depth to the next.
+        let fens = [
+            "r1bq1rk1/pp2ppbp/2np1np1/8/3NP3/2N1BP2/PPPQ2PP/R3KB1R w KQ - 3 9",
+            "r2q1rk1/ppp2ppp/2npbn2/2b1p3/2B1P3/2PP1N2/PP1N1PPP/R1BQ1RK1 w - - 2 8",
+            "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3",
+        ];
+        let aspiration_recorder = TotalRecorder {
+            nodes: RefCell::new(0),
+        };
+        let full_recorder = TotalRecorder {
+            nodes: RefCell::new(0),
+        };
+        for fen in &fens {
+            let pos = Position::from_fen(fen).unwrap();
+            let mut aspiring: Searcher<ShannonEvaluator> = Default::default();
+            aspiring.set_params(SearchParams {
+                aspiration_delta: Some(1.0),
+                ..Default::default()
+            });
+            let aspiration = aspiring.search(&pos, 4, None, &aspiration_recorder);
+
+            let mut full: Searcher<ShannonEvaluator> = Default::default();
+            let full_window = full.search(&pos, 4, None, &full_recorder);
+            assert_eq!(full_window.best_move, aspiration.best_move, "{}", fen);
+            assert_eq!(full_window.score, aspiration.score, "{}", fen);
+        }
+
+        let aspiration_nodes = *aspiration_recorder.nodes.borrow();
+        let full_nodes = *full_recorder.nodes.borrow();
+        assert!(
+            aspiration_nodes < full_nodes,
+            "{} nodes with aspiration windows, {} without",
+            aspiration_nodes,
+            full_nodes
+        );
+    }
+
+    #[test]
+    fn aspiration_window_re_search() {
+        struct ReSearchRecorder {
+            re_searches: RefCell<u64>,
+        }
+
+        impl DataRecorder for ReSearchRecorder {
+            fn record(&self, _pos: &Position, rec: &Record) {
+                *self.re_searches.borrow_mut() += rec.aspiration_re_searches;
+            }
+        }
+
+        // The score swings by more than a tenth of a pawn from one depth to the next in this sharp position, so the
+        // search keeps falling outside of such a narrow window.
+        let pos =
+            Position::from_fen("r1bqkb1r/pppp1ppp/2n5/4p3/2BnP3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 5")
+                .unwrap();
+        let recorder = ReSearchRecorder {
+            re_searches: RefCell::new(0),
+        };
+        let mut search: Searcher<ShannonEvaluator> = Default::default();
+        search.set_params(SearchParams {
+            aspiration_delta: Some(0.1),
+            ..Default::default()
+        });
+        let result = search.search(&pos, 4, None, &recorder);
+        assert!(*recorder.re_searches.borrow() > 0);
+
+        let mut full: Searcher<ShannonEvaluator> = Default::default();
+        let full_window = full.search(&pos, 4, None, &NullDataRecorder);
+        assert_eq!(full_window.best_move, result.best_move);
+        assert_eq!(full_window.score, result.score);
+    }
+
+    #[test]
+    fn killer_moves_reduce_nodes() {
+        // A Yugoslav Attack against the Dragon, where both sides have plenty of quiet moves and few captures.
+        let pos =
+            Position::from_fen("r1bq1rk1/pp2ppbp/2np1np1/8/3NP3/2N1BP2/PPPQ2PP/R3KB1R w KQ - 3 9")
+                .unwrap();
+
+        let mut with_killers: Searcher<ShannonEvaluator> = Default::default();
+        let with_nodes = with_killers
+            .search(&pos, 4, None, &NullDataRecorder)
+            .nodes_searched;
+
+        let mut without_killers: Searcher<ShannonEvaluator> = Default::default();
+        without_killers.set_params(SearchParams {
+            killer_moves: false,
+            ..Default::default()
+        });
+        let without_nodes = without_killers
+            .search(&pos, 4, None, &NullDataRecorder)
+            .nodes_searched;
+
+        // Principal variation search's null-window searches cut off cheaply no matter which quiet move comes first,
+        // which leaves the killers much less to save at this depth than in a full-window search. They should still save
+        // at least 5%.
+        assert!(
+            with_nodes * 20 < without_nodes * 19,
+            "{} nodes with killers, {} without",
+            with_nodes,
+            without_nodes
+        );
+    }
+
+    #[test]
+    fn futility_pruning_reduces_nodes() {
+        let pos = Position::from_fen(
+            "r2q1rk1/ppp2ppp/2npbn2/2b1p3/2B1P3/2PP1N2/PP1N1PPP/R1BQ1RK1 w - - 2 8",
+        )
+        .unwrap();
+        let mut pruning: Searcher<ShannonEvaluator> = Default::default();
+        pruning.set_params(SearchParams {
+            futility_margin: Some(1.0),
+            ..Default::default()
+        });
+        let pruned = pruning.search(&pos, 4, None, &NullDataRecorder);
+
+        let mut full: Searcher<ShannonEvaluator> = Default::default();
+        let unpruned = full.search(&pos, 4, None, &NullDataRecorder);
+        assert!(pos.is_legal(pruned.best_move));
+        assert!(
+            pruned.nodes_searched < unpruned.nodes_searched,
+            "{} nodes with futility pruning, {} without",
+            pruned.nodes_searched,
+            unpruned.nodes_searched
+        );
+    }
+
+    #[test]
+    fn razoring_reduces_nodes() {
+        let pos = Position::from_fen(
+            "r2q1rk1/ppp2ppp/2npbn2/2b1p3/2B1P3/2PP1N2/PP1N1PPP/R1BQ1RK1 w - - 2 8",
+        )
+        .unwrap();
+        let mut razoring: Searcher<ShannonEvaluator> = Default::default();
+        razoring.set_params(SearchParams {
+            razor_margin: Some(2.0),
+            ..Default::default()
+        });
+        let razored = razoring.search(&pos, 4, None, &NullDataRecorder);
+
+        let mut full: Searcher<ShannonEvaluator> = Default::default();
+        let unrazored = full.search(&pos, 4, None, &NullDataRecorder);
+        assert!(pos.is_legal(razored.best_move));
+        assert!(
+            razored.nodes_searched < unrazored.nodes_searched,
+            "{} nodes with razoring, {} without",
+            razored.nodes_searched,
+            unrazored.nodes_searched
+        );
+    }
+
+    #[test]
+    fn null_move_pruning_reduces_nodes() {
+        // A quiet Giuoco Piano middlegame, where most of the moves that Black can answer with don't threaten anything.
+        let pos = Position::from_fen(
+            "r2q1rk1/ppp2ppp/2npbn2/2b1p3/2B1P3/2PP1N2/PP1N1PPP/R1BQ1RK1 w - - 2 8",
+        )
+        .unwrap();
+        let mut pruning: Searcher<ShannonEvaluator> = Default::default();
+        pruning.set_params(SearchParams {
+            null_move_reduction: Some(2),
+            ..Default::default()
+        });
+        let pruned = pruning.search(&pos, 5, None, &NullDataRecorder);
+
+        let mut full: Searcher<ShannonEvaluator> = Default::default();
+        let unpruned = full.search(&pos, 5, None, &NullDataRecorder);
+        assert_eq!(unpruned.best_move, pruned.best_move);
+        assert!(
+            pruned.nodes_searched * 4 < unpruned.nodes_searched * 3,
+            "{} nodes with null moves, {} without",
+            pruned.nodes_searched,
+            unpruned.nodes_searched
+        );
+    }
+
+    #[test]
+    fn null_move_not_tried_with_only_pawns() {
+        let mut searcher: Searcher<ShannonEvaluator> = Default::default();
+        searcher.set_params(SearchParams {
+            null_move_reduction: Some(2),
+            ..Default::default()
+        });
+        let mut search = IterativeSearch::new(&searcher, 0, None, None, &NullSearchObserver);
+
+        // White is a knight up, so passing fails high against any beta that White isn't winning by.
+        let beta = Score::Evaluated(0.0);
+        let mut pos = Position::from_fen("8/8/8/1k6/1p6/1K6/1P4N1/8 w - - 0 1").unwrap();
+        assert!(search.null_move_fails_high(&mut pos, beta, 4, 1));
+
+        // King and pawn endings are where zugzwang happens, so passing isn't tried at all.
+        let mut pos = Position::from_fen("8/8/8/1k6/1p6/1K6/1P6/8 w - - 0 1").unwrap();
+        assert!(!search.null_move_fails_high(&mut pos, Score::Evaluated(-10.0), 4, 1));
+    }
+
+    #[test]
+    fn move_ordering_killers_after_captures() {
+        let pos = Position::from_fen("5b2/8/3r2r1/2P5/5B2/8/3Q4/8 w - - 0 1").unwrap();
+        let killer = Move::quiet(Square::D2, Square::A5);
+        let mut moves = pos.legal_moves();
+        order_moves(&pos, &mut moves, &[Move::null(), killer], &[[0; 64]; 64]);
+        assert_eq!(Move::capture(Square::C5, Square::D6), moves[0]);
+        let killer_idx = moves.iter().position(|&m| m == killer).unwrap();
+        for (idx, mov) in moves.iter().enumerate() {
+            if !mov.is_capture() && idx != killer_idx {
+                assert!(killer_idx < idx, "{} ordered before {}", mov, killer);
+            }
+        }
+    }
+
+    #[test]
+    fn see_ge_losing_capture() {
+        let pos = Position::from_fen("4k3/8/4p3/3p2r1/8/5N2/8/K2Q4 w - - 0 1").unwrap();
+        // Qxd5 trades a queen for a pawn, since the pawn on e6 recaptures.
+        assert!(!see_ge(&pos, Move::capture(Square::D1, Square::D5), 0));
+        // Nxg5 wins a rook outright.
+        assert!(see_ge(&pos, Move::capture(Square::F3, Square::G5), 0));
+        assert!(see_ge(&pos, Move::capture(Square::F3, Square::G5), 5));
+        assert!(!see_ge(&pos, Move::capture(Square::F3, Square::G5), 6));
+    }
+
+    #[test]
+    fn good_capture_filter() {
+        let pos = Position::from_fen("4k3/8/4p3/3p2r1/8/5N2/8/K2Q4 w - - 0 1").unwrap();
+        let gen = MoveGenerator::new();
+        let mut moves = MoveVec::default();
+        gen.generate_captures(&pos, &mut moves);
+        assert_eq!(moves.len(), 2);
+
+        filter_good_captures(&pos, &mut moves);
+        assert_eq!(moves.as_slice(), &[Move::capture(Square::F3, Square::G5)]);
+    }
 }
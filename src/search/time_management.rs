@@ -0,0 +1,188 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Time management: deciding how much of the clock to spend on the current move.
+use std::time::Duration;
+
+use crate::types::Color;
+
+/// The number of moves left in the game that the clock is assumed to need to cover, when the GUI
+/// doesn't say how many moves remain until the next time control.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Time held back from every move to cover the delay between the engine deciding on a move and the
+/// GUI stopping its clock.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+/// Below this much time on the clock, the engine is in danger of flagging and stops planning for
+/// the rest of the time control.
+const PANIC_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// In panic mode, each move gets this fraction of the remaining time plus half of the increment.
+const PANIC_DIVISOR: u32 = 20;
+
+/// The time spent searching when the GUI gives neither a clock nor a depth limit.
+const DEFAULT_BUDGET: Duration = Duration::from_secs(1);
+
+/// The limits on a search, as sent by the GUI along with a `go` command.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchRequest {
+    /// The maximum depth to search to.
+    pub depth: Option<u32>,
+    /// The time left on White's clock.
+    pub wtime: Option<Duration>,
+    /// The time left on Black's clock.
+    pub btime: Option<Duration>,
+    /// White's increment per move.
+    pub winc: Option<Duration>,
+    /// Black's increment per move.
+    pub binc: Option<Duration>,
+    /// The number of moves until the next time control, if there is one.
+    pub moves_to_go: Option<u32>,
+    /// The exact amount of time to search for.
+    pub movetime: Option<Duration>,
+    /// Whether to search until told to stop.
+    pub infinite: bool,
+}
+
+/// Returns the amount of time that the given side should spend on its move, or `None` if the
+/// search shouldn't be limited by time at all.
+///
+/// A fixed `movetime` is used as-is. Otherwise, the time left on the clock is split evenly across
+/// the moves remaining until the next time control, and the increment is spent in full since it
+/// comes back after the move. Once the clock runs low, the engine spends only a small slice of what
+/// remains so that it doesn't lose on time.
+pub fn allocate_time(request: &SearchRequest, side: Color) -> Option<Duration> {
+    if request.infinite {
+        return None;
+    }
+
+    if let Some(movetime) = request.movetime {
+        return Some(movetime);
+    }
+
+    let (remaining, increment) = match side {
+        Color::White => (request.wtime, request.winc),
+        Color::Black => (request.btime, request.binc),
+    };
+
+    let remaining = match remaining {
+        Some(remaining) => remaining,
+        // With a depth limit and no clock, the GUI wants exactly that depth.
+        None if request.depth.is_some() => return None,
+        None => return Some(DEFAULT_BUDGET),
+    };
+
+    let increment = increment.unwrap_or_default();
+    let available = remaining.saturating_sub(MOVE_OVERHEAD);
+    let budget = if remaining < PANIC_THRESHOLD {
+        available / PANIC_DIVISOR + increment / 2
+    } else {
+        let moves_to_go = request.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        available / moves_to_go + increment
+    };
+
+    // Whatever the increment, the move must be made before the clock runs out.
+    Some(budget.min(available))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{allocate_time, SearchRequest};
+    use crate::types::Color;
+    use std::time::Duration;
+
+    fn ms(millis: u64) -> Option<Duration> {
+        Some(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn movetime_is_exact() {
+        let request = SearchRequest {
+            wtime: ms(60_000),
+            movetime: ms(2_500),
+            ..Default::default()
+        };
+        assert_eq!(ms(2_500), allocate_time(&request, Color::White));
+        assert_eq!(ms(2_500), allocate_time(&request, Color::Black));
+    }
+
+    #[test]
+    fn infinite_is_unlimited() {
+        let request = SearchRequest {
+            wtime: ms(60_000),
+            infinite: true,
+            ..Default::default()
+        };
+        assert_eq!(None, allocate_time(&request, Color::White));
+    }
+
+    #[test]
+    fn depth_without_clock_is_unlimited() {
+        let request = SearchRequest {
+            depth: Some(6),
+            ..Default::default()
+        };
+        assert_eq!(None, allocate_time(&request, Color::White));
+        assert_eq!(ms(1_000), allocate_time(&Default::default(), Color::White));
+    }
+
+    #[test]
+    fn sudden_death() {
+        // One minute to play the rest of the game is spread over thirty moves.
+        let request = SearchRequest {
+            wtime: ms(60_050),
+            btime: ms(30_050),
+            ..Default::default()
+        };
+        assert_eq!(ms(2_000), allocate_time(&request, Color::White));
+        assert_eq!(ms(1_000), allocate_time(&request, Color::Black));
+    }
+
+    #[test]
+    fn increment_is_spent() {
+        let request = SearchRequest {
+            wtime: ms(60_050),
+            btime: ms(60_050),
+            winc: ms(1_000),
+            binc: ms(500),
+            ..Default::default()
+        };
+        assert_eq!(ms(3_000), allocate_time(&request, Color::White));
+        assert_eq!(ms(2_500), allocate_time(&request, Color::Black));
+    }
+
+    #[test]
+    fn moves_to_go() {
+        let request = SearchRequest {
+            wtime: ms(10_050),
+            moves_to_go: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(ms(2_000), allocate_time(&request, Color::White));
+
+        // With one move to go before the time control, all of the time can be used, but no more.
+        let request = SearchRequest {
+            wtime: ms(10_050),
+            winc: ms(5_000),
+            moves_to_go: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(ms(10_000), allocate_time(&request, Color::White));
+    }
+
+    #[test]
+    fn low_time_panic() {
+        let request = SearchRequest {
+            btime: ms(850),
+            binc: ms(100),
+            ..Default::default()
+        };
+        assert_eq!(ms(90), allocate_time(&request, Color::Black));
+    }
+}
@@ -5,7 +5,10 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-use hashbrown::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::Path;
 use std::sync::atomic::AtomicU64;
 use std::sync::RwLock;
 
@@ -13,6 +16,15 @@ use crate::eval::Score;
 use crate::moves::Move;
 use crate::position::Position;
 
+const TABLE_FILE_MAGIC: [u8; 4] = *b"APTT";
+
+/// The size of a table constructed with `TranspositionTable::new`.
+const DEFAULT_SIZE_MB: usize = 4;
+
+/// The number of entries in a bucket. A bucket fills exactly one 64-byte cache line, so a probe
+/// only ever touches one cache line.
+const BUCKET_SIZE: usize = 4;
+
 #[derive(Copy, Clone, Debug)]
 pub enum NodeKind {
     PrincipalVariation(Score),
@@ -28,20 +40,141 @@ pub struct TableEntry {
     pub node: NodeKind,
 }
 
+// Flags for a packed entry's `flags` byte. The low two bits are the node kind (0 = PV, 1 = all,
+// 2 = cut) and the next two are the score kind (0 = evaluated, 1 = win, 2 = loss).
+const FLAG_NODE_MASK: u8 = 0b0000_0011;
+const FLAG_SCORE_SHIFT: u8 = 2;
+const FLAG_SCORE_MASK: u8 = 0b0000_1100;
+const FLAG_HAS_MOVE: u8 = 0b0001_0000;
+const FLAG_OCCUPIED: u8 = 0b1000_0000;
+
+/// A TableEntry packed into 16 bytes, so that four of them fit in a cache line. Depths are
+/// saturated at 255 plies.
+#[derive(Copy, Clone, Default)]
+struct PackedEntry {
+    key: u64,
+    score: u32,
+    best_move: u16,
+    depth: u8,
+    flags: u8,
+}
+
+impl PackedEntry {
+    fn pack(entry: &TableEntry) -> PackedEntry {
+        let (node_kind, score) = match entry.node {
+            NodeKind::PrincipalVariation(score) => (0, score),
+            NodeKind::All(score) => (1, score),
+            NodeKind::Cut(score) => (2, score),
+        };
+        let (score_kind, score_bits) = match score {
+            Score::Evaluated(score) => (0, score.to_bits()),
+            Score::Win(plies) => (1, plies),
+            Score::Loss(plies) => (2, plies),
+        };
+        let (has_move, best_move) = match entry.best_move {
+            Some(mov) => (FLAG_HAS_MOVE, mov.to_bits()),
+            None => (0, 0),
+        };
+
+        PackedEntry {
+            key: entry.zobrist_key,
+            score: score_bits,
+            best_move,
+            depth: entry.depth.min(u32::from(u8::MAX)) as u8,
+            flags: FLAG_OCCUPIED | has_move | (score_kind << FLAG_SCORE_SHIFT) | node_kind,
+        }
+    }
+
+    fn unpack(self) -> TableEntry {
+        let score = match (self.flags & FLAG_SCORE_MASK) >> FLAG_SCORE_SHIFT {
+            0 => Score::Evaluated(f32::from_bits(self.score)),
+            1 => Score::Win(self.score),
+            _ => Score::Loss(self.score),
+        };
+        let node = match self.flags & FLAG_NODE_MASK {
+            0 => NodeKind::PrincipalVariation(score),
+            1 => NodeKind::All(score),
+            _ => NodeKind::Cut(score),
+        };
+
+        TableEntry {
+            zobrist_key: self.key,
+            best_move: if self.flags & FLAG_HAS_MOVE != 0 {
+                Some(Move::from_bits(self.best_move))
+            } else {
+                None
+            },
+            depth: u32::from(self.depth),
+            node,
+        }
+    }
+
+    fn is_occupied(self) -> bool {
+        self.flags & FLAG_OCCUPIED != 0
+    }
+}
+
+/// A group of entries that share a cache line. Positions hash to a bucket and may occupy any
+/// entry within it.
+#[derive(Copy, Clone, Default)]
+#[repr(align(64))]
+struct Bucket {
+    entries: [PackedEntry; BUCKET_SIZE],
+}
+
+impl Bucket {
+    fn find(&self, key: u64) -> Option<&PackedEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.is_occupied() && entry.key == key)
+    }
+
+    /// Stores an entry in this bucket. An existing entry for the same position is always
+    /// replaced; otherwise the new entry goes into an empty slot if there is one, or else replaces
+    /// the shallowest entry in the bucket, since that's the one that's cheapest to recompute.
+    fn store(&mut self, entry: PackedEntry) {
+        let slot = match self
+            .entries
+            .iter()
+            .position(|e| e.is_occupied() && e.key == entry.key)
+        {
+            Some(slot) => slot,
+            None => match self.entries.iter().position(|e| !e.is_occupied()) {
+                Some(slot) => slot,
+                None => (0..BUCKET_SIZE)
+                    .min_by_key(|&slot| self.entries[slot].depth)
+                    .unwrap(),
+            },
+        };
+
+        self.entries[slot] = entry;
+    }
+}
+
 pub struct TableStats {
     table_hits: AtomicU64,
     table_misses: AtomicU64,
 }
 
+/// A fixed-size hash table of search results, keyed by zobrist hash. When the table is full, new
+/// entries replace old ones.
 pub struct TranspositionTable {
-    table: RwLock<HashMap<u64, TableEntry>>,
+    table: RwLock<Vec<Bucket>>,
     stats: TableStats,
 }
 
 impl TranspositionTable {
     pub fn new() -> TranspositionTable {
+        TranspositionTable::with_size_mb(DEFAULT_SIZE_MB)
+    }
+
+    /// Constructs a new table that occupies at most the given number of megabytes. The number of
+    /// buckets is rounded down to a power of two.
+    pub fn with_size_mb(size_mb: usize) -> TranspositionTable {
+        let buckets = (size_mb * 1024 * 1024 / mem::size_of::<Bucket>()).max(1);
+        let buckets = 1 << (63 - (buckets as u64).leading_zeros());
         TranspositionTable {
-            table: RwLock::new(HashMap::new()),
+            table: RwLock::new(vec![Bucket::default(); buckets]),
             stats: TableStats {
                 table_hits: AtomicU64::new(0),
                 table_misses: AtomicU64::new(0),
@@ -53,14 +186,43 @@ impl TranspositionTable {
         &self.stats
     }
 
+    /// Returns the number of entries in the table.
+    pub fn len(&self) -> usize {
+        let table = self.table.read().expect("T-Table lock was poisoned");
+        table
+            .iter()
+            .flat_map(|bucket| bucket.entries.iter())
+            .filter(|entry| entry.is_occupied())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of entries that the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.table.read().expect("T-Table lock was poisoned").len() * BUCKET_SIZE
+    }
+
+    /// Removes every entry from the table.
+    pub fn clear(&self) {
+        let mut table = self.table.write().expect("T-Table lock was poisoned");
+        for bucket in table.iter_mut() {
+            *bucket = Bucket::default();
+        }
+    }
+
     pub fn query<F, R>(&self, pos: &Position, f: F) -> R
     where
         F: FnOnce(Option<&TableEntry>) -> R,
     {
         let key = pos.zobrist_hash();
         let table = self.table.read().expect("T-Table lock was poisoned");
-        let entry = table.get(&key);
-        f(entry)
+        let entry = table[bucket_index(&table, key)]
+            .find(key)
+            .map(|entry| entry.unpack());
+        f(entry.as_ref())
     }
 
     pub fn query_copy(&self, pos: &Position) -> Option<TableEntry> {
@@ -116,8 +278,307 @@ impl TranspositionTable {
         self.record_entry(entry);
     }
 
+    /// Saves every entry in this table to the file at the given path, so that it can be loaded
+    /// again by `load`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Loads the entries saved by `save` from the file at the given path into this table,
+    /// replacing any entries for the same positions. The table must have the same capacity as the
+    /// table that was saved; otherwise, this fails with `InvalidData` and leaves the table as it
+    /// was.
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+        self.read_from(&mut reader)
+    }
+
+    // The saved format is a magic number, the capacity of the table, and the number of entries,
+    // followed by the entries themselves. Every number is little-endian. Each entry is:
+    //   * 8 bytes - zobrist key
+    //   * 4 bytes - depth
+    //   * 2 bytes - best move, as `Move::to_bits`
+    //   * 2 bytes - score, as `Score::to_i16`
+    //   * 1 byte  - node kind (0 = PV, 1 = all, 2 = cut), plus 4 if there is a best move
+    //
+    // A saved table can only be loaded into a table of the same capacity, since a smaller table
+    // would silently drop the entries that collide in it. The entry count exists to detect
+    // truncated files.
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let table = self.table.read().expect("T-Table lock was poisoned");
+        let entries: Vec<TableEntry> = table
+            .iter()
+            .flat_map(|bucket| bucket.entries.iter())
+            .filter(|entry| entry.is_occupied())
+            .map(|entry| entry.unpack())
+            .collect();
+        writer.write_all(&TABLE_FILE_MAGIC)?;
+        writer.write_all(&((table.len() * BUCKET_SIZE) as u64).to_le_bytes())?;
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for entry in &entries {
+            let (kind, score) = match entry.node {
+                NodeKind::PrincipalVariation(score) => (0u8, score),
+                NodeKind::All(score) => (1u8, score),
+                NodeKind::Cut(score) => (2u8, score),
+            };
+            let (has_move, best_move) = match entry.best_move {
+                Some(mov) => (4u8, mov.to_bits()),
+                None => (0u8, 0),
+            };
+
+            writer.write_all(&entry.zobrist_key.to_le_bytes())?;
+            writer.write_all(&entry.depth.to_le_bytes())?;
+            writer.write_all(&best_move.to_le_bytes())?;
+            writer.write_all(&score.to_i16().to_le_bytes())?;
+            writer.write_all(&[kind | has_move])?;
+        }
+
+        Ok(())
+    }
+
+    fn read_from<R: Read>(&self, reader: &mut R) -> io::Result<()> {
+        fn invalid(message: &str) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        }
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != TABLE_FILE_MAGIC {
+            return Err(invalid("not a saved transposition table"));
+        }
+
+        let mut capacity = [0u8; 8];
+        reader.read_exact(&mut capacity)?;
+        if u64::from_le_bytes(capacity) != self.capacity() as u64 {
+            return Err(invalid(
+                "saved transposition table has a different capacity",
+            ));
+        }
+
+        let mut count = [0u8; 8];
+        reader.read_exact(&mut count)?;
+        let count = u64::from_le_bytes(count);
+        let mut entries = vec![];
+        for _ in 0..count {
+            let mut buf = [0u8; 17];
+            reader.read_exact(&mut buf)?;
+            let mut key = [0u8; 8];
+            key.copy_from_slice(&buf[0..8]);
+            let mut depth = [0u8; 4];
+            depth.copy_from_slice(&buf[8..12]);
+            let best_move = Move::from_bits(u16::from_le_bytes([buf[12], buf[13]]));
+            let score = Score::from_i16(i16::from_le_bytes([buf[14], buf[15]]));
+            let node = match buf[16] & 3 {
+                0 => NodeKind::PrincipalVariation(score),
+                1 => NodeKind::All(score),
+                2 => NodeKind::Cut(score),
+                _ => return Err(invalid("invalid node kind")),
+            };
+
+            entries.push(TableEntry {
+                zobrist_key: u64::from_le_bytes(key),
+                best_move: if buf[16] & 4 != 0 {
+                    Some(best_move)
+                } else {
+                    None
+                },
+                depth: u32::from_le_bytes(depth),
+                node,
+            });
+        }
+
+        for entry in entries {
+            self.record_entry(entry);
+        }
+
+        Ok(())
+    }
+
     fn record_entry(&self, entry: TableEntry) {
         let mut table = self.table.write().expect("T-Table lock was poisoned");
-        table.insert(entry.zobrist_key, entry);
+        let index = bucket_index(&table, entry.zobrist_key);
+        table[index].store(PackedEntry::pack(&entry));
+    }
+}
+
+/// Returns the index of the bucket that the given key hashes to. The number of buckets is always a
+/// power of two.
+fn bucket_index(table: &[Bucket], key: u64) -> usize {
+    (key as usize) & (table.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io;
+
+    use std::mem;
+
+    use super::{Bucket, NodeKind, PackedEntry, TableEntry, TranspositionTable};
+    use crate::eval::Score;
+    use crate::moves::Move;
+    use crate::position::Position;
+    use crate::types::Square;
+
+    #[test]
+    fn save_and_load() {
+        let table = TranspositionTable::new();
+        let start = Position::from_start_position();
+        let e4 = Move::double_pawn_push(Square::E2, Square::E4);
+        table.record_principal_variation(&start, e4, 4, Score::Evaluated(0.5));
+
+        let mut after_e4 = start.clone();
+        after_e4.apply_move(e4);
+        let e5 = Move::double_pawn_push(Square::E7, Square::E5);
+        table.record_cut(&after_e4, e5, 3, Score::Win(5));
+
+        let mut after_e5 = after_e4.clone();
+        after_e5.apply_move(e5);
+        table.record_all(&after_e5, 2, Score::Loss(2));
+
+        let path = env::temp_dir().join(format!("apollo-tt-{}.bin", std::process::id()));
+        table.save(&path).unwrap();
+        let loaded = TranspositionTable::new();
+        loaded.load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(3, loaded.len());
+        let entry = loaded.query_copy(&start).unwrap();
+        assert_eq!(Some(e4), entry.best_move);
+        assert_eq!(4, entry.depth);
+        match entry.node {
+            NodeKind::PrincipalVariation(score) => assert_eq!(Score::Evaluated(0.5), score),
+            node => panic!("unexpected node {:?}", node),
+        }
+
+        let entry = loaded.query_copy(&after_e4).unwrap();
+        assert_eq!(Some(e5), entry.best_move);
+        match entry.node {
+            NodeKind::Cut(score) => assert_eq!(Score::Win(5), score),
+            node => panic!("unexpected node {:?}", node),
+        }
+
+        let entry = loaded.query_copy(&after_e5).unwrap();
+        assert_eq!(None, entry.best_move);
+        match entry.node {
+            NodeKind::All(score) => assert_eq!(Score::Loss(2), score),
+            node => panic!("unexpected node {:?}", node),
+        }
+    }
+
+    #[test]
+    fn load_rejects_different_capacity() {
+        let table = TranspositionTable::with_size_mb(1);
+        let start = Position::from_start_position();
+        let e4 = Move::double_pawn_push(Square::E2, Square::E4);
+        table.record_principal_variation(&start, e4, 4, Score::Evaluated(0.5));
+
+        let path = env::temp_dir().join(format!("apollo-tt-size-{}.bin", std::process::id()));
+        table.save(&path).unwrap();
+        let smaller = TranspositionTable::with_size_mb(0);
+        let err = smaller.load(&path).unwrap_err();
+        let same = TranspositionTable::with_size_mb(1);
+        same.load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert!(smaller.is_empty());
+        assert_eq!(1, same.len());
+    }
+
+    #[test]
+    fn entries_fit_in_cache_lines() {
+        assert_eq!(16, mem::size_of::<PackedEntry>());
+        assert_eq!(64, mem::size_of::<Bucket>());
+        assert_eq!(64, mem::align_of::<Bucket>());
+    }
+
+    #[test]
+    fn pack_round_trip() {
+        for &(best_move, node) in &[
+            (
+                Some(Move::quiet(Square::G1, Square::F3)),
+                NodeKind::PrincipalVariation(Score::Evaluated(-0.37)),
+            ),
+            (None, NodeKind::All(Score::Loss(7))),
+            (
+                Some(Move::capture(Square::D4, Square::E5)),
+                NodeKind::Cut(Score::Win(3)),
+            ),
+        ] {
+            let entry = TableEntry {
+                zobrist_key: 0xdead_beef_cafe_f00d,
+                best_move,
+                depth: 9,
+                node,
+            };
+            let unpacked = PackedEntry::pack(&entry).unpack();
+            assert_eq!(format!("{:?}", entry), format!("{:?}", unpacked));
+        }
+    }
+
+    #[test]
+    fn stored_entries_are_retrievable() {
+        let table = TranspositionTable::new();
+        let mut pos = Position::from_start_position();
+        let mut positions = vec![];
+        for uci in &["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"] {
+            let mov = pos.move_from_uci(uci).unwrap();
+            table.record_cut(&pos, mov, 3, Score::Evaluated(0.25));
+            positions.push((pos.clone(), mov));
+            pos.apply_move(mov);
+        }
+
+        assert_eq!(positions.len(), table.len());
+        for (pos, mov) in positions {
+            assert_eq!(Some(mov), table.query_copy(&pos).unwrap().best_move);
+        }
+    }
+
+    #[test]
+    fn bucket_replaces_shallowest() {
+        // A table with a single bucket, so every position collides.
+        let table = TranspositionTable::with_size_mb(0);
+        assert_eq!(4, table.capacity());
+
+        let mut pos = Position::from_start_position();
+        let mut positions = vec![];
+        for (depth, uci) in ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"].iter().enumerate() {
+            let mov = pos.move_from_uci(uci).unwrap();
+            table.record_cut(&pos, mov, [5, 2, 7, 4, 6][depth], Score::Evaluated(0.0));
+            positions.push(pos.clone());
+            pos.apply_move(mov);
+        }
+
+        // The depth 2 entry made way for the last one.
+        assert_eq!(4, table.len());
+        assert!(table.query_copy(&positions[1]).is_none());
+        for &idx in &[0, 2, 3, 4] {
+            assert!(table.query_copy(&positions[idx]).is_some());
+        }
+
+        // Re-recording a position replaces its entry rather than taking another slot.
+        let mov = positions[0].move_from_uci("d2d4").unwrap();
+        table.record_cut(&positions[0], mov, 1, Score::Evaluated(0.0));
+        assert_eq!(4, table.len());
+        assert_eq!(
+            Some(mov),
+            table.query_copy(&positions[0]).unwrap().best_move
+        );
+    }
+
+    #[test]
+    fn load_rejects_garbage() {
+        let table = TranspositionTable::new();
+        assert!(table.read_from(&mut &b"not a table"[..]).is_err());
+
+        // A table that claims to have more entries than it does is truncated.
+        let mut truncated = b"APTT".to_vec();
+        truncated.extend_from_slice(&1u64.to_le_bytes());
+        assert!(table.read_from(&mut &truncated[..]).is_err());
+        assert!(table.is_empty());
     }
 }
@@ -0,0 +1,91 @@
+// Copyright 2017-2019 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Self-play harness, for generating games to tune against and to regression-test the searcher.
+
+use crate::eval::BoardEvaluator;
+use crate::position::Position;
+use crate::search::{DataRecorder, Searcher};
+use crate::types::GameResult;
+
+/// The longest game, in plies, that self-play will play before giving up and calling it a draw.
+/// Without a cap, the searcher is liable to shuffle pieces back and forth forever in dead positions.
+pub const MAX_GAME_PLIES: u32 = 300;
+
+/// Plays a game between the searcher and itself from the starting position, searching each move
+/// to the given depth. Every search records its statistics through the given recorder.
+pub fn play_game<E: BoardEvaluator>(
+    evaluator: E,
+    depth: u32,
+    recorder: &dyn DataRecorder,
+) -> GameResult {
+    play_game_from(evaluator, Position::from_start_position(), depth, recorder)
+}
+
+/// Plays a game between the searcher and itself from the given position. Games that go on for
+/// longer than `MAX_GAME_PLIES` are adjudicated as draws.
+pub fn play_game_from<E: BoardEvaluator>(
+    evaluator: E,
+    start: Position,
+    depth: u32,
+    recorder: &dyn DataRecorder,
+) -> GameResult {
+    let mut searcher = Searcher::with_evaluator(evaluator, None);
+    let mut pos = start;
+    let mut history = vec![];
+    for _ in 0..MAX_GAME_PLIES {
+        if let Some(result) = pos.outcome() {
+            return result;
+        }
+
+        searcher.set_game_history(history.clone());
+        let result = searcher.search(&pos, depth, None, recorder);
+        history.push(pos.zobrist_hash());
+        pos.apply_move(result.best_move);
+    }
+
+    pos.outcome().unwrap_or(GameResult::Draw)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::{play_game, play_game_from};
+    use crate::eval::ShannonEvaluator;
+    use crate::position::Position;
+    use crate::search::{DataRecorder, NullDataRecorder, Record};
+    use crate::types::{Color, GameResult};
+
+    struct CountingRecorder {
+        count: Cell<u32>,
+    }
+
+    impl DataRecorder for CountingRecorder {
+        fn record(&self, _pos: &Position, _rec: &Record) {
+            self.count.set(self.count.get() + 1);
+        }
+    }
+
+    #[test]
+    fn shallow_game_terminates() {
+        let recorder = CountingRecorder {
+            count: Cell::new(0),
+        };
+        // Whatever the result, getting one at all means that the game ended.
+        play_game(ShannonEvaluator::default(), 1, &recorder);
+        assert!(recorder.count.get() > 0);
+    }
+
+    #[test]
+    fn finds_mate() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = play_game_from(ShannonEvaluator::default(), pos, 2, &NullDataRecorder);
+        assert_eq!(GameResult::Win(Color::White), result);
+    }
+}
@@ -102,6 +102,11 @@ pub enum Square {
 }
 
 impl Square {
+    /// Iterates over every square on the board, in the same order as `SQUARES`.
+    pub fn iter() -> impl Iterator<Item = Square> {
+        SQUARES.iter().cloned()
+    }
+
     pub fn of(rank: Rank, file: File) -> Square {
         let rank = rank.to_u32().unwrap();
         let file = file.to_u32().unwrap();
@@ -116,12 +121,27 @@ impl Square {
         FromPrimitive::from_u32(self.to_u32().unwrap() & 7).unwrap()
     }
 
+    /// Offsets this square by the given raw number of squares. Panics if the resulting square
+    /// is off the board. Note that offsets that cross the east or west edge of the board wrap
+    /// around to an adjacent rank; it is up to callers to guard against that.
     pub fn plus(self, offset: i32) -> Square {
         let prim = self.to_i32().unwrap();
-        FromPrimitive::from_i32(prim + offset).unwrap()
+        FromPrimitive::from_i32(prim + offset)
+            .unwrap_or_else(|| panic!("square {} offset by {} is off the board", self, offset))
     }
 
+    /// Returns the square adjacent to this one in the given direction. Panics if there is no such
+    /// square. Callers are expected to check for board edges before calling this.
     pub fn towards(self, dir: Direction) -> Square {
+        // Stepping off the east or west edge wouldn't leave the board, it'd wrap around to the
+        // other side, so we need to check for that separately.
+        let file = self.file() as i32 + dir.file_step();
+        debug_assert!(
+            (0..8).contains(&file),
+            "square {} has no neighbor to the {:?}",
+            self,
+            dir
+        );
         self.plus(dir.as_vector())
     }
 }
@@ -257,6 +277,13 @@ pub static RANKS: [Rank; 8] = [
     Rank::Eight,
 ];
 
+impl Rank {
+    /// Iterates over every rank, from rank one to rank eight, in the same order as `RANKS`.
+    pub fn iter() -> impl Iterator<Item = Rank> {
+        RANKS.iter().cloned()
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 pub enum File {
     A,
@@ -315,6 +342,13 @@ pub static FILES: [File; 8] = [
     File::H,
 ];
 
+impl File {
+    /// Iterates over every file, from the A file to the H file, in the same order as `FILES`.
+    pub fn iter() -> impl Iterator<Item = File> {
+        FILES.iter().cloned()
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 pub enum Color {
     White,
@@ -322,12 +356,26 @@ pub enum Color {
 }
 
 impl Color {
+    /// Iterates over both colors, in the same order as `COLORS`.
+    pub fn iter() -> impl Iterator<Item = Color> {
+        COLORS.iter().cloned()
+    }
+
     pub fn toggle(self) -> Color {
         match self {
             Color::White => Color::Black,
             Color::Black => Color::White,
         }
     }
+
+    /// The offset of this color's boards in an array of boards indexed by `Piece::board_index`.
+    /// White's six boards come first, followed by Black's.
+    pub fn offset(self) -> usize {
+        match self {
+            Color::White => 0,
+            Color::Black => 6,
+        }
+    }
 }
 
 impl Display for Color {
@@ -389,6 +437,13 @@ pub static PIECE_KINDS: [PieceKind; 6] = [
     PieceKind::King,
 ];
 
+impl PieceKind {
+    /// Iterates over every kind of piece, in the same order as `PIECE_KINDS`.
+    pub fn iter() -> impl Iterator<Item = PieceKind> {
+        PIECE_KINDS.iter().cloned()
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 pub enum Direction {
     North,
@@ -414,6 +469,15 @@ impl Direction {
             Direction::NorthWest => 7,
         }
     }
+
+    /// The number of files that a single step in this direction moves across.
+    fn file_step(self) -> i32 {
+        match self {
+            Direction::NorthEast | Direction::East | Direction::SouthEast => 1,
+            Direction::SouthWest | Direction::West | Direction::NorthWest => -1,
+            Direction::North | Direction::South => 0,
+        }
+    }
 }
 
 bitflags! {
@@ -439,6 +503,12 @@ impl Piece {
         Piece { kind, color }
     }
 
+    /// The index of this piece's board in an array of twelve boards, one for each kind of piece of
+    /// each color.
+    pub fn board_index(&self) -> usize {
+        self.color.offset() + self.kind.as_index()
+    }
+
     pub fn attacks(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
         match self.kind {
             PieceKind::Pawn => attacks::pawn_attacks(sq, self.color),
@@ -537,3 +607,98 @@ impl Display for Piece {
         f.write_char(chr)
     }
 }
+
+/// The result of a finished game of chess.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    /// The given color won the game.
+    Win(Color),
+
+    /// The game was drawn.
+    Draw,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, Direction, File, Piece, PieceKind, Rank, Square};
+    use super::{COLORS, FILES, PIECE_KINDS, RANKS, SQUARES};
+
+    #[test]
+    fn iterators() {
+        assert_eq!(64, Square::iter().count());
+        assert_eq!(SQUARES.to_vec(), Square::iter().collect::<Vec<_>>());
+        assert_eq!(RANKS.to_vec(), Rank::iter().collect::<Vec<_>>());
+        assert_eq!(FILES.to_vec(), File::iter().collect::<Vec<_>>());
+        assert_eq!(PIECE_KINDS.to_vec(), PieceKind::iter().collect::<Vec<_>>());
+        assert_eq!(COLORS.to_vec(), Color::iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn board_indices_are_distinct() {
+        let mut seen = [false; 12];
+        for color in Color::iter() {
+            for kind in PieceKind::iter() {
+                let idx = Piece::new(kind, color).board_index();
+                assert!(!seen[idx], "{:?} {:?} reuses index {}", color, kind, idx);
+                seen[idx] = true;
+            }
+        }
+
+        assert!(seen.iter().all(|&seen| seen));
+    }
+
+    #[test]
+    fn towards_interior() {
+        assert_eq!(Square::E5, Square::E4.towards(Direction::North));
+        assert_eq!(Square::F5, Square::E4.towards(Direction::NorthEast));
+        assert_eq!(Square::F4, Square::E4.towards(Direction::East));
+        assert_eq!(Square::F3, Square::E4.towards(Direction::SouthEast));
+        assert_eq!(Square::E3, Square::E4.towards(Direction::South));
+        assert_eq!(Square::D3, Square::E4.towards(Direction::SouthWest));
+        assert_eq!(Square::D4, Square::E4.towards(Direction::West));
+        assert_eq!(Square::D5, Square::E4.towards(Direction::NorthWest));
+    }
+
+    #[test]
+    fn towards_along_edges() {
+        assert_eq!(Square::A2, Square::A1.towards(Direction::North));
+        assert_eq!(Square::B1, Square::A1.towards(Direction::East));
+        assert_eq!(Square::G8, Square::H8.towards(Direction::West));
+        assert_eq!(Square::H7, Square::H8.towards(Direction::South));
+        assert_eq!(Square::G7, Square::H8.towards(Direction::SouthWest));
+    }
+
+    #[test]
+    #[should_panic]
+    fn towards_off_north_edge() {
+        Square::E8.towards(Direction::North);
+    }
+
+    #[test]
+    #[should_panic]
+    fn towards_off_south_edge() {
+        Square::A1.towards(Direction::South);
+    }
+
+    // Wrapping around the east or west edge is only caught by a debug assertion, so these tests
+    // only run with debug assertions enabled.
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn towards_off_east_edge() {
+        Square::H4.towards(Direction::East);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn towards_off_west_edge() {
+        Square::A4.towards(Direction::NorthWest);
+    }
+
+    #[test]
+    #[should_panic]
+    fn plus_off_board() {
+        Square::H8.plus(1);
+    }
+}
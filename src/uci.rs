@@ -5,43 +5,58 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-use std::fmt::Write;
-use std::io::{self, BufRead};
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
 use std::time::Duration;
 
 use crate::book::OpeningBook;
-use crate::eval::Score;
-use crate::eval::ShannonEvaluator;
+use crate::eval::{DynamicEvaluator, MaterialEvaluator, Score, ShannonEvaluator};
 use crate::position::Position;
-use crate::search::{NullDataRecorder, Searcher};
+use crate::search::{
+    allocate_time, NullDataRecorder, SearchInfo, SearchObserver, SearchRequest, Searcher,
+};
 
 macro_rules! uci_println {
-    ($fmt:expr) => {
+    ($out:expr, $fmt:expr) => {
         {
             info!(concat!("=> ", $fmt));
-            println!($fmt);
+            writeln!($out, $fmt).expect("failed to write UCI output");
         }
     };
-    ($fmt:expr, $($arg:tt)*) => {
+    ($out:expr, $fmt:expr, $($arg:tt)*) => {
         {
             info!(concat!("=> ", $fmt), $($arg)*);
-            println!($fmt, $($arg)*);
+            writeln!($out, $fmt, $($arg)*).expect("failed to write UCI output");
         }
     };
 }
 
-pub struct UciServer {
+/// The depth limit for searches that the GUI doesn't give one.
+const MAX_DEPTH: u32 = 12;
+
+pub struct UciServer<W = io::Stdout> {
     book: Option<OpeningBook>,
     pos: Position,
-    search: Searcher<ShannonEvaluator>,
+    search: Searcher<DynamicEvaluator>,
+    out: W,
 }
 
 impl UciServer {
     pub fn new(book: Option<OpeningBook>) -> UciServer {
+        UciServer::with_output(book, io::stdout())
+    }
+}
+
+impl<W: Write> UciServer<W> {
+    /// Constructs a new UciServer that writes all of its output to the given sink instead of
+    /// stdout.
+    pub fn with_output(book: Option<OpeningBook>, out: W) -> UciServer<W> {
         UciServer {
             book: book,
             pos: Position::new(),
             search: Default::default(),
+            out,
         }
     }
 
@@ -50,97 +65,260 @@ impl UciServer {
         let stdin = locked_stdin.lock();
         for maybe_line in stdin.lines() {
             let line = maybe_line?;
-            info!("<= {}", line);
-            let components: Vec<_> = line.split_whitespace().collect();
-            let (&first, rest) = components.split_first().unwrap_or((&"", &[]));
-            match (first, rest) {
-                ("uci", []) => self.handle_uci(),
-                ("isready", []) => uci_println!("readyok"),
-                ("debug", ["on"]) => {}
-                ("debug", ["off"]) => {}
-                ("quit", []) => return Ok(()),
-                ("ucinewgame", []) => {
-                    info!("clearing search tables");
-                    self.search = Searcher::new(self.book.clone());
-                }
-                ("position", args) => self.handle_position(args),
-                ("go", args) => self.handle_go(args),
-                ("stop", []) => {}
-                _ => uci_println!("unrecognized command"),
+            if !self.handle_line(&line) {
+                return Ok(());
             }
         }
 
         Ok(())
     }
 
-    fn handle_uci(&self) {
+    /// Handles a single line of input from the GUI. Returns false if the engine should exit.
+    fn handle_line(&mut self, line: &str) -> bool {
+        info!("<= {}", line);
+        let components: Vec<_> = line.split_whitespace().collect();
+        let (&first, rest) = components.split_first().unwrap_or((&"", &[]));
+        match (first, rest) {
+            ("uci", []) => self.handle_uci(),
+            // The GUI sends isready to synchronize with the engine, which must always answer
+            // with readyok once it is done processing all prior commands.
+            ("isready", []) => uci_println!(self.out, "readyok"),
+            // Apollo doesn't require registration, so "register later", "register name ..."
+            // and friends are all accepted and ignored.
+            ("register", _) => {}
+            ("debug", ["on"]) => {}
+            ("debug", ["off"]) => {}
+            ("quit", []) => return false,
+            ("ucinewgame", []) => {
+                info!("clearing search tables");
+                self.search = Searcher::new(self.book.clone());
+            }
+            ("position", args) => self.handle_position(args),
+            ("go", args) => self.handle_go(args),
+            ("setoption", args) => self.handle_setoption(args),
+            ("stop", []) => {}
+            _ => uci_println!(self.out, "unrecognized command"),
+        }
+
+        true
+    }
+
+    fn handle_uci(&mut self) {
         uci_println!(
+            self.out,
             "id name {} {}",
             env!("CARGO_PKG_NAME"),
             env!("CARGO_PKG_VERSION")
         );
-        uci_println!("id author {}", env!("CARGO_PKG_AUTHORS"));
-        uci_println!("uciok");
+        uci_println!(self.out, "id author {}", env!("CARGO_PKG_AUTHORS"));
+        uci_println!(self.out, "option name Clear Hash type button");
+        uci_println!(
+            self.out,
+            "option name Evaluator type combo default Shannon var Shannon var Material"
+        );
+        uci_println!(self.out, "uciok");
     }
 
-    fn handle_position(&mut self, slice: &[&str]) {
-        let move_idx = slice
-            .into_iter()
-            .position(|&idx| idx == "moves")
-            .unwrap_or(slice.len() - 1);
-        let moves = &slice[move_idx + 1..];
+    fn handle_setoption(&mut self, args: &[&str]) {
+        // setoption name <id> [value <x>], where both the id and the value may contain spaces.
+        let value_idx = args
+            .iter()
+            .position(|&arg| arg == "value")
+            .unwrap_or(args.len());
+        let name = match args.split_first() {
+            Some((&"name", _)) => args[1..value_idx].join(" "),
+            _ => {
+                uci_println!(self.out, "invalid setoption command");
+                return;
+            }
+        };
+        let value = args.get(value_idx + 1..).unwrap_or(&[]).join(" ");
 
-        let fen_idx = slice.into_iter().position(|&idx| idx == "fen");
-        let startpos_idx = slice.into_iter().position(|&idx| idx == "startpos");
-        let fen = if let Some(idx) = fen_idx {
-            let s = &slice[idx + 1..move_idx + 1];
-            s.join(" ")
-        } else if let Some(_) = startpos_idx {
-            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_owned()
-        } else {
-            uci_println!("invalid position command");
-            return;
+        // Option names are case-insensitive, and so are the values of combo options.
+        match (name.to_lowercase().as_str(), value.to_lowercase().as_str()) {
+            ("clear hash", _) => {
+                info!("clearing transposition table");
+                self.search.clear_hash();
+            }
+            ("evaluator", "shannon") => {
+                info!("switching to the Shannon evaluator");
+                self.search
+                    .set_evaluator(DynamicEvaluator::new(ShannonEvaluator::new()));
+            }
+            ("evaluator", "material") => {
+                info!("switching to the material evaluator");
+                self.search
+                    .set_evaluator(DynamicEvaluator::new(MaterialEvaluator::new()));
+            }
+            ("evaluator", _) => uci_println!(self.out, "invalid evaluator {}", value),
+            _ => info!("ignoring unknown option: {}", name),
+        }
+    }
+
+    fn handle_position(&mut self, args: &[&str]) {
+        // The position is either "startpos" or "fen" followed by the FEN's fields, which the
+        // tokenizer has split apart, and then optionally "moves" followed by a list of moves.
+        let (setup, moves) = match args.iter().position(|&arg| arg == "moves") {
+            Some(idx) => (&args[..idx], &args[idx + 1..]),
+            None => (args, &[][..]),
         };
 
-        self.pos = if let Ok(pos) = Position::from_fen(&fen) {
-            pos
-        } else {
-            uci_println!("invalid fen");
-            return;
+        self.pos = match setup.split_first() {
+            Some((&"startpos", [])) => Position::from_start_position(),
+            Some((&"fen", fields)) if !fields.is_empty() => {
+                let fen = fields.join(" ");
+                debug!("fen: {}", fen);
+                match Position::from_fen(&fen) {
+                    Ok(pos) => pos,
+                    Err(_) => {
+                        uci_println!(self.out, "invalid fen");
+                        return;
+                    }
+                }
+            }
+            _ => {
+                uci_println!(self.out, "invalid position command");
+                return;
+            }
         };
 
         debug!("moves: {:?}", moves);
-        debug!("fen: {}", fen);
+        let mut history = vec![];
         for mov in moves {
-            if let Some(parsed_move) = self.pos.move_from_uci(mov) {
-                assert!(self.pos.is_legal(parsed_move));
-                self.pos.apply_move(parsed_move);
-            } else {
-                info!("invalid move: {}", mov);
+            match self.pos.move_from_uci(mov) {
+                Some(parsed_move) if self.pos.is_legal(parsed_move) => {
+                    history.push(self.pos.zobrist_hash());
+                    self.pos.apply_move(parsed_move)
+                }
+                _ => {
+                    // Every move after this one would be interpreted against the wrong position.
+                    info!("invalid move: {}", mov);
+                    uci_println!(self.out, "invalid move {}", mov);
+                    break;
+                }
             }
         }
+
+        self.search.set_game_history(history);
     }
 
-    fn handle_go(&mut self, _: &[&str]) {
-        info!("beginning search, (depth 10, 20 second budget)");
-        let result = self.search.search(
+    fn handle_go(&mut self, args: &[&str]) {
+        let request = parse_go(args);
+        let budget = allocate_time(&request, self.pos.side_to_move());
+
+        // Without a fixed move time, stop starting new depths halfway through the time budget,
+        // since the next depth is likely to take longer than all of the previous ones put together.
+        let soft_limit = match request.movetime {
+            Some(_) => budget,
+            None => budget.map(|budget| budget / 2),
+        };
+        let hard_limit = budget;
+        let depth = request.depth.unwrap_or(MAX_DEPTH);
+
+        info!(
+            "beginning search, (depth {}, soft limit {:?}, hard limit {:?})",
+            depth, soft_limit, hard_limit
+        );
+        let observer = UciInfoObserver {
+            out: RefCell::new(&mut self.out),
+        };
+        let result = self.search.search_observed(
             &self.pos,
-            12,
-            Some(Duration::from_secs(1)),
+            depth,
+            soft_limit,
+            hard_limit,
             &NullDataRecorder,
+            &observer,
         );
         info!("move: {} ({})", result.best_move, result.score);
+        match result.ponder_move() {
+            Some(ponder) => {
+                uci_println!(self.out, "bestmove {} ponder {}", result.best_move, ponder)
+            }
+            None => uci_println!(self.out, "bestmove {}", result.best_move),
+        }
+    }
+}
 
-        let mut out = String::new();
-        write!(&mut out, "info depth 5 nodes {}", result.nodes_searched).unwrap();
-        match result.score {
-            Score::Evaluated(score) => write!(&mut out, " score cp {}", score).unwrap(),
-            Score::Win(moves) => write!(&mut out, " score mate {}", moves).unwrap(),
-            Score::Loss(moves) => write!(&mut out, " score mate -{}", moves).unwrap(),
+/// A search observer that reports the search's progress to the GUI with an `info` line for each
+/// completed depth.
+struct UciInfoObserver<W> {
+    out: RefCell<W>,
+}
+
+impl<W: Write> SearchObserver for UciInfoObserver<W> {
+    fn depth_completed(&self, info: &SearchInfo) {
+        let mut out = self.out.borrow_mut();
+        uci_println!(out, "{}", format_info(info));
+    }
+}
+
+/// Formats the given search progress as the arguments of a UCI `info` command.
+fn format_info(info: &SearchInfo) -> String {
+    let mut out = format!("info depth {}", info.depth);
+    match info.score {
+        Score::Evaluated(score) => {
+            write!(&mut out, " score cp {}", (score * 100.0).round() as i32).unwrap()
+        }
+        // Mate scores are in plies, but UCI wants them in moves.
+        Score::Win(plies) => write!(&mut out, " score mate {}", plies.div_ceil(2)).unwrap(),
+        Score::Loss(plies) => write!(&mut out, " score mate -{}", plies / 2).unwrap(),
+    }
+
+    write!(
+        &mut out,
+        " nodes {} nps {} time {}",
+        info.nodes,
+        info.nodes_per_second(),
+        info.elapsed.as_millis()
+    )
+    .unwrap();
+    if !info.pv.is_empty() {
+        out.push_str(" pv");
+        for mov in info.pv {
+            write!(&mut out, " {}", mov).unwrap();
+        }
+    }
+
+    out
+}
+
+/// Parses the arguments to a `go` command. Arguments that aren't understood are ignored.
+fn parse_go(args: &[&str]) -> SearchRequest {
+    let mut request = SearchRequest::default();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        if arg == "infinite" {
+            request.infinite = true;
+            continue;
         }
-        uci_println!("{}", out);
-        uci_println!("bestmove {}", result.best_move);
+
+        // Some GUIs send negative clock times once a player has overstepped them.
+        let value = match args
+            .clone()
+            .next()
+            .and_then(|value| value.parse::<i64>().ok())
+        {
+            Some(value) => value.max(0) as u64,
+            None => continue,
+        };
+
+        let millis = Some(Duration::from_millis(value));
+        match arg {
+            "depth" => request.depth = Some(value as u32),
+            "wtime" => request.wtime = millis,
+            "btime" => request.btime = millis,
+            "winc" => request.winc = millis,
+            "binc" => request.binc = millis,
+            "movestogo" => request.moves_to_go = Some(value as u32),
+            "movetime" => request.movetime = millis,
+            _ => continue,
+        }
+
+        args.next();
     }
+
+    request
 }
 
 impl Default for UciServer {
@@ -271,3 +449,290 @@ impl UciServer {
 }
 
 */
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_go, UciServer};
+    use crate::eval::{BoardEvaluator, MaterialEvaluator, Score};
+    use crate::position::Position;
+    use crate::search::SearchRequest;
+    use std::time::Duration;
+
+    fn server() -> UciServer<Vec<u8>> {
+        UciServer::with_output(None, vec![])
+    }
+
+    fn output(server: &UciServer<Vec<u8>>) -> Vec<String> {
+        String::from_utf8(server.out.clone())
+            .unwrap()
+            .lines()
+            .map(|l| l.to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn isready_readyok() {
+        let mut server = server();
+        assert!(server.handle_line("isready"));
+        assert_eq!(vec!["readyok"], output(&server));
+    }
+
+    #[test]
+    fn register_is_accepted() {
+        let mut server = server();
+        assert!(server.handle_line("register later"));
+        assert!(server.handle_line("register name Apollo code 1234"));
+        assert!(server.handle_line("isready"));
+        assert_eq!(vec!["readyok"], output(&server));
+    }
+
+    #[test]
+    fn readyok_is_not_a_command() {
+        // readyok is sent by the engine, never by the GUI.
+        let mut server = server();
+        assert!(server.handle_line("readyok"));
+        assert!(server.handle_line("isready"));
+        assert_eq!(vec!["unrecognized command", "readyok"], output(&server));
+    }
+
+    #[test]
+    fn uci_identifies_engine() {
+        let mut server = server();
+        assert!(server.handle_line("uci"));
+        let lines = output(&server);
+        assert!(lines[0].starts_with("id name apollo"));
+        assert!(lines[1].starts_with("id author "));
+        assert!(lines.contains(&"option name Clear Hash type button".to_owned()));
+        assert_eq!("uciok", lines.last().unwrap());
+    }
+
+    #[test]
+    fn setoption_clear_hash() {
+        let mut server = server();
+        assert!(server.handle_line("position startpos"));
+        assert!(server.handle_line("go depth 2"));
+        assert!(!server.search.transposition_table().is_empty());
+
+        assert!(server.handle_line("setoption name Clear Hash"));
+        assert_eq!(0, server.search.transposition_table().len());
+    }
+
+    #[test]
+    fn setoption_evaluator() {
+        // White is up a rook, and no captures are available. At depth 1, the material evaluator
+        // scores every move the same as the current position.
+        let fen = "4k3/4p3/8/8/8/8/4P3/R3K3 w - - 0 1";
+        let expected = match MaterialEvaluator::new().evaluate(&Position::from_fen(fen).unwrap()) {
+            Score::Evaluated(score) => score,
+            score => panic!("unexpected material score {}", score),
+        };
+        let expected = (expected * 100.0).round() as i32;
+
+        let mut shannon_server = server();
+        assert!(shannon_server.handle_line(&format!("position fen {}", fen)));
+        assert!(shannon_server.handle_line("go depth 1"));
+        let shannon = output(&shannon_server);
+        assert!(!shannon[0].contains(&format!(" score cp {} ", expected)));
+
+        let mut server = server();
+        assert!(server.handle_line("setoption name Evaluator value Material"));
+        assert!(server.handle_line(&format!("position fen {}", fen)));
+        assert!(server.handle_line("go depth 1"));
+        let material = output(&server);
+        assert!(
+            material[0].contains(&format!(" score cp {} ", expected)),
+            "{}",
+            material[0]
+        );
+
+        assert!(server.handle_line("setoption name Evaluator value Bogus"));
+        assert_eq!(
+            vec!["invalid evaluator Bogus"],
+            output(&server)[2..].to_vec()
+        );
+    }
+
+    #[test]
+    fn go_depth_one_bestmove() {
+        let mut server = server();
+        assert!(server.handle_line("position startpos moves e2e4"));
+        assert!(server.handle_line("go depth 1"));
+        let lines = output(&server);
+        assert_eq!(2, lines.len());
+        assert!(lines[0].starts_with("info depth 1 "));
+        let components: Vec<_> = lines[1].split_whitespace().collect();
+        assert_eq!(2, components.len());
+        assert_eq!("bestmove", components[0]);
+        let mov = server.pos.move_from_uci(components[1]).unwrap();
+        assert!(server.pos.is_legal(mov));
+    }
+
+    #[test]
+    fn go_reports_each_depth() {
+        let mut server = server();
+        assert!(server.handle_line("position startpos"));
+        assert!(server.handle_line("go depth 3"));
+        let lines = output(&server);
+        assert_eq!(4, lines.len());
+        for (depth, line) in lines[..3].iter().enumerate() {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            assert_eq!(&["info", "depth"], &fields[..2]);
+            assert_eq!((depth + 1).to_string(), fields[2]);
+            assert_eq!(&["score", "cp"], &fields[3..5]);
+            assert_eq!(&["nodes"], &fields[6..7]);
+            assert_eq!(&["nps"], &fields[8..9]);
+            assert_eq!(&["time"], &fields[10..11]);
+            assert_eq!("pv", fields[12]);
+            assert!(fields.len() > 13);
+        }
+
+        // The last line's principal variation begins with the move that the engine plays.
+        let best_move = lines[3].split_whitespace().nth(1).unwrap();
+        assert_eq!(Some(best_move), lines[2].split_whitespace().nth(13));
+    }
+
+    #[test]
+    fn go_reports_mate_in_moves() {
+        let mut server = server();
+        assert!(server.handle_line("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"));
+        assert!(server.handle_line("go depth 2"));
+        let lines = output(&server);
+        assert!(
+            lines[1].starts_with("info depth 2 score mate 1 "),
+            "{}",
+            lines[1]
+        );
+        assert!(lines[1].ends_with(" pv a1a8"), "{}", lines[1]);
+        assert_eq!("bestmove a1a8", lines[2]);
+    }
+
+    #[test]
+    fn go_reports_ponder_move() {
+        let mut server = server();
+        assert!(server.handle_line("position startpos"));
+        assert!(server.handle_line("go depth 3"));
+        let lines = output(&server);
+        let components: Vec<_> = lines.last().unwrap().split_whitespace().collect();
+        assert_eq!(4, components.len());
+        assert_eq!("bestmove", components[0]);
+        assert_eq!("ponder", components[2]);
+    }
+
+    #[test]
+    fn position_rejects_illegal_move() {
+        let mut server = server();
+        assert!(server.handle_line("position startpos moves e2e4 e2e4 e7e5"));
+        assert_eq!(vec!["invalid move e2e4"], output(&server));
+        assert_eq!(1, server.pos.move_history().len());
+    }
+
+    #[test]
+    fn position_startpos() {
+        let mut server = server();
+        assert!(server.handle_line("position startpos"));
+        assert_eq!(
+            Position::from_start_position().as_fen(),
+            server.pos.as_fen()
+        );
+
+        assert!(server.handle_line("position startpos moves e2e4 e7e5"));
+        assert_eq!(
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+            server.pos.as_fen()
+        );
+        assert!(output(&server).is_empty());
+    }
+
+    #[test]
+    fn position_fen() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut server = server();
+        assert!(server.handle_line(&format!("position fen {}", fen)));
+        assert_eq!(fen, server.pos.as_fen());
+
+        assert!(server.handle_line(&format!("position fen {} moves e1g1 a6e2", fen)));
+        assert_eq!(
+            "r3k2r/p1ppqpb1/1n2pnp1/3PN3/1p2P3/2N2Q1p/PPPBbPPP/R4RK1 w kq - 0 2",
+            server.pos.as_fen()
+        );
+        assert!(output(&server).is_empty());
+    }
+
+    #[test]
+    fn position_malformed() {
+        let mut server = server();
+        assert!(server.handle_line("position"));
+        assert!(server.handle_line("position fen moves e2e4"));
+        assert!(server.handle_line("position startpos e2e4"));
+        assert_eq!(
+            vec![
+                "invalid position command",
+                "invalid position command",
+                "invalid position command"
+            ],
+            output(&server)
+        );
+    }
+
+    #[test]
+    fn go_clock() {
+        let request = parse_go(&[
+            "wtime",
+            "60000",
+            "btime",
+            "-200",
+            "winc",
+            "1000",
+            "binc",
+            "1000",
+            "movestogo",
+            "20",
+        ]);
+        assert_eq!(
+            SearchRequest {
+                wtime: Some(Duration::from_secs(60)),
+                btime: Some(Duration::from_secs(0)),
+                winc: Some(Duration::from_secs(1)),
+                binc: Some(Duration::from_secs(1)),
+                moves_to_go: Some(20),
+                ..Default::default()
+            },
+            request
+        );
+
+        let request = parse_go(&["infinite", "searchmoves", "e2e4", "movetime", "500"]);
+        assert!(request.infinite);
+        assert_eq!(Some(Duration::from_millis(500)), request.movetime);
+    }
+
+    #[test]
+    fn go_movetime() {
+        let mut server = server();
+        assert!(server.handle_line("position startpos"));
+        assert!(server.handle_line("go movetime 50"));
+        assert!(output(&server).last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn go_with_almost_no_time() {
+        // Less time is left than the move overhead, but the engine still has to play a legal move.
+        for command in &["go wtime 20 btime 20", "go movetime 1"] {
+            let mut server = server();
+            assert!(server.handle_line("position startpos"));
+            assert!(server.handle_line(command));
+            let output = output(&server);
+            let best_move = output.last().unwrap().split_whitespace().nth(1).unwrap();
+            let legal = server
+                .pos
+                .move_from_uci(best_move)
+                .map_or(false, |mov| server.pos.is_legal(mov));
+            assert!(legal, "{}: bestmove {}", command, best_move);
+        }
+    }
+
+    #[test]
+    fn quit_stops_server() {
+        let mut server = server();
+        assert!(!server.handle_line("quit"));
+    }
+}